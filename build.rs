@@ -0,0 +1,24 @@
+//! Embeds the git commit and the cargo profile of the build for
+//! `--version-json`.
+
+use std::env;
+use std::process::Command;
+
+fn main() {
+    let commit = Command::new("git")
+        .args(&["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=AITIOS_GIT_COMMIT={}", commit.trim());
+    }
+
+    if let Ok(profile) = env::var("PROFILE") {
+        println!("cargo:rustc-env=AITIOS_BUILD_PROFILE={}", profile);
+    }
+
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
@@ -1,19 +1,71 @@
-use clap::{App, Arg};
+use app::target_filter::parse_target_level;
+use clap::{App, AppSettings, Arg, SubCommand};
+use files::ArchiveFormat;
+use runner::BAKE_FILTER_NAMES;
 
 pub fn new_app<'a, 'b>() -> App<'a, 'b> {
     App::new("aitios")
         .version(crate_version!())
         .author("krachzack <hello@phstadler.com>")
         .about("Procedural weathering simulation on the command line with aitios")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(
+            SubCommand::with_name("validate")
+                .about("Loads a simulation and checks its input assets and output directories without simulating.")
+                .long_about("Loads a simulation and checks that all input assets can be loaded and all output directories can be written to, then lists the files the simulation would produce. Exits unsuccessfully if any problem was found.")
+                .arg(
+                    Arg::with_name("SIMULATION_SPEC_FILE")
+                        .help("Adds a new simulation specification fragment in a YAML or TOML file at the given path.")
+                        .required(true)
+                        .validator(validate_simulation_spec)
+                        .multiple(true)
+                        .takes_value(true)
+                )
+                .arg(
+                    Arg::with_name("spec")
+                        .short("s")
+                        .long("spec")
+                        .multiple(true)
+                        .takes_value(true)
+                        .help("Evaluates the given simulation spec directly")
+                        .value_name("INLINE_SIMULATION_SPEC")
+                )
+                .arg(
+                    Arg::with_name("max-problems")
+                        .long("max-problems")
+                        .takes_value(true)
+                        .value_name("COUNT")
+                        .default_value("10")
+                        .validator(validate_max_problems)
+                        .help("Maximum number of problems to list.")
+                )
+        )
         .arg(
             Arg::with_name("SIMULATION_SPEC_FILE")
-                .help("Adds a new simulation specification fragment in a YAML file at the given path.")
-                .long_help("Adds a new simulation specification fragment in a YAML file at the given path. Multiple specs can be provided and later specs will add to or even override earlier specs, depending on the property. See --spec to provide an inline specification without a file.")
-                .required(true)
+                .help("Adds a new simulation specification fragment in a YAML or TOML file at the given path.")
+                .long_help("Adds a new simulation specification fragment in a YAML or TOML file at the given path. Files ending in .toml are read as TOML, all others as YAML. Multiple specs can be provided and later specs will add to or even override earlier specs, depending on the property. Relative inputs are resolved against the directory of the spec first and the working directory second, relative outputs are written next to the spec, or below --output-dir if given. If the path is -, the spec is read from stdin and relative paths in it are resolved against the working directory. Gzipped specs, e.g. sim.yml.gz or gzipped data on stdin, are decompressed automatically, and the format of specs on stdin is detected from their first line. See --spec to provide an inline specification without a file.")
+                .required_unless_one(&["list-effects", "dump-config-schema", "version-json"])
                 .validator(validate_simulation_spec)
                 .multiple(true)
                 .takes_value(true)
         )
+        .arg(
+            Arg::with_name("list-effects")
+                .long("list-effects")
+                .help("Lists the kinds of effects that can be used in simulation specs and exits.")
+        )
+        .arg(
+            Arg::with_name("version-json")
+                .long("version-json")
+                .help("Prints the version, git commit and build profile as JSON and exits.")
+                .long_help("Prints a JSON object with the version, the git commit the binary was built from, or null if unknown, and the cargo build profile, e.g. release, and exits. Unlike --version, the output is meant to be read by tools.")
+        )
+        .arg(
+            Arg::with_name("dump-config-schema")
+                .long("dump-config-schema")
+                .help("Prints a JSON Schema of simulation specs and exits.")
+                .long_help("Prints a JSON Schema of simulation specs to stdout and exits, e.g. for editors to validate and autocomplete specs. The schema is derived from the types specs are read into, so it always matches the accepted spec format. Spec files are neither required nor read.")
+        )
         .arg(
             Arg::with_name("spec")
                 .short("s")
@@ -31,6 +83,13 @@ pub fn new_app<'a, 'b>() -> App<'a, 'b> {
                 .multiple(true)
                 .help("Activates verbose output.")
         )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .conflicts_with("verbose")
+                .help("Silences all terminal output except for errors that terminate the program, logs are still written to log files.")
+        )
         .arg(
             Arg::with_name("log")
                 .short("l")
@@ -41,6 +100,80 @@ pub fn new_app<'a, 'b>() -> App<'a, 'b> {
                 .max_values(64)
                 .value_name("LOG_FILE")
                 .help("Specifies a file in which to log simulation progress.")
+                .long_help("Specifies a file in which to log simulation progress. In the file name, {datetime} is replaced with the start time of the simulation and {spec_name} with the stem of the first spec file, e.g. a for a.yml, or spec if no spec file was given. Without a file name, a log with a default name is added below the working directory, see --no-default-log.")
+        )
+        .arg(
+            Arg::with_name("no-default-log")
+                .long("no-default-log")
+                .help("Does not add a log file with a default name for a -l without a file name.")
+                .long_help("Does not add a log file with a default name below the working directory when -l is given without a file name, e.g. to only raise the verbosity of the terminal. Has no effect if every -l is given a file name.")
+        )
+        .arg(
+            Arg::with_name("log-level")
+                .long("log-level")
+                .takes_value(true)
+                .value_name("LEVEL")
+                .possible_values(&["off", "error", "warn", "info", "debug", "trace"])
+                .help("Sets the level of messages written to log files, defaults to the terminal level set with -v.")
+        )
+        .arg(
+            Arg::with_name("verbose-target")
+                .long("verbose-target")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("TARGET=LEVEL")
+                .validator(validate_verbose_target)
+                .help("Sets the log level for a single module and its submodules, e.g. aitios_surf=trace. Can be used multiple times.")
+                .long_help("Sets the log level for a single log target, usually a module path like aitios_surf or aitios_cli::runner, and its submodules, e.g. aitios_surf=trace to debug surfel generation while everything else is logged at the level set with -v or --log-level. Can be used multiple times, the most specific matching target wins. Applies to the terminal, log files and syslog, but loggers that are off, e.g. the terminal with --quiet, stay off.")
+        )
+        .arg(
+            Arg::with_name("log-timezone")
+                .long("log-timezone")
+                .takes_value(true)
+                .value_name("TIMEZONE")
+                .possible_values(&["local", "utc"])
+                .default_value("local")
+                .help("Sets the timezone of timestamps in file logs and log file names.")
+                .long_help("Sets the timezone of timestamps in file logs, JSON terminal logs and {datetime} in log file names. File log timestamps are written in RFC 3339 format with milliseconds and the offset of the timezone, e.g. 2018-01-26T18:30:09.453+00:00.")
+        )
+        .arg(
+            Arg::with_name("syslog")
+                .long("syslog")
+                .help("Additionally logs to the local syslog daemon.")
+                .long_help("Additionally logs to the local syslog daemon via its Unix socket, with the level given with --log-level. Not available on Windows.")
+        )
+        .arg(
+            Arg::with_name("syslog-facility")
+                .long("syslog-facility")
+                .takes_value(true)
+                .value_name("FACILITY")
+                .possible_values(&[
+                    "user", "daemon", "local0", "local1", "local2", "local3", "local4", "local5",
+                    "local6", "local7",
+                ])
+                .default_value("user")
+                .help("Sets the syslog facility for --syslog.")
+        )
+        .arg(
+            Arg::with_name("log-format")
+                .long("log-format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&["human", "json"])
+                .default_value("human")
+                .help("Sets the format of terminal and file logs.")
+                .long_help("Sets the format of terminal and file logs. With json, every log record is written as a JSON object on its own line, with the fields timestamp, level, target and message. JSON terminal logs are written to stderr.")
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .takes_value(true)
+                .value_name("WHEN")
+                .possible_values(&["auto", "always", "never"])
+                .default_value("auto")
+                .help("Sets whether level labels in terminal logs are colorized.")
+                .long_help("Sets whether level labels in human readable terminal logs are colorized. With auto, logs are colorized only if stdout is a terminal. If the NO_COLOR environment variable is set to a non-empty value, auto turns into never, but always still colorizes.")
         )
         .arg(
             Arg::with_name("threads")
@@ -49,7 +182,468 @@ pub fn new_app<'a, 'b>() -> App<'a, 'b> {
                 .takes_value(true)
                 .value_name("THREAD_COUNT")
                 .validator(validate_thread_count)
-                .help("Overrides thread pool size from number of virtual processors to the given thread count.")
+                .help("Overrides thread pool size from number of virtual processors, or the CPU quota when running in a container, to the given thread count.")
+                .long_help("Overrides thread pool size from number of virtual processors, or the CPU quota when running in a container, to the given thread count. If not given, a positive thread count in the AITIOS_THREADS environment variable is used instead.")
+        )
+        .arg(
+            Arg::with_name("region-mask")
+                .long("region-mask")
+                .takes_value(true)
+                .value_name("MASK_IMAGE")
+                .help("Restricts weathering to the bright regions of the given image in UV space.")
+                .long_help("Restricts weathering to the bright regions of the given image in UV space. Triangles where the mask is darker than 50% are still simulated and exported, but their surfels keep their initial concentrations. Overrides a region mask set in the simulation spec.")
+        )
+        .arg(
+            Arg::with_name("object")
+                .long("object")
+                .visible_alias("target-object")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("OBJECT_NAME")
+                .help("Restricts the simulation to the scene objects with the given name. Can be used multiple times.")
+                .long_help("Restricts the simulation to the scene objects with the given name. Can be used multiple times to select more than one object. Objects are matched by their OBJ object or group name, and selecting a name that is not in any scene fails with a list of the available names. Only selected objects get surfels and hence receive deposited substances. Objects that are not selected are not simulated and not exported, see --object-as-occluder to keep them as obstacles for gammatons.")
+        )
+        .arg(
+            Arg::with_name("only-substance")
+                .long("only-substance")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("NAME")
+                .help("Bakes density textures only for the substance with the given name. Can be used multiple times.")
+                .long_help("Restricts the textures written by density effects to the substances with the given names, e.g. to debug a single weathering effect. Can be used multiple times to select more than one substance. All substances are still simulated, and layer effects are not affected. Fails if a name is not a substance of the spec.")
+        )
+        .arg(
+            Arg::with_name("object-as-occluder")
+                .long("object-as-occluder")
+                .requires("object")
+                .help("Keeps objects not selected with --object as obstacles for gammatons, without weathering or exporting them.")
+        )
+        .arg(
+            Arg::with_name("warn-unused-substances")
+                .long("warn-unused-substances")
+                .help("Warns about substances that are never emitted, initially present or produced by a rule.")
+        )
+        .arg(
+            Arg::with_name("first-hit-only")
+                .long("first-hit-only")
+                .help("Settles gammatons on the first surface they hit, disabling bounces.")
+                .long_help("Settles gammatons on the first surface they hit by overriding p_straight, p_parabolic and p_flow of all gammaton sources with zero. This is considerably faster than multi-bounce transport and only produces direct weathering.")
+        )
+        .arg(
+            Arg::with_name("bake-filter")
+                .long("bake-filter")
+                .takes_value(true)
+                .value_name("FILTER")
+                .possible_values(&BAKE_FILTER_NAMES)
+                .help("Overrides the filter used to bake surfel concentrations into density textures.")
+                .long_help("Overrides the filter used to bake surfel concentrations into density textures, replacing flat_filtering in the spec. nearest takes the concentration of the nearest surfel of each texel, resulting in sharp but blocky textures. linear and gaussian average the surfels of each texel within --bake-radius, weighting closer surfels higher, where gaussian falls off more smoothly. Texels without surfels within the radius take their nearest surfel.")
+        )
+        .arg(
+            Arg::with_name("bake-radius")
+                .long("bake-radius")
+                .takes_value(true)
+                .value_name("FLOAT")
+                .requires("bake-filter")
+                .validator(validate_bake_radius)
+                .help("Radius of linear and gaussian bake filters, defaults to the surfel distance.")
+                .long_help("Radius around each texel within which surfels are averaged by linear and gaussian bake filters. Larger radii give smoother textures. Defaults to the surfel distance of the simulation and has no effect on nearest filtering. Must be a positive number.")
+        )
+        .arg(
+            Arg::with_name("output-bit-depth")
+                .long("output-bit-depth")
+                .takes_value(true)
+                .value_name("BITS")
+                .possible_values(&["8", "16"])
+                .help("Overrides the bits per channel of density textures written as PNG.")
+                .long_help("Overrides the bits per channel of density textures written as PNG, replacing output_bit_depth in the spec, which defaults to 8. With 16, concentrations are mapped to the full range of 65536 gray values, avoiding the banding of 8 bit textures. Textures written as OpenEXR always store unquantized floats, and layer textures keep the depth of their sample textures.")
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("Fails instead of warning about problems with the spec, e.g. a mismatching spec version or unused substances.")
+        )
+        .arg(
+            Arg::with_name("fail-on-warnings")
+                .long("fail-on-warnings")
+                .help("Exits with an error if any warnings were logged during the run.")
+                .long_help("Exits with an error if any warnings or errors were logged during the run, e.g. for CI. The number of warnings is always reported at the end of a run. With --batch, the warnings of each spec are counted on their own and a spec that warned counts as failed, and with --watch, each run is checked on its own. Unlike --strict, the run is not aborted at the first warning and all outputs are still written, but the failure hooks run instead of the completion hooks.")
+        )
+        .arg(
+            Arg::with_name("print-bounds")
+                .long("print-bounds")
+                .help("Prints bounding box and surface area of the scene and exits without simulating.")
+                .long_help("Loads the scene and prints its axis-aligned bounding box, total surface area and how the surfel distance relates to it, then exits without simulating. Surfels are not generated for this, so the printed surfel count is estimated from the surface area and the surfel distance. Useful to check if the units of the scene match the physical parameters in the spec.")
+        )
+        .arg(
+            Arg::with_name("retry")
+                .long("retry")
+                .takes_value(true)
+                .value_name("RETRIES")
+                .validator(validate_retry_count)
+                .help("Retries writing output files up to the given number of times after transient I/O errors.")
+                .long_help("Retries writing output files up to the given number of times after transient I/O errors, e.g. timeouts or interruptions on network file systems. Errors like missing permissions or a full disk fail immediately.")
+        )
+        .arg(
+            Arg::with_name("retry-delay")
+                .long("retry-delay")
+                .takes_value(true)
+                .value_name("MILLISECONDS")
+                .default_value("1000")
+                .validator(validate_retry_delay)
+                .help("Time to wait before retrying to write an output file.")
+        )
+        .arg(
+            Arg::with_name("describe")
+                .long("describe")
+                .takes_value(true)
+                .value_name("DETAIL")
+                .possible_values(&["summary", "full", "none"])
+                .default_value("summary")
+                .help("Controls how much of the simulation description is logged before running.")
+                .long_help("Controls how much of the simulation description is logged before running. summary logs counts of entities, iterations, surfels and substances, full additionally lists scenes and substance names, and none logs no description at all.")
+        )
+        .arg(
+            Arg::with_name("plan")
+                .long("plan")
+                .help("Prints the stages, estimated time and memory, and output files of the simulation and exits without simulating.")
+                .long_help("Prints the stages, estimated time and memory, and output files with estimated sizes of the simulation and exits without simulating. The estimates are derived from gammaton, surfel and texel counts with simple models and only give an order of magnitude.")
+        )
+        .arg(
+            Arg::with_name("explain")
+                .long("explain")
+                .conflicts_with_all(&["plan", "print-bounds"])
+                .help("Describes what the simulation will do in plain words and exits without simulating.")
+                .long_help("Describes what the simulation will do in plain words after loading it and exits without simulating. The explanation lists the number of iterations, the gammaton sources with the substances they deposit, the materials whose surfels receive weathering, the iterations after which effects are performed and the files they write.")
+        )
+        .arg(
+            Arg::with_name("plan-format")
+                .long("plan-format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("Format of the report printed by --plan.")
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Loads the simulation and logs its description, but exits without simulating.")
+        )
+        .arg(
+            Arg::with_name("bench")
+                .long("bench")
+                .conflicts_with_all(&["dry-run", "checkpoint-every", "snapshot-every", "emit-surfel-cloud", "archive"])
+                .help("Only traces the simulation and prints the throughput in gammatons and iterations per second.")
+                .long_help("Runs the simulation without performing effects or writing benchmarks, checkpoints or snapshots, to measure tracing and substance transport in isolation. After the run, a single line is printed to stdout in the form: bench gammatons_per_second=F iterations_per_second=F gammatons=N iterations=N seconds=F. Preheat iterations are included in the counts.")
+        )
+        .arg(
+            Arg::with_name("print-config")
+                .long("print-config")
+                .help("Prints the effective command line configuration as JSON and exits without simulating.")
+                .long_help("Prints the effective command line configuration as JSON and exits without simulating. This includes the resolved thread count, verbosity, log files and all other settings after applying defaults, but not the simulation spec itself.")
+        )
+        .arg(
+            Arg::with_name("print-spec")
+                .long("print-spec")
+                .conflicts_with("print-config")
+                .help("Prints the effective simulation spec as YAML and exits without simulating.")
+                .long_help("Prints the effective simulation spec as YAML and exits without simulating. The spec is printed after merging all spec files, inline specs and includes, expanding environment variables and applying --set, --iterations, --surfel-density as surfel_distance, --texture-resolution as the size of all textures and --skip-outputs, which drops the benchmark. Options that only take effect when building the simulation, such as --emission-count, --wind, --first-hit-only, --object, --substances, --triangle-limit and --bake-filter, are not reflected in the printed spec. Relative input paths are printed resolved.")
+        )
+        .arg(
+            Arg::with_name("list-substances")
+                .long("list-substances")
+                .conflicts_with_all(&["print-config", "print-spec"])
+                .help("Prints the parameters of all substances of the spec and exits without simulating.")
+                .long_help("Loads the gammaton sources and surfel specs of the simulation and prints a table with a row for every substance in every source or material that mentions it, then exits without simulating. Rows show the initial concentration, the rate at which bouncing gammatons absorb the substance for sources or at which settling gammatons deposit it for materials, and surfel rules like deterioration that change it. Overrides with --set are applied before listing.")
+        )
+        .arg(
+            Arg::with_name("check-assets")
+                .long("check-assets")
+                .help("Opens and parses all input assets and reports those that fail, without simulating.")
+                .long_help("Opens and parses all input assets referenced by the spec, including scenes, material textures, gammaton source and surfel specs, emission meshes, blend samples and the region mask. All assets that fail to load are reported together and the program exits without simulating, unsuccessfully if any asset failed.")
+        )
+        .arg(
+            Arg::with_name("on-complete")
+                .long("on-complete")
+                .takes_value(true)
+                .value_name("COMMAND")
+                .help("Runs the given shell command after a successful run.")
+                .long_help("Runs the given shell command after a successful run. The environment variables AITIOS_STATUS, AITIOS_EXIT_STATUS, AITIOS_DURATION (in seconds) and AITIOS_OUTPUT_DIR describe the run. AITIOS_OUTPUT_DIR is the absolute directory given with --output-dir with its placeholders expanded, or the working directory without it. If the simulations of a batch or of --watch wrote into different directories, it is the innermost directory containing all of them.")
+        )
+        .arg(
+            Arg::with_name("on-failure")
+                .long("on-failure")
+                .takes_value(true)
+                .value_name("COMMAND")
+                .help("Runs the given shell command after a failed run.")
+                .long_help("Runs the given shell command after a failed run. In addition to the environment variables of --on-complete, AITIOS_ERROR contains the error message.")
+        )
+        .arg(
+            Arg::with_name("hook-strict")
+                .long("hook-strict")
+                .help("Exits unsuccessfully if an --on-complete or --on-failure command fails.")
+        )
+        .arg(
+            Arg::with_name("set")
+                .long("set")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("KEY=VALUE")
+                .validator(validate_assignment)
+                .help("Sets a single field of the simulation spec, e.g. --set iterations=20.")
+                .long_help("Sets a single field of the combined simulation spec after all spec files and inline specs have been loaded. The key is a dotted path into the spec, where numbers index into lists, e.g. benchmark.iterations or effects.0.density.width. The value is parsed as YAML. Only fields that exist in the spec format can be set, and lists are replaced rather than appended to. Can be given multiple times.")
+        )
+        .arg(
+            Arg::with_name("batch")
+                .long("batch")
+                .help("Runs each simulation spec file separately, one after another, instead of combining them.")
+                .long_help("Runs each simulation spec file separately, one after another, instead of combining them into a single simulation. Inline specs are applied to each of the spec files. Each simulation has its own start time and spec name, so use {datetime} or {spec_name} in log file names to get separate logs, e.g. -l logs/{spec_name}-{datetime}.log. A failed simulation does not stop the batch unless --fail-fast is given. A summary of succeeded and failed specs is logged at the end.")
+        )
+        .arg(
+            Arg::with_name("watch")
+                .long("watch")
+                .conflicts_with("batch")
+                .help("Runs the simulation again whenever the spec files or the assets they reference change.")
+                .long_help("Runs the simulation again whenever one of the spec files or one of the scenes, gammaton sources or surfel specs they reference change, until interrupted with Ctrl-C. Changes while a simulation is running are picked up after it finished. Use {datetime} in log file names to keep the logs of earlier runs.")
+        )
+        .arg(
+            Arg::with_name("fail-fast")
+                .long("fail-fast")
+                .requires("batch")
+                .help("Stops a batch after the first failed simulation.")
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .takes_value(true)
+                .value_name("N")
+                .requires("batch")
+                .validator(validate_job_count)
+                .help("Runs up to the given number of simulations of a batch at the same time.")
+                .long_help("Runs up to the given number of simulations of a batch at the same time, each on an equal share of the threads. Each simulation still logs into its own log files, while progress of the batch is only logged to the terminal. A failed simulation does not affect the others, with --fail-fast no further simulations are started but those already running are finished. Defaults to 1, running simulations one after another.")
+        )
+        .arg(
+            Arg::with_name("continue-on-error")
+                .long("continue-on-error")
+                .requires("batch")
+                .conflicts_with("fail-fast")
+                .help("Runs all simulations of a batch even if some fail, which is the default.")
+                .long_help("Runs all simulations of a batch even if some of them fail, and exits unsuccessfully at the end if any failed. This is the default for --batch, the flag makes it explicit, e.g. in scripts. Either way, a summary of succeeded and failed specs is logged after the batch.")
+        )
+        .arg(
+            Arg::with_name("output-dir")
+                .long("output-dir")
+                .takes_value(true)
+                .value_name("DIRECTORY")
+                .help("Writes all outputs of the spec with relative paths below the given directory, which may contain {spec} and {datetime}.")
+                .long_help("Prepends the given directory to all relative output paths of the simulation spec, i.e. to the outputs of effects, benchmarks, the spec log, checkpoints and snapshots, so a run can be redirected without editing the spec. Outputs relative to the directory of the spec file are written below the given directory instead. The directory may be a template, e.g. runs/{spec}/{datetime}, where {spec} is replaced with the file name of the first spec file without extension and {datetime} with the time the spec was loaded, as in log file names, so each spec of a batch gets its own directory. The directory and its parents are created before simulating, but it is an error if the directory already exists, e.g. because an earlier run or another spec of the batch resolved to the same directory, unless --force is given. Absolute paths and files given on the command line are not affected.")
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .requires("output-dir")
+                .help("Writes into the directory given with --output-dir even if it already exists.")
+        )
+        .arg(
+            Arg::with_name("iterations")
+                .long("iterations")
+                .takes_value(true)
+                .value_name("N")
+                .validator(validate_iterations)
+                .help("Overrides the number of iterations set in the simulation spec.")
+        )
+        .arg(
+            Arg::with_name("no-clobber")
+                .long("no-clobber")
+                .conflicts_with("overwrite")
+                .help("Refuses to run if any output file of the spec already exists.")
+                .long_help("Checks the output files of all effects of the spec before simulating and fails with a list of those that already exist, instead of overwriting the results of an earlier run. The check runs before any assets are loaded. Placeholders other than {datetime} and {spec_name}, e.g. {iteration}, match any text, so outputs of iterations this run would not reach also count as existing. Checkpoints, snapshots, logs and files given on the command line are not checked.")
+        )
+        .arg(
+            Arg::with_name("overwrite")
+                .long("overwrite")
+                .help("Overwrites existing output files, which is the default.")
+        )
+        .arg(
+            Arg::with_name("gammaton-count")
+                .long("gammaton-count")
+                .takes_value(true)
+                .value_name("N")
+                .validator(validate_gammaton_count)
+                .help("Overrides the number of gammatons each source emits per iteration.")
+                .long_help("Overrides the emission count of every gammaton source with the given number of gammatons per iteration, e.g. for quick low quality previews before a final run with the counts from the spec. Must be at least one.")
+        )
+        .arg(
+            Arg::with_name("flow-direction")
+                .long("flow-direction")
+                .alias("wind")
+                .takes_value(true)
+                .value_name("X,Y,Z")
+                .validator(validate_flow_direction)
+                .help("Overrides the flow direction of all gammaton sources, e.g. --flow-direction 1,-1,0.")
+                .long_help("Overrides the flow direction of every gammaton source with the given vector of three comma-separated numbers. Flowing gammatons move along the projection of this direction onto the triangle they are on, e.g. 0,-1,0 lets water run down and 1,-1,0 lets it be driven sideways as if by wind, which is also accepted as --wind. Straight and parabolic bounces are not affected. Must not be the zero vector.")
+        )
+        .arg(
+            Arg::with_name("surfel-density")
+                .long("surfel-density")
+                .takes_value(true)
+                .value_name("FLOAT")
+                .validator(validate_surfel_density)
+                .help("Overrides the surfel distance of the spec to generate roughly this many surfels per square unit.")
+                .long_help("Overrides the surfel distance of the simulation spec with one that generates roughly the given number of surfels per square unit of surface area, i.e. a surfel distance of 1/sqrt(FLOAT). Higher densities give finer weathering details at the cost of memory and synthesis time. Must be a positive number.")
+        )
+        .arg(
+            Arg::with_name("texture-resolution")
+                .long("texture-resolution")
+                .takes_value(true)
+                .value_name("WxH")
+                .validator(validate_texture_resolution)
+                .help("Overrides the width and height of all baked textures, e.g. 512x512.")
+                .long_help("Overrides the width and height of all textures baked by density and layer effects with two positive integers separated by x, e.g. 512x512 or 1024x512. Useful for quick low resolution previews of specs that bake large textures. Layer effects use it instead of the dimensions of the original maps and samples.")
+        )
+        .arg(
+            Arg::with_name("limit-triangles")
+                .long("limit-triangles")
+                .takes_value(true)
+                .value_name("N")
+                .validator(validate_triangle_limit)
+                .help("Only simulates the first N triangles of the scenes, for quick but inaccurate previews.")
+                .long_help("Only keeps the first N triangles of the simulated entities, in the order of the scene files and the entities in them, before generating surfels and tracing. Entities without any triangles left are dropped entirely. This trades accuracy for speed when iterating on a spec for a dense mesh, and a warning is logged when a preview subset is in use. Unselected objects kept as occluders with --object-as-occluder are not limited.")
+        )
+        .arg(
+            Arg::with_name("time-budget")
+                .long("time-budget")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .validator(validate_time_budget)
+                .help("Stops cleanly after the iteration during which the given wall-clock time ran out.")
+                .long_help("Checks the elapsed wall-clock time of the run after each iteration and stops when the given number of seconds is used up, performing the effects of the last completed iteration so partial results are written. The run still counts as successful, but a warning is logged and the status in --summary-json is time_budget_reached. Useful with job time limits on clusters, together with --checkpoint-every.")
+        )
+        .arg(
+            Arg::with_name("preheat")
+                .long("preheat")
+                .takes_value(true)
+                .value_name("ITERATIONS")
+                .validator(validate_preheat)
+                .help("Traces the given number of iterations before the first counted iteration.")
+                .long_help("Traces the given number of iterations before the first counted iteration, letting the simulation settle before iterations are counted. Preheat iterations do not add to the iteration count of the spec and produce no effects, benchmarks or checksums.")
+        )
+        .arg(
+            Arg::with_name("checksum-log")
+                .long("checksum-log")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Writes a checksum of all surfel concentrations after each iteration to the given file.")
+                .long_help("Writes a checksum of all surfel concentrations after each iteration to the given file, one line per iteration with the iteration number followed by the hash in hexadecimal. Runs that are expected to be identical can be compared line by line to find the first iteration that diverged.")
+        )
+        .arg(
+            Arg::with_name("stats-csv")
+                .long("stats-csv")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Writes statistics of the substance concentrations after each iteration to the given CSV file.")
+                .long_help("Writes a CSV file with a header and one row per iteration, with the iteration number, a timestamp and the total, mean and maximum concentration over all surfels for each substance. Substance columns are ordered by name.")
+        )
+        .arg(
+            Arg::with_name("emit-surfel-cloud")
+                .long("emit-surfel-cloud")
+                .alias("dump-surfels")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Writes the surfels after the last iteration as a point cloud to the given file.")
+                .long_help("Writes one point per surfel after the last iteration, with position, normal and the concentration of each substance. Files ending in .ply are written as ASCII PLY with one property per substance, all others as whitespace separated XYZ columns. With --snapshot-every, a cloud is also written with each snapshot, named like the textures. Also available as --dump-surfels.")
+        )
+        .arg(
+            Arg::with_name("archive")
+                .long("archive")
+                .takes_value(true)
+                .value_name("FILE.zip|FILE.tar.gz")
+                .validator(validate_archive_path)
+                .help("Packages all output files into a single archive after a successful run.")
+                .long_help("Packages all output files, including benchmarks, into a single archive after a successful run. The format is inferred from the extension, which must be .zip, .tar.gz or .tgz. Paths inside the archive are relative to the directory given with --output-dir, or to the working directory without it.")
+        )
+        .arg(
+            Arg::with_name("summary-json")
+                .long("summary-json")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Writes a summary of the run as JSON to the given file.")
+                .long_help("Writes a summary of the run as JSON to the given file, with the status, the number of simulated iterations, the wall-clock duration in seconds, the number of traced gammatons and all written output files. The summary is also written for interrupted runs.")
+        )
+        .arg(
+            Arg::with_name("manifest")
+                .long("manifest")
+                .conflicts_with_all(&["dry-run", "bench"])
+                .help("Writes manifest.json into the output directory, recording how the outputs were produced.")
+                .long_help("Writes manifest.json into the directory given with --output-dir, or the working directory without it, after the run. The manifest records the aitios version, a hash of the effective spec as printed by --print-spec, the seed, which is always null since the random number generator of the simulation is not seeded and repeated runs only yield statistically equivalent results, the number of threads, start and end timestamps in UTC, and all written output files with their sizes in bytes.")
+        )
+        .arg(
+            Arg::with_name("checkpoint-every")
+                .long("checkpoint-every")
+                .takes_value(true)
+                .value_name("ITERATIONS")
+                .validator(validate_iteration_interval)
+                .help("Writes a checkpoint of the simulation state every given number of iterations.")
+                .long_help("Writes a checkpoint with the iteration index and the substance concentrations of all surfels after every iteration with an index divisible by the given number. Checkpoints are JSON files named after the start time and the iteration, written into the directory given with --checkpoint-dir.")
+        )
+        .arg(
+            Arg::with_name("checkpoint-dir")
+                .long("checkpoint-dir")
+                .takes_value(true)
+                .value_name("DIRECTORY")
+                .default_value("checkpoints")
+                .help("Sets the directory for checkpoints written with --checkpoint-every.")
+        )
+        .arg(
+            Arg::with_name("snapshot-every")
+                .long("snapshot-every")
+                .takes_value(true)
+                .value_name("ITERATIONS")
+                .validator(validate_iteration_interval)
+                .help("Writes the weathering textures every given number of iterations.")
+                .long_help("Writes the textures of all density and layer effects after every iteration with an index divisible by the given number, in addition to the outputs of the effects. Snapshots are written into the directory given with --snapshot-dir, with the iteration appended to the file names, e.g. weathering_0005.png.")
+        )
+        .arg(
+            Arg::with_name("snapshot-dir")
+                .long("snapshot-dir")
+                .takes_value(true)
+                .value_name("DIRECTORY")
+                .default_value("snapshots")
+                .help("Sets the directory for texture snapshots written with --snapshot-every.")
+        )
+        .arg(
+            Arg::with_name("progress-fifo")
+                .long("progress-fifo")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("Writes a JSON line per completed iteration into the given FIFO.")
+                .long_help("Writes a JSON line per completed iteration into the existing FIFO at the given path, e.g. {\"iteration\":5,\"total\":100,\"elapsed_ms\":1234}. Writing never blocks the simulation, lines are dropped while no reader has the FIFO open or while the reader does not keep up. Not available on Windows.")
+        )
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .help("Logs the time spent in each phase of loading and running the simulation.")
+                .long_help("Logs a table with the wall-clock time spent in each phase of loading and running the simulation after it finished, e.g. mesh loading, surfel generation, tracing and texture synthesis, along with how often each phase was entered.")
+        )
+        .arg(
+            Arg::with_name("profile-out")
+                .long("profile-out")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Writes the table of --profile to the given file, implies --profile.")
+        )
+        .arg(
+            Arg::with_name("max-memory")
+                .long("max-memory")
+                .takes_value(true)
+                .value_name("BYTES")
+                .validator(validate_max_memory)
+                .help("Aborts before building the simulation if the estimated peak memory exceeds the given bytes.")
+                .long_help("Aborts before building the simulation, i.e. before surfels are generated, if the estimated peak memory exceeds the given number of bytes. The estimate is derived from the surface area of the simulated entities, the surfel distance, the substance count and the texture resolutions. For the estimate, the scenes are loaded once more before building. The estimate is logged at info level.")
         )
 }
 
@@ -61,14 +655,332 @@ fn validate_simulation_spec(simulation_spec_file: String) -> Result<(), String>
     Ok(())
 }
 
+fn validate_job_count(job_count: String) -> Result<(), String> {
+    let count = usize::from_str_radix(&job_count, 10).map_err(|e| {
+        format!(
+            "Invalid job count specified: {count}\nCause: {cause}",
+            count = job_count,
+            cause = e
+        )
+    })?;
+
+    if count == 0 {
+        Err(format!(
+            "Invalid job count specified: {}\nCause: At least one job is required",
+            job_count
+        ))
+    } else {
+        Ok(())
+    }
+}
+
 fn validate_thread_count(thread_count: String) -> Result<(), String> {
-    usize::from_str_radix(&thread_count, 10)
+    let count = usize::from_str_radix(&thread_count, 10).map_err(|e| {
+        format!(
+            "Invalid thread count specified: {count}\nCause: {cause}",
+            count = thread_count,
+            cause = e
+        )
+    })?;
+
+    if count == 0 {
+        Err(format!(
+            "Invalid thread count specified: {}\nCause: At least one thread is required",
+            thread_count
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn validate_max_problems(max_problems: String) -> Result<(), String> {
+    usize::from_str_radix(&max_problems, 10)
         .map(|_| ())
         .map_err(|e| {
             format!(
-                "Invalid thread count specified: {count}\nCause: {cause}",
-                count = thread_count,
-                cause = e
+                "Invalid maximum problem count specified: {}\nCause: {}",
+                max_problems, e
             )
         })
 }
+
+fn validate_retry_count(retries: String) -> Result<(), String> {
+    u32::from_str_radix(&retries, 10)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid retry count specified: {}\nCause: {}", retries, e))
+}
+
+fn validate_retry_delay(delay: String) -> Result<(), String> {
+    u64::from_str_radix(&delay, 10)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid retry delay specified: {}\nCause: {}", delay, e))
+}
+
+/// Parses a vector of three comma-separated numbers, e.g. `0,-1,0`.
+pub fn parse_vector(vector: &str) -> Result<[f32; 3], String> {
+    let components = vector
+        .split(',')
+        .map(|c| c.trim().parse::<f32>().map_err(|e| format!("{}", e)))
+        .collect::<Result<Vec<f32>, String>>()?;
+
+    match components.as_slice() {
+        &[x, y, z] if components.iter().all(|c| c.is_finite()) => Ok([x, y, z]),
+        &[_, _, _] => Err(String::from("All components must be finite numbers")),
+        _ => Err(format!(
+            "Expected three comma-separated numbers X,Y,Z, but got {}",
+            components.len()
+        )),
+    }
+}
+
+/// Parses a resolution of two positive integers separated by `x`, e.g.
+/// `512x256`, into width and height.
+pub fn parse_resolution(resolution: &str) -> Result<(usize, usize), String> {
+    let mut dimensions = resolution.splitn(2, 'x').map(|d| usize::from_str_radix(d, 10));
+    match (dimensions.next(), dimensions.next()) {
+        (Some(Ok(0)), Some(Ok(_))) | (Some(Ok(_)), Some(Ok(0))) => {
+            Err(String::from("Width and height must be at least one"))
+        }
+        (Some(Ok(width)), Some(Ok(height))) => Ok((width, height)),
+        (Some(Err(e)), _) | (_, Some(Err(e))) => Err(format!("{}", e)),
+        _ => Err(String::from("Expected width and height separated by x, e.g. 512x512")),
+    }
+}
+
+fn validate_verbose_target(target_level: String) -> Result<(), String> {
+    parse_target_level(&target_level)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid target level specified: {}\nCause: {}", target_level, e))
+}
+
+fn validate_texture_resolution(resolution: String) -> Result<(), String> {
+    parse_resolution(&resolution)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid texture resolution specified: {}\nCause: {}", resolution, e))
+}
+
+fn validate_flow_direction(direction: String) -> Result<(), String> {
+    match parse_vector(&direction) {
+        Ok([x, y, z]) if x == 0.0 && y == 0.0 && z == 0.0 => Err(format!(
+            "Invalid flow direction specified: {}\nCause: Must not be the zero vector",
+            direction
+        )),
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!(
+            "Invalid flow direction specified: {}\nCause: {}",
+            direction, e
+        )),
+    }
+}
+
+fn validate_assignment(assignment: String) -> Result<(), String> {
+    match assignment.find('=') {
+        Some(idx) if idx > 0 => Ok(()),
+        _ => Err(format!(
+            "Invalid spec field assignment specified: {}\nCause: Expected KEY=VALUE",
+            assignment
+        )),
+    }
+}
+
+fn validate_iterations(iterations: String) -> Result<(), String> {
+    u32::from_str_radix(&iterations, 10)
+        .map(|_| ())
+        .map_err(|e| {
+            format!(
+                "Invalid iteration count specified: {}\nCause: {}",
+                iterations, e
+            )
+        })
+}
+
+fn validate_time_budget(seconds: String) -> Result<(), String> {
+    match u64::from_str_radix(&seconds, 10) {
+        Ok(0) => Err(format!(
+            "Invalid time budget specified: {}\nCause: Must be at least one second",
+            seconds
+        )),
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Invalid time budget specified: {}\nCause: {}", seconds, e)),
+    }
+}
+
+fn validate_preheat(preheat: String) -> Result<(), String> {
+    u32::from_str_radix(&preheat, 10)
+        .map(|_| ())
+        .map_err(|e| {
+            format!(
+                "Invalid preheat iteration count specified: {}\nCause: {}",
+                preheat, e
+            )
+        })
+}
+
+fn validate_max_memory(bytes: String) -> Result<(), String> {
+    u64::from_str_radix(&bytes, 10)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid memory limit specified: {}\nCause: {}", bytes, e))
+}
+
+fn validate_gammaton_count(count: String) -> Result<(), String> {
+    match usize::from_str_radix(&count, 10) {
+        Ok(0) => Err(format!(
+            "Invalid gammaton count specified: {}\nCause: Must be at least one gammaton",
+            count
+        )),
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Invalid gammaton count specified: {}\nCause: {}", count, e)),
+    }
+}
+
+fn validate_triangle_limit(limit: String) -> Result<(), String> {
+    match usize::from_str_radix(&limit, 10) {
+        Ok(0) => Err(format!(
+            "Invalid triangle limit specified: {}\nCause: Must be at least one triangle",
+            limit
+        )),
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Invalid triangle limit specified: {}\nCause: {}", limit, e)),
+    }
+}
+
+fn validate_surfel_density(density: String) -> Result<(), String> {
+    match density.parse::<f32>() {
+        Ok(d) if d.is_finite() && d > 0.0 => Ok(()),
+        Ok(_) => Err(format!(
+            "Invalid surfel density specified: {}\nCause: Must be a positive number",
+            density
+        )),
+        Err(e) => Err(format!(
+            "Invalid surfel density specified: {}\nCause: {}",
+            density, e
+        )),
+    }
+}
+
+fn validate_bake_radius(radius: String) -> Result<(), String> {
+    match radius.parse::<f32>() {
+        Ok(r) if r.is_finite() && r > 0.0 => Ok(()),
+        Ok(_) => Err(format!(
+            "Invalid bake radius specified: {}\nCause: Must be a positive number",
+            radius
+        )),
+        Err(e) => Err(format!(
+            "Invalid bake radius specified: {}\nCause: {}",
+            radius, e
+        )),
+    }
+}
+
+fn validate_iteration_interval(interval: String) -> Result<(), String> {
+    match u32::from_str_radix(&interval, 10) {
+        Ok(0) => Err(format!(
+            "Invalid iteration interval specified: {}\nCause: Must be at least one iteration",
+            interval
+        )),
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!(
+            "Invalid iteration interval specified: {}\nCause: {}",
+            interval, e
+        )),
+    }
+}
+
+fn validate_archive_path(archive: String) -> Result<(), String> {
+    ArchiveFormat::from_path(&archive)
+        .map(|_| ())
+        .ok_or_else(|| {
+            format!(
+                "Invalid archive specified: {}\nCause: Extension must be .zip, .tar.gz or .tgz",
+                archive
+            )
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn thread_count_validation() {
+        assert!(validate_thread_count(String::from("0")).is_err());
+        assert!(validate_thread_count(String::from("1")).is_ok());
+        assert!(validate_thread_count(String::from("four")).is_err());
+    }
+
+    #[test]
+    fn verbose_target_is_repeatable() {
+        let matches = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "tests/examples/simulation.yml",
+            "--verbose-target",
+            "aitios_surf=trace",
+            "--verbose-target",
+            "aitios_sim=debug",
+        ]);
+        assert_eq!(2, matches.values_of("verbose-target").unwrap().count());
+
+        let invalid = new_app().get_matches_from_safe(vec![
+            "aitios-cli",
+            "tests/examples/simulation.yml",
+            "--verbose-target",
+            "aitios_surf",
+        ]);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn job_count_validation() {
+        assert!(validate_job_count(String::from("0")).is_err());
+        assert!(validate_job_count(String::from("2")).is_ok());
+        assert!(validate_job_count(String::from("-1")).is_err());
+    }
+
+    #[test]
+    fn flow_direction_validation() {
+        assert_eq!(Ok([1.0, -1.0, 0.5]), parse_vector("1,-1,0.5"));
+        assert_eq!(Ok([0.0, -1.0, 0.0]), parse_vector("0, -1, 0"));
+        assert_eq!(
+            Err(String::from("Expected three comma-separated numbers X,Y,Z, but got 2")),
+            parse_vector("1,2")
+        );
+        assert!(parse_vector("1,2,3,4").is_err());
+        assert!(parse_vector("1,2,up").is_err());
+        assert!(parse_vector("1,inf,0").is_err());
+        assert!(validate_flow_direction(String::from("0,0,0")).is_err());
+
+        let message = validate_flow_direction(String::from("1,2")).unwrap_err();
+        assert!(message.starts_with("Invalid flow direction specified: 1,2"), "{}", message);
+    }
+
+    #[test]
+    fn texture_resolution_parsing() {
+        assert_eq!(Ok((512, 512)), parse_resolution("512x512"));
+        assert_eq!(Ok((1024, 256)), parse_resolution("1024x256"));
+        assert!(parse_resolution("512x").is_err());
+        assert!(parse_resolution("512").is_err());
+        assert!(parse_resolution("0x512").is_err());
+        assert!(parse_resolution("512x512x3").is_err());
+
+        let message = validate_texture_resolution(String::from("512x")).unwrap_err();
+        assert!(message.starts_with("Invalid texture resolution specified: 512x"), "{}", message);
+    }
+
+    #[test]
+    fn surfel_density_validation() {
+        assert!(validate_surfel_density(String::from("0.5")).is_ok());
+        assert!(validate_surfel_density(String::from("12")).is_ok());
+        assert!(validate_surfel_density(String::from("0")).is_err());
+        assert!(validate_surfel_density(String::from("-1.0")).is_err());
+        assert!(validate_surfel_density(String::from("inf")).is_err());
+        assert!(validate_surfel_density(String::from("NaN")).is_err());
+        assert!(validate_surfel_density(String::from("dense")).is_err());
+    }
+
+    #[test]
+    fn triangle_limit_validation() {
+        assert!(validate_triangle_limit(String::from("1000")).is_ok());
+        assert!(validate_triangle_limit(String::from("0")).is_err());
+        assert!(validate_triangle_limit(String::from("-5")).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use std::fs::read_to_string;
+
+/// Detects the CPU quota of the cgroup the process runs in, as a possibly
+/// fractional number of CPUs, e.g. `1.5` for a Kubernetes limit of `1500m`.
+///
+/// Both the unified hierarchy of cgroup v2 and the CFS quota of cgroup v1
+/// are supported. Returns `None` if no limit is set, or on platforms other
+/// than Linux.
+pub fn cpu_limit() -> Option<f64> {
+    if cfg!(target_os = "linux") {
+        read_to_string("/sys/fs/cgroup/cpu.max")
+            .ok()
+            .and_then(|cpu_max| parse_cpu_max(&cpu_max))
+            .or_else(|| {
+                ["/sys/fs/cgroup/cpu", "/sys/fs/cgroup/cpu,cpuacct"]
+                    .iter()
+                    .filter_map(|dir| {
+                        let quota = read_to_string(format!("{}/cpu.cfs_quota_us", dir)).ok()?;
+                        let period = read_to_string(format!("{}/cpu.cfs_period_us", dir)).ok()?;
+                        parse_cfs_quota(&quota, &period)
+                    })
+                    .next()
+            })
+    } else {
+        None
+    }
+}
+
+/// Number of threads that fully use, but do not exceed, the given
+/// number of CPUs.
+pub fn threads_for_limit(cpu_limit: f64) -> usize {
+    (cpu_limit.ceil() as usize).max(1)
+}
+
+/// Parses cgroup v2 `cpu.max`, which contains the quota and the period in
+/// microseconds, or `max` for the quota if unlimited.
+fn parse_cpu_max(cpu_max: &str) -> Option<f64> {
+    let mut fields = cpu_max.split_whitespace();
+    let quota = fields.next()?;
+    let period = fields.next().unwrap_or("100000");
+    parse_cfs_quota(quota, period)
+}
+
+/// Parses cgroup v1 quota and period in microseconds, where a quota
+/// of `-1` means unlimited.
+fn parse_cfs_quota(quota: &str, period: &str) -> Option<f64> {
+    let quota: f64 = quota.trim().parse().ok()?;
+    let period: f64 = period.trim().parse().ok()?;
+
+    if quota > 0.0 && period > 0.0 {
+        Some(quota / period)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cgroup_v2() {
+        assert_eq!(Some(2.0), parse_cpu_max("200000 100000\n"));
+        assert_eq!(Some(1.5), parse_cpu_max("150000 100000"));
+        assert_eq!(None, parse_cpu_max("max 100000\n"));
+        assert_eq!(None, parse_cpu_max(""));
+    }
+
+    #[test]
+    fn cgroup_v1() {
+        assert_eq!(Some(0.5), parse_cfs_quota("50000\n", "100000\n"));
+        assert_eq!(None, parse_cfs_quota("-1\n", "100000\n"));
+    }
+
+    #[test]
+    fn fractional_limits_round_up() {
+        assert_eq!(2, threads_for_limit(1.5));
+        assert_eq!(4, threads_for_limit(4.0));
+        assert_eq!(1, threads_for_limit(0.1));
+    }
+}
@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+/// Effective configuration of the command line layer after all defaults
+/// have been resolved, as printed by `--print-config`.
+///
+/// In contrast to the simulation spec, this describes how the simulation
+/// is run rather than what is being simulated.
+#[derive(Debug, Serialize)]
+pub struct CliConfig {
+    pub spec_files: Vec<String>,
+    pub inline_specs: Vec<String>,
+    pub overrides: Vec<String>,
+    pub batch: bool,
+    pub fail_fast: bool,
+    /// Simulations of a batch run at the same time.
+    pub jobs: usize,
+    pub watch: bool,
+    pub threads: usize,
+    pub verbosity: String,
+    /// Levels set with `--verbose-target`, as `target=level`.
+    pub verbose_targets: Vec<String>,
+    pub log_format: String,
+    pub log_timezone: String,
+    /// Whether terminal logs are colorized, after detecting the terminal.
+    pub color: bool,
+    /// Facility if logging to syslog.
+    pub syslog: Option<String>,
+    pub log_level: String,
+    pub log_files: Vec<PathBuf>,
+    pub region_mask: Option<String>,
+    /// Output directory after expanding `{spec}` and `{datetime}`.
+    pub output_dir: Option<PathBuf>,
+    pub force: bool,
+    pub describe: String,
+    pub retries: u32,
+    pub retry_delay_ms: u64,
+    pub on_complete: Option<String>,
+    pub on_failure: Option<String>,
+    pub hook_strict: bool,
+    pub first_hit_only: bool,
+    pub bake_filter: Option<String>,
+    pub bake_radius: Option<f32>,
+    /// Bits per channel of density PNGs, from the spec or `--output-bit-depth`.
+    pub output_bit_depth: u8,
+    pub strict: bool,
+    pub fail_on_warnings: bool,
+    pub iterations: Option<u32>,
+    pub surfel_density: Option<f32>,
+    pub gammaton_count: Option<usize>,
+    pub flow_direction: Option<[f32; 3]>,
+    pub texture_resolution: Option<(usize, usize)>,
+    pub triangle_limit: Option<usize>,
+    pub preheat: u32,
+    pub checksum_log: Option<String>,
+    pub stats_csv: Option<String>,
+    pub surfel_cloud: Option<String>,
+    pub archive: Option<String>,
+    pub summary_json: Option<String>,
+    /// Path of `manifest.json` if written.
+    pub manifest: Option<PathBuf>,
+    pub checkpoint_every: Option<u32>,
+    pub checkpoint_dir: PathBuf,
+    pub snapshot_every: Option<u32>,
+    pub snapshot_dir: PathBuf,
+    pub max_memory: Option<u64>,
+    pub time_budget: Option<u64>,
+    pub no_clobber: bool,
+    pub profile: bool,
+    pub profile_out: Option<String>,
+}
@@ -0,0 +1,59 @@
+use failure::{Error, ResultExt};
+use std::process::Command;
+
+/// Runs the given command line in the platform shell with the given
+/// additional environment variables and waits for it to finish.
+///
+/// Returns an error if the command could not be spawned or exited
+/// unsuccessfully.
+pub fn run_hook(command: &str, env: &[(&str, String)]) -> Result<(), Error> {
+    info!("Running hook: {}", command);
+
+    let status = shell(command)
+        .envs(env.iter().cloned())
+        .status()
+        .with_context(|_| format!("Failed to spawn hook command \"{}\"", command))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format_err!(
+            "Hook command \"{}\" failed with {}",
+            command,
+            status
+        ))
+    }
+}
+
+fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+
+    shell.arg(command);
+    shell
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hook_receives_env() {
+        run_hook(
+            "test \"$AITIOS_STATUS\" = success",
+            &[("AITIOS_STATUS", String::from("success"))],
+        ).expect("Expected hook to see AITIOS_STATUS");
+    }
+
+    #[test]
+    fn failing_hook_is_error() {
+        assert!(run_hook("exit 3", &[]).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use app::log_time::LogTimezone;
+use log::{LevelFilter, Log, Metadata, Record};
+use serde_json;
+use simplelog::{Config, SharedLogger};
+use std::io::Write;
+use std::sync::Mutex;
+
+/// Logger that writes one JSON object per record and line, with the fields
+/// `timestamp`, `level`, `target` and `message`, for `--log-format json`.
+pub struct JsonLogger<W: Write + Send + 'static> {
+    level: LevelFilter,
+    timezone: LogTimezone,
+    sink: Mutex<W>,
+}
+
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    timestamp: String,
+    level: String,
+    target: &'a str,
+    message: String,
+}
+
+impl<W: Write + Send + 'static> JsonLogger<W> {
+    pub fn new(level: LevelFilter, timezone: LogTimezone, sink: W) -> Box<Self> {
+        Box::new(JsonLogger {
+            level,
+            timezone,
+            sink: Mutex::new(sink),
+        })
+    }
+}
+
+impl<W: Write + Send + 'static> Log for JsonLogger<W> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let record = JsonRecord {
+            timestamp: self.timezone.now(),
+            level: record.level().to_string(),
+            target: record.target(),
+            message: record.args().to_string(),
+        };
+
+        if let Ok(mut line) = serde_json::to_vec(&record) {
+            line.push(b'\n');
+            if let Ok(mut sink) = self.sink.lock() {
+                let _ = sink.write_all(&line);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut sink) = self.sink.lock() {
+            let _ = sink.flush();
+        }
+    }
+}
+
+impl<W: Write + Send + 'static> SharedLogger for JsonLogger<W> {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<Log> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use log::Level;
+    use serde_json::Value;
+    use std::io;
+    use std::sync::Arc;
+
+    /// Collects written bytes where the test can still read them after the
+    /// logger took ownership.
+    #[derive(Clone)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn one_object_per_record() {
+        let output = Shared(Arc::new(Mutex::new(Vec::new())));
+        let logger = JsonLogger::new(LevelFilter::Info, LogTimezone::Local, output.clone());
+
+        logger.log(
+            &Record::builder()
+                .args(format_args!("Iteration {} of {} started...", 1, 2))
+                .level(Level::Info)
+                .target("aitios_cli::runner")
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .args(format_args!("Not enabled"))
+                .level(Level::Debug)
+                .build(),
+        );
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(1, lines.len(), "Expected debug record to be filtered");
+
+        let record: Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!("INFO", record["level"]);
+        assert_eq!("aitios_cli::runner", record["target"]);
+        assert_eq!("Iteration 1 of 2 started...", record["message"]);
+        assert!(record["timestamp"].is_string());
+    }
+}
@@ -0,0 +1,135 @@
+use chrono::{DateTime, Local, Utc};
+use files::fs_timestamp;
+use simplelog::Config;
+use std::io::{self, Write};
+
+/// Format of timestamps in file logs, RFC 3339 with milliseconds and the
+/// offset of the timezone, e.g. `2018-01-26T18:30:09.453+01:00`.
+pub const LOG_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f%:z";
+
+/// Timezone of timestamps in file logs and log file names, as chosen with
+/// `--log-timezone`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogTimezone {
+    Utc,
+    Local,
+}
+
+impl LogTimezone {
+    /// Timezone for the given value of `--log-timezone`, defaulting to local
+    /// time.
+    pub fn from_arg(arg: Option<&str>) -> Self {
+        match arg {
+            Some("utc") => LogTimezone::Utc,
+            _ => LogTimezone::Local,
+        }
+    }
+
+    /// Current time in `LOG_TIME_FORMAT`.
+    pub fn now(self) -> String {
+        match self {
+            LogTimezone::Utc => Utc::now().format(LOG_TIME_FORMAT).to_string(),
+            LogTimezone::Local => Local::now().format(LOG_TIME_FORMAT).to_string(),
+        }
+    }
+
+    /// Filename safe timestamp of the given time in this timezone, for
+    /// `{datetime}` in log file names.
+    pub fn fs_timestamp(self, time: DateTime<Local>) -> String {
+        match self {
+            LogTimezone::Utc => fs_timestamp(time.with_timezone(&Utc)),
+            LogTimezone::Local => fs_timestamp(time),
+        }
+    }
+}
+
+/// Config for human readable file logs.
+///
+/// Timestamps of simplelog are always in UTC with a fixed format, so they
+/// are turned off in favor of the timestamps of `Timestamped`.
+pub fn file_log_config() -> Config {
+    Config {
+        time: None,
+        ..Config::default()
+    }
+}
+
+/// Writer that prefixes every line with the current time in the given
+/// timezone.
+///
+/// Lines split across multiple writes would be prefixed more than once, so
+/// this should only wrap writers that get passed whole lines, e.g. inside of
+/// `WholeLines`.
+pub struct Timestamped<W: Write> {
+    inner: W,
+    timezone: LogTimezone,
+}
+
+impl<W: Write> Timestamped<W> {
+    pub fn new(inner: W, timezone: LogTimezone) -> Self {
+        Timestamped { inner, timezone }
+    }
+}
+
+impl<W: Write> Write for Timestamped<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = self.timezone.now();
+        let mut prefixed = Vec::with_capacity(buf.len() + now.len() + 1);
+
+        let mut start = 0;
+        while start < buf.len() {
+            let end = buf[start..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map(|newline| start + newline + 1)
+                .unwrap_or(buf.len());
+            prefixed.extend_from_slice(now.as_bytes());
+            prefixed.push(b' ');
+            prefixed.extend_from_slice(&buf[start..end]);
+            start = end;
+        }
+
+        self.inner.write_all(&prefixed)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn utc_lines_have_zero_offset() {
+        let mut output = Vec::new();
+        {
+            let mut log = Timestamped::new(&mut output, LogTimezone::Utc);
+            write!(log, "[INFO] first\n[INFO] second\n").unwrap();
+        }
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(2, lines.len());
+        for (line, message) in lines.iter().zip(&["[INFO] first", "[INFO] second"]) {
+            let (timestamp, rest) = line.split_at(line.find(' ').unwrap());
+            assert!(timestamp.ends_with("+00:00"), "{}", line);
+            let time = DateTime::parse_from_rfc3339(timestamp).unwrap();
+            assert_eq!(0, time.offset().local_minus_utc());
+            assert_eq!(*message, &rest[1..]);
+        }
+    }
+
+    #[test]
+    fn utc_log_file_names() {
+        let time = DateTime::parse_from_rfc3339("2018-01-26T18:30:09.453+01:00")
+            .unwrap()
+            .with_timezone(&Local);
+        assert_eq!(
+            "2018-01-26T17_30_09.453+00_00",
+            LogTimezone::Utc.fs_timestamp(time)
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fs::metadata;
+use std::io;
+use std::path::PathBuf;
+
+/// File name of the manifest in the output directory.
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Record of how the outputs of a run were produced, as written to
+/// `manifest.json` by `--manifest`.
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    /// Version of aitios that produced the outputs.
+    pub version: String,
+    /// FNV-1a hash of the effective simulation spec in YAML, as printed by
+    /// `--print-spec`, in hexadecimal.
+    pub spec_hash: String,
+    /// Seed of the simulation, always `None` since the random numbers of
+    /// aitios-sim are drawn from an internal generator that is not seeded,
+    /// so repeated runs are statistically equivalent, but not identical.
+    pub seed: Option<u64>,
+    pub threads: usize,
+    pub started: String,
+    pub finished: String,
+    pub outputs: Vec<ManifestOutput>,
+}
+
+/// An output file with its size after the run.
+#[derive(Debug, Serialize)]
+pub struct ManifestOutput {
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+impl Manifest {
+    /// Creates a manifest for a run of the given effective spec, looking up
+    /// the sizes of the given outputs.
+    pub fn new(
+        spec_yaml: &str,
+        threads: usize,
+        started: DateTime<Utc>,
+        finished: DateTime<Utc>,
+        outputs: &[PathBuf],
+    ) -> io::Result<Self> {
+        let outputs = outputs
+            .iter()
+            .map(|path| {
+                Ok(ManifestOutput {
+                    path: path.clone(),
+                    bytes: metadata(path)?.len(),
+                })
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Manifest {
+            version: String::from(crate_version!()),
+            spec_hash: format!("{:016x}", spec_hash(spec_yaml)),
+            seed: None,
+            threads,
+            started: started.to_rfc3339_opts(SecondsFormat::Millis, true),
+            finished: finished.to_rfc3339_opts(SecondsFormat::Millis, true),
+            outputs,
+        })
+    }
+}
+
+/// Stable 64 bit FNV-1a hash of the given spec text.
+fn spec_hash(spec_yaml: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    spec_yaml.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spec_hash_is_stable() {
+        assert_eq!(0xcbf2_9ce4_8422_2325, spec_hash(""));
+        assert_eq!(0xaf63_dc4c_8601_ec8c, spec_hash("a"));
+        assert_ne!(spec_hash("iterations: 1"), spec_hash("iterations: 2"));
+    }
+}
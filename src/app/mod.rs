@@ -3,7 +3,21 @@
 //! include functionality similar to the command line tool.
 
 mod app;
+mod cgroup;
+mod config;
+mod hook;
+mod json_log;
+mod log_time;
+mod manifest;
+mod progress;
 mod run;
+#[cfg(unix)]
+mod syslog_log;
+mod target_filter;
+mod term_log;
+mod version;
+mod warn_count;
+mod watch;
 
 pub use self::app::new_app;
 pub use self::run::{run, run_with_args};
@@ -0,0 +1,209 @@
+use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(unix)]
+use libc;
+#[cfg(unix)]
+use std::fs::{File, OpenOptions};
+use std::io;
+#[cfg(unix)]
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Reports progress after each completed iteration, with the number of the
+/// iteration and the total number of iterations.
+pub type IterationProgress = Box<FnMut(u32, u32)>;
+
+/// Makes a progress bar advancing with every completed iteration, showing
+/// elapsed time and the estimated remaining time.
+///
+/// The bar is drawn to stderr and cleared after the last iteration.
+pub fn progress_bar(iterations: u32) -> IterationProgress {
+    let bar = ProgressBar::new(u64::from(iterations));
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template("Iteration {pos}/{len} [{bar:40}] {elapsed_precise}, ETA {eta_precise}"),
+    );
+
+    Box::new(move |iteration, total| {
+        bar.set_position(u64::from(iteration));
+        if iteration >= total {
+            bar.finish_and_clear();
+        }
+    })
+}
+
+/// Logs the completed iterations along with the estimated remaining time,
+/// for when no progress bar can be displayed.
+pub fn progress_log() -> IterationProgress {
+    let start = Instant::now();
+
+    Box::new(move |iteration, total| {
+        let remaining = estimate_remaining(start.elapsed(), iteration, total);
+        info!(
+            "Completed iteration {} of {}, about {}s remaining.",
+            iteration,
+            total,
+            remaining.as_secs()
+        );
+    })
+}
+
+/// Reports progress to both of the given functions.
+pub fn progress_both(
+    mut first: IterationProgress,
+    mut second: IterationProgress,
+) -> IterationProgress {
+    Box::new(move |iteration, total| {
+        first(iteration, total);
+        second(iteration, total);
+    })
+}
+
+/// Writes a JSON line per completed iteration into the FIFO at the given
+/// path, e.g. `{"iteration":5,"total":100,"elapsed_ms":1234}`, for frontends
+/// that show progress without parsing the terminal output.
+///
+/// Writing is best-effort and never blocks the simulation. Lines are dropped
+/// while no reader has the FIFO open or while the reader does not keep up,
+/// and the FIFO is opened again for later lines if the reader went away.
+///
+/// Fails if the path does not refer to an existing FIFO.
+#[cfg(unix)]
+pub fn progress_fifo<P: Into<PathBuf>>(path: P) -> io::Result<IterationProgress> {
+    let path = path.into();
+    if !path.metadata()?.file_type().is_fifo() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{:?} is not a FIFO", path),
+        ));
+    }
+
+    let start = Instant::now();
+    let mut fifo: Option<File> = None;
+
+    Ok(Box::new(move |iteration, total| {
+        if fifo.is_none() {
+            // Fails without a reader, try again with the next line
+            fifo = open_nonblocking(&path).ok();
+        }
+
+        let elapsed = start.elapsed();
+        let elapsed_ms = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_nanos() / 1_000_000);
+        let line = format!(
+            "{{\"iteration\":{},\"total\":{},\"elapsed_ms\":{}}}\n",
+            iteration, total, elapsed_ms
+        );
+
+        // Lines are shorter than PIPE_BUF and hence written completely or not
+        // at all
+        let reader_gone = match fifo {
+            Some(ref mut fifo) => match fifo.write(line.as_bytes()) {
+                Ok(_) => false,
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => false,
+                Err(_) => true,
+            },
+            None => false,
+        };
+        if reader_gone {
+            fifo = None;
+        }
+    }))
+}
+
+#[cfg(not(unix))]
+pub fn progress_fifo<P: Into<PathBuf>>(_path: P) -> io::Result<IterationProgress> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Progress FIFOs are only supported on Unix-like systems",
+    ))
+}
+
+#[cfg(unix)]
+fn open_nonblocking(path: &PathBuf) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+}
+
+/// Extrapolates the remaining time from the average time of the iterations
+/// done so far.
+fn estimate_remaining(elapsed: Duration, done: u32, total: u32) -> Duration {
+    if done == 0 || done >= total {
+        Duration::from_secs(0)
+    } else {
+        elapsed / done * (total - done)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn remaining_time_from_average_iteration() {
+        assert_eq!(
+            Duration::from_secs(30),
+            estimate_remaining(Duration::from_secs(10), 1, 4)
+        );
+        assert_eq!(
+            Duration::from_secs(5),
+            estimate_remaining(Duration::from_secs(15), 3, 4)
+        );
+        assert_eq!(
+            Duration::from_secs(0),
+            estimate_remaining(Duration::from_secs(15), 4, 4)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fifo_receives_progress_lines() {
+        use app::run_with_args;
+        use serde_json::{self, Value};
+        use std::env::temp_dir;
+        use std::ffi::CString;
+        use std::fs::{create_dir_all, remove_dir_all};
+        use std::io::Read;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = temp_dir().join("aitios-progress-fifo-test");
+        if dir.exists() {
+            remove_dir_all(&dir).unwrap();
+        }
+        create_dir_all(&dir).unwrap();
+        let fifo_path = dir.join("progress");
+        let c_path = CString::new(fifo_path.as_os_str().as_bytes()).unwrap();
+        assert_eq!(0, unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) });
+
+        // Open without blocking so the reader is there before the writer
+        let mut reader = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&fifo_path)
+            .unwrap();
+
+        let result = run_with_args(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "--output-dir",
+            dir.join("output").to_str().unwrap(),
+            "--progress-fifo",
+            fifo_path.to_str().unwrap(),
+        ]);
+        assert!(result.is_ok(), "Expected sky to run: {:?}", result);
+
+        // The writer is closed after the run, so this reads until the end
+        let mut progress = String::new();
+        reader.read_to_string(&mut progress).unwrap();
+        let line = progress.lines().next().expect("Expected a progress line");
+        let line: Value = serde_json::from_str(line).unwrap();
+        assert_eq!(1, line["iteration"]);
+        assert_eq!(1, line["total"]);
+        assert!(line["elapsed_ms"].is_u64());
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
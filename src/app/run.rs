@@ -1,16 +1,45 @@
+use app::app::{parse_resolution, parse_vector};
+use app::cgroup;
+use app::config::CliConfig;
+use app::hook::run_hook;
+use app::json_log::JsonLogger;
+use app::log_time::{file_log_config, LogTimezone, Timestamped};
+use app::manifest::{Manifest, MANIFEST_FILE_NAME};
 use app::new_app;
-use builder::SimulationBuilder;
+use app::progress::{progress_bar, progress_both, progress_fifo, progress_log};
+#[cfg(unix)]
+use app::syslog_log::SyslogLogger;
+use app::target_filter::TargetLevels;
+use app::term_log::{ColorChoice, PlainTermLogger};
+use app::version::VersionInfo;
+use app::warn_count::{count_job_warnings, warning_count, WarningCounter};
+use app::watch::InputWatcher;
+use atty::{self, Stream};
+use builder::{input_paths, SimulationBuilder};
+use chrono::Utc;
 use clap::{ArgMatches, ErrorKind as ClapErrorKind, Result as ClapResult};
+use ctrlc;
 use failure::{err_msg, Error, ResultExt};
-use files::{create_file_recursively, fs_timestamp};
-use rayon::ThreadPoolBuilder;
+use files::{
+    create_distinct_files, create_file_recursively, write_archive, JobLogFiles, LogFiles, Retry,
+    WholeLines,
+};
+use rayon::{current_num_threads, ThreadPool, ThreadPoolBuilder};
+use runner::{RunStatus, RunSummary};
+use serde_json;
+use serde_yaml;
 use simplelog::{CombinedLogger, Config, LevelFilter, SharedLogger, TermLogger, WriteLogger};
+use spec::{EffectSpec, SimulationSpec};
 use std::collections::HashSet;
 use std::default::Default;
-use std::env::current_dir;
+use std::env::{current_dir, var, var_os};
 use std::ffi::OsString;
-use std::fs::create_dir_all;
+use std::fs::{create_dir, create_dir_all};
+use std::io::{stderr, stdin, ErrorKind, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
 /// Runs with the specified arguments rather than `std::env::args()`.
 /// The first argument will be the executable name, the second will
@@ -33,32 +62,41 @@ pub fn run() -> Result<(), Error> {
 fn run_with_matches(matches: ClapResult<ArgMatches>) -> Result<(), Error> {
     match matches {
         // CLI arg parsing succeeded, unwrap the result and start loading and running simulation.
-        Ok(ref matched) => {
-            init_thread_pool(matched)?;
-
-            let builder = init_simulation_builder(matched)?;
-
-            {
-                // Init logging after spec reading but before building
-                let spec = builder.spec();
-                init_logging(matched, &spec.log, &fs_timestamp(builder.creation_time()))?;
-            }
-
-            info!("Simulation specification ready, preparing simulation...");
-            let mut runner = builder.build()?;
-
-            // Log the description line-wise
-            info!("Simulation ready.");
-            for line in format!("{}", runner).lines() {
-                info!("{}", line);
-            }
-
-            info!("Simulation running...");
-            runner.run();
-            info!("Finished simulation, done.");
-
+        Ok(ref matched) if matched.subcommand_matches("validate").is_some() => {
+            validate(matched.subcommand_matches("validate").unwrap())
+        }
+        Ok(ref matched) if matched.is_present("list-effects") => {
+            print!("{}", effect_list());
+            Ok(())
+        }
+        Ok(ref matched) if matched.is_present("version-json") => {
+            println!("{}", serde_json::to_string_pretty(&VersionInfo::current())?);
+            Ok(())
+        }
+        Ok(ref matched) if matched.is_present("dump-config-schema") => {
+            println!("{}", spec_schema()?);
             Ok(())
         }
+        Ok(ref matched) => {
+            let start_time = SystemTime::now();
+            // Output directories of all simulations, for the hooks
+            let output_dirs = Mutex::new(Vec::new());
+            // Batches and watching check the warnings of each simulation
+            let result = if matched.is_present("batch") {
+                run_batch(matched, &output_dirs)
+            } else if matched.is_present("watch") {
+                run_watch(matched, &output_dirs)
+            } else {
+                let warnings_before = warning_count();
+                run_simulation(matched, &output_dirs).and_then(|_| {
+                    let warnings = warning_count() - warnings_before;
+                    check_warnings(warnings, matched.is_present("fail-on-warnings"))
+                })
+            };
+            let output_dirs = output_dirs.into_inner().unwrap();
+            let hook_result = run_hooks(matched, &result, start_time, &output_dirs);
+            result.and(hook_result)
+        }
         // CLI argument parsing either failed or the user just wanted help or version information
         Err(matches_error) => {
             init_logging_fallback()?;
@@ -82,451 +120,2846 @@ fn run_with_matches(matches: ClapResult<ArgMatches>) -> Result<(), Error> {
     }
 }
 
-fn init_thread_pool(matches: &ArgMatches) -> Result<(), Error> {
-    if let Some(thread_count) = matches.value_of("THREAD_COUNT") {
-        let thread_count = usize::from_str_radix(&thread_count, 10).unwrap(); // Can be unwrapped since validator checks this
+/// Reports the number of warnings logged during a simulation and fails if
+/// there were any and failing on warnings was requested.
+fn check_warnings(warnings: usize, fail_on_warnings: bool) -> Result<(), Error> {
+    if warnings == 0 {
+        info!("No warnings were emitted.");
+        return Ok(());
+    }
 
-        ThreadPoolBuilder::new()
-            .num_threads(thread_count)
-            .build_global()
-            .context("Thread pool could not be set up with specified thread count.")?
+    warn!("{} warnings were emitted.", warnings);
+    if fail_on_warnings {
+        Err(format_err!(
+            "Failing because {} warnings were emitted, as requested with --fail-on-warnings",
+            warnings
+        ))
+    } else {
+        Ok(())
     }
-    Ok(())
 }
 
-fn init_simulation_builder(matches: &ArgMatches) -> Result<SimulationBuilder, Error> {
-    // Can unwrap since is marked as required and parsing would have failed otherwise
-    let mut spec_file_paths = matches.indices_of("SIMULATION_SPEC_FILE").map(|i| {
-        i.zip(
-            matches
-                .values_of("SIMULATION_SPEC_FILE")
-                .expect("Found simulation spec files but no indices"),
-        ).peekable()
-    });
+/// Lists the tag and description of every kind of effect, one per line.
+fn effect_list() -> String {
+    EffectSpec::kinds()
+        .into_iter()
+        .map(|(tag, description)| format!("{:<14}{}\n", tag, description))
+        .collect()
+}
 
-    let mut inline_specs = matches.indices_of("spec").map(|i| {
-        i.zip(
-            matches
-                .values_of("spec")
-                .expect("Found spec indices but no values"),
-        ).peekable()
-    });
+/// JSON Schema of simulation specs, pretty-printed.
+fn spec_schema() -> Result<String, Error> {
+    Ok(serde_json::to_string_pretty(&SimulationSpec::json_schema())?)
+}
 
-    let mut builder = SimulationBuilder::new();
+fn run_simulation(matches: &ArgMatches, output_dirs: &Mutex<Vec<PathBuf>>) -> Result<(), Error> {
+    let (pool, threads) = init_thread_pool(matches)?;
 
-    loop {
-        let advance_files = {
-            let next_file = spec_file_paths.as_mut().and_then(|f| f.peek());
-            let next_inline = inline_specs.as_mut().and_then(|i| i.peek());
-            match (next_file, next_inline) {
-                (None, None) => break,
-                (Some((file_idx, spec_file)), Some((inline_idx, spec_inline))) => {
-                    // Smaller idx first
-                    if file_idx < inline_idx {
-                        // Advance iterators, so we can terminate some time later
-                        builder = builder.append_spec_fragment_file(spec_file)?;
-                        true
-                    } else {
-                        builder = builder.append_spec_fragment_str(spec_inline)?;
-                        false
+    let builder = init_simulation_builder(matches)?;
+
+    if matches.is_present("print-config") {
+        // Reports the threads of the pool simulations would run on
+        let config = pool.install(|| effective_config(matches, &builder))?;
+        println!("{}", serde_json::to_string_pretty(&config)?);
+        return Ok(());
+    }
+
+    if matches.is_present("print-spec") {
+        println!("{}", effective_spec(&builder)?);
+        return Ok(());
+    }
+
+    if matches.is_present("list-substances") {
+        println!("{}", builder.substances()?);
+        return Ok(());
+    }
+
+    // Before logging, which may create the spec log in it
+    output_dirs.lock().unwrap().extend(create_output_dir(matches, &builder)?);
+
+    {
+        // Init logging after spec reading but before building
+        let spec = builder.spec();
+        let datetime = log_timezone(matches).fs_timestamp(builder.creation_time());
+        init_logging(matches, &spec.log, &datetime, builder.spec_name())?;
+    }
+
+    log_thread_pool(&pool, &threads);
+
+    let interrupt = interrupt_on_ctrl_c();
+    pool.install(|| simulate(matches, builder, interrupt))
+}
+
+/// Runs each simulation spec file on its own, with the inline specs applied
+/// to each of them.
+///
+/// With `--jobs`, up to the given number of specs run at the same time, each
+/// on its own share of the threads and logging into its own files, otherwise
+/// they run one after another.
+///
+/// Failed simulations are logged and the next one is run, unless
+/// `--fail-fast` is given. A simulation that was interrupted ends the batch.
+/// Either way, simulations that already started are finished and a summary
+/// of all attempted specs is logged at the end.
+fn run_batch(matches: &ArgMatches, output_dirs: &Mutex<Vec<PathBuf>>) -> Result<(), Error> {
+    let spec_files = spec_file_args(matches);
+    let jobs = batch_jobs(matches);
+
+    // Logging can only be installed once, so files are switched for each spec
+    let log_files = JobLogFiles::new(jobs);
+    init_batch_logging(matches, log_files.clone())?;
+
+    let job_pools = {
+        // Only sizes the job pools and is dropped, so that its threads do
+        // not compete with those of the jobs
+        let (pool, threads) = init_thread_pool(matches)?;
+        log_thread_pool(&pool, &threads);
+        init_job_pools(pool.current_num_threads(), jobs)?
+    };
+    // Only waits for the job pools, one thread per job
+    let drivers = ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .thread_name(|idx| format!("aitios-batch-{}", idx))
+        .build()
+        .context("Thread pool for batch jobs could not be set up.")?;
+    let interrupt = interrupt_on_ctrl_c();
+
+    let next_spec = AtomicUsize::new(0);
+    let stopped = AtomicBool::new(false);
+    let outcomes = Mutex::new(Vec::new());
+    let stop_err = Mutex::new(None);
+    {
+        let (spec_files, next_spec, stopped) = (&spec_files, &next_spec, &stopped);
+        let (outcomes, stop_err, log_files) = (&outcomes, &stop_err, &log_files);
+        drivers.scope(|scope| {
+            for (job, &(ref job_pool, ref job_warnings)) in job_pools.iter().enumerate() {
+                let interrupt = interrupt.clone();
+                scope.spawn(move |_| loop {
+                    let nth = next_spec.fetch_add(1, Ordering::SeqCst);
+                    if nth >= spec_files.len() || stopped.load(Ordering::SeqCst) {
+                        break;
                     }
-                }
-                (Some((_, spec_file)), None) => {
-                    builder = builder.append_spec_fragment_file(spec_file)?;
-                    true
-                }
-                (None, Some((_, spec_inline))) => {
-                    builder = builder.append_spec_fragment_str(spec_inline)?;
-                    false
-                }
+
+                    let spec_file = spec_files[nth];
+                    info!(
+                        "Simulation {} of {}: {}",
+                        nth + 1,
+                        spec_files.len(),
+                        spec_file.1
+                    );
+
+                    let files = log_files.job(job);
+                    let result = job_pool.install(|| {
+                        run_batch_spec(
+                            matches,
+                            spec_file,
+                            files,
+                            job_warnings,
+                            output_dirs,
+                            interrupt.clone(),
+                        )
+                    });
+                    let result = result.and(files.close().map_err(Error::from));
+
+                    outcomes.lock().unwrap().push((nth, spec_file.1, result.is_ok()));
+                    if let Err(err) = result {
+                        error!("Simulation {} failed: {}", spec_file.1, err);
+                        if matches.is_present("fail-fast") || interrupt.load(Ordering::SeqCst) {
+                            stopped.store(true, Ordering::SeqCst);
+                            stop_err.lock().unwrap().get_or_insert(err);
+                        }
+                    }
+                });
             }
-        };
-        if advance_files {
-            spec_file_paths
-                .as_mut()
-                .unwrap()
-                .next()
-                .expect("Could not advance iterator after peeking");
-        } else {
-            inline_specs
-                .as_mut()
-                .unwrap()
-                .next()
-                .expect("Could not advance iterator after peeking");
-        }
+        });
     }
 
-    Ok(builder)
+    // Finished in any order, but summarized in the order of arguments
+    let mut outcomes = outcomes.into_inner().unwrap();
+    outcomes.sort();
+    let outcomes: Vec<(&str, bool)> = outcomes
+        .into_iter()
+        .map(|(_, spec_file, succeeded)| (spec_file, succeeded))
+        .collect();
+    info!("{}", batch_summary(&outcomes, spec_files.len()));
+
+    if let Some(err) = stop_err.into_inner().unwrap() {
+        return Err(err);
+    }
+    let failed = outcomes.iter().filter(|&&(_, succeeded)| !succeeded).count();
+    if failed > 0 {
+        Err(format_err!(
+            "{} of {} simulations failed.",
+            failed,
+            spec_files.len()
+        ))
+    } else {
+        Ok(())
+    }
 }
 
-/// Initializes logging using the given argument matching result
-/// and an optional additional log path.
-///
-/// If matching failed, tries to set up terminal only logging and
-/// returns Ok(()) if successful, otherwise some Err value..
+/// Number of specs of a batch that run at the same time, as given with
+/// `--jobs`, but no more than there are specs.
+fn batch_jobs(matches: &ArgMatches) -> usize {
+    if !matches.is_present("batch") {
+        return 1;
+    }
+
+    // Can be unwrapped since validator checks this
+    matches
+        .value_of("jobs")
+        .map(|jobs| usize::from_str_radix(jobs, 10).unwrap())
+        .unwrap_or(1)
+        .min(spec_file_args(matches).len())
+        .max(1)
+}
+
+/// Splits the given number of threads evenly into thread pools for the given
+/// number of batch jobs, with at least one thread per job.
 ///
-/// If matching was successful, tries to apply the logging config
-/// and returns Ok(()) if successful, otherwise some Err value.
-fn init_logging(
+/// The threads of each pool log into the log files of their job and count
+/// their warnings into the counter returned with the pool.
+fn init_job_pools(
+    threads: usize,
+    jobs: usize,
+) -> Result<Vec<(ThreadPool, Arc<AtomicUsize>)>, Error> {
+    let threads_per_job = (threads / jobs).max(1);
+    if jobs > 1 {
+        info!(
+            "Running {} simulations at a time with {} threads each.",
+            jobs, threads_per_job
+        );
+    }
+
+    (0..jobs)
+        .map(|job| {
+            let warnings = Arc::new(AtomicUsize::new(0));
+            let job_warnings = warnings.clone();
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(threads_per_job)
+                .thread_name(move |idx| format!("aitios-job{}-{}", job, idx))
+                .start_handler(move |_| {
+                    JobLogFiles::enter(job);
+                    count_job_warnings(job_warnings.clone());
+                })
+                .build()
+                .context("Thread pool for batch job could not be set up.")?;
+            Ok((pool, warnings))
+        })
+        .collect()
+}
+
+/// Lists which of the attempted specs of a batch succeeded and failed, and
+/// how many were not attempted because the batch stopped early.
+fn batch_summary(outcomes: &[(&str, bool)], spec_count: usize) -> String {
+    let succeeded = outcomes.iter().filter(|&&(_, succeeded)| succeeded).count();
+    let mut summary = format!(
+        "Batch finished, {} of {} simulations succeeded.",
+        succeeded, spec_count
+    );
+    for &(spec_file, succeeded) in outcomes.iter() {
+        let outcome = if succeeded { "ok" } else { "failed" };
+        summary.push_str(&format!("\n  {:<8}{}", outcome, spec_file));
+    }
+    match spec_count - outcomes.len() {
+        0 => (),
+        1 => summary.push_str("\n  1 simulation was not attempted."),
+        skipped => summary.push_str(&format!("\n  {} simulations were not attempted.", skipped)),
+    }
+    summary
+}
+
+/// Loads and runs a single spec file of a batch, logging into the log files
+/// of this spec and checking the warnings counted for its job meanwhile.
+fn run_batch_spec(
     matches: &ArgMatches,
-    additional_log_path: &Option<PathBuf>,
-    datetime: &str,
+    spec_file: (usize, &str),
+    log_files: &LogFiles,
+    job_warnings: &AtomicUsize,
+    output_dirs: &Mutex<Vec<PathBuf>>,
+    interrupt: Arc<AtomicBool>,
 ) -> Result<(), Error> {
-    configure_logging(
-        matches,
-        additional_log_path
-            .as_ref()
-            .map(|p| p.to_string_lossy())
-            .iter(),
-        datetime,
-    ).or_else(|_| init_logging_fallback())
+    let warnings_before = job_warnings.load(Ordering::SeqCst);
+    let builder = init_simulation_builder_from(matches, vec![spec_file])?;
+    simulate_with_log_files(matches, builder, log_files, output_dirs, interrupt)?;
+
+    let warnings = job_warnings.load(Ordering::SeqCst) - warnings_before;
+    check_warnings(warnings, matches.is_present("fail-on-warnings"))
 }
 
-/// Makes the only logger log to stdout as a fallback if logging setup did not
-/// work out as planned.
-fn init_logging_fallback() -> Result<(), Error> {
-    TermLogger::init(LevelFilter::Warn, Default::default())
-        .context("Could not install fallback terminal logger")?;
+/// Runs the simulation again whenever one of the spec files or one of the
+/// input files referenced by them change, until interrupted.
+///
+/// Changes while a simulation is running are picked up after it finished.
+fn run_watch(matches: &ArgMatches, output_dirs: &Mutex<Vec<PathBuf>>) -> Result<(), Error> {
+    let (pool, threads) = init_thread_pool(matches)?;
 
-    Ok(())
-}
+    // Logging can only be installed once, so files are switched for each run
+    let log_files = LogFiles::new();
+    init_batch_logging(matches, log_files.clone())?;
 
-fn configure_logging<I, S>(
-    arg_matches: &ArgMatches,
-    additional_logs: I,
-    datetime: &str,
-) -> Result<(), Error>
-where
-    I: IntoIterator<Item = S>,
-    S: AsRef<str>,
-{
-    // Nothing => warn, -v => Info, -vv => Debug
-    let filter = match arg_matches.occurrences_of("verbose") {
-        0 => LevelFilter::Warn,
-        1 => LevelFilter::Info,
-        _ => LevelFilter::Debug,
-    };
+    log_thread_pool(&pool, &threads);
+    let interrupt = interrupt_on_ctrl_c();
 
-    let mut loggers: Vec<Box<SharedLogger>> = vec![
-        TermLogger::new(filter, Config::default())
-            .ok_or(err_msg("Failed to set up logging to terminal."))?,
-    ];
+    loop {
+        let warnings_before = warning_count();
+        let builder = init_simulation_builder(matches);
 
-    let log_paths = canonical_log_file_paths(arg_matches, additional_logs, datetime)?;
-    for log in log_paths.into_iter() {
-        let log = create_file_recursively(log).context("Failed to create log file.")?;
+        let mut inputs: Vec<PathBuf> = spec_file_args(matches)
+            .into_iter()
+            .filter(|&(_, f)| f != "-")
+            .map(|(_, f)| PathBuf::from(f))
+            .collect();
+        if let Ok(ref builder) = builder {
+            inputs.extend(input_paths(builder.spec()));
+        }
+        let watcher = InputWatcher::new(inputs)?;
 
-        loggers.push(WriteLogger::new(filter, Config::default(), log));
-    }
+        let result = builder.and_then(|b| {
+            pool.install(|| {
+                simulate_with_log_files(matches, b, &log_files, output_dirs, interrupt.clone())
+            })
+        });
+        // Runs one after another, so the global count is exact
+        let result = result.and_then(|_| {
+            let warnings = warning_count() - warnings_before;
+            check_warnings(warnings, matches.is_present("fail-on-warnings"))
+        });
+        log_files.close()?;
 
-    CombinedLogger::init(loggers).context("Failed to set up combined logger.")?;
+        if interrupt.load(Ordering::SeqCst) {
+            return result;
+        }
+        if let Err(err) = result {
+            error!("Simulation failed: {}", err);
+        }
 
-    Ok(())
+        info!("Watching {} files for changes...", watcher.len());
+        if !watcher.wait_for_change(&interrupt) {
+            return Ok(());
+        }
+        info!("Inputs changed, running again.");
+    }
 }
 
-fn canonical_log_file_paths<I, S>(
-    arg_matches: &ArgMatches,
-    additional_logs: I,
-    datetime: &str,
-) -> Result<HashSet<PathBuf>, Error>
-where
-    I: IntoIterator<Item = S>,
-    S: AsRef<str>,
-{
-    let mut log_files = Vec::new();
+/// Runs the simulation with logs going into the given log files, which are
+/// opened for the log paths of the simulation first.
+fn simulate_with_log_files(
+    matches: &ArgMatches,
+    builder: SimulationBuilder,
+    log_files: &LogFiles,
+    output_dirs: &Mutex<Vec<PathBuf>>,
+    interrupt: Arc<AtomicBool>,
+) -> Result<(), Error> {
+    if matches.is_present("print-config") {
+        let config = effective_config(matches, &builder)?;
+        println!("{}", serde_json::to_string_pretty(&config)?);
+        return Ok(());
+    }
 
-    // First add log files explicitly specified with CLI args
-    if let Some(log_file_args) = arg_matches.values_of("log") {
-        log_files.extend(log_file_args.map(|a| log_arg_to_log_path(a, datetime)))
+    if matches.is_present("print-spec") {
+        println!("{}", effective_spec(&builder)?);
+        return Ok(());
     }
 
-    // If more log arguments were specified than log file names,
-    // add exactly one additional log with a default log name.
-    //
-    // e.g. `aitios-cli sim.yml -l asdf.log -l` will log to both
-    // asdf.log and the default log filename below the cwd.
-    if (log_files.len() as u64) < (arg_matches.occurrences_of("log") as u64) {
-        log_files.push(log_arg_to_log_path(
-            &synthesize_datetime_log_filename(datetime),
-            datetime,
-        ));
+    if matches.is_present("list-substances") {
+        println!("{}", builder.substances()?);
+        return Ok(());
     }
 
-    // Finally add the additional log files, most likely coming from
-    // simulation configuration.
-    log_files.extend(
-        additional_logs
-            .into_iter()
-            .map(|l| log_arg_to_log_path(l.as_ref(), datetime)),
-    );
+    output_dirs.lock().unwrap().extend(create_output_dir(matches, &builder)?);
 
-    // Canonicalize paths, filter out duplicates and abort on any errors
-    log_files.into_iter().collect()
+    let spec_log = builder.spec().log.as_ref().map(|p| p.to_string_lossy().into_owned());
+    let log_paths = canonical_log_file_paths(
+        matches,
+        spec_log.iter(),
+        &log_timezone(matches).fs_timestamp(builder.creation_time()),
+        builder.spec_name(),
+    )?;
+    log_files
+        .open(log_paths)
+        .context("Failed to create log file.")?;
+
+    simulate(matches, builder, interrupt)
 }
 
-fn log_arg_to_log_path(arg: &str, datetime: &str) -> Result<PathBuf, Error> {
-    // Replace {datetime} pattern with filename safe timestamp
-    let arg = arg.replace("{datetime}", datetime);
-    let path: &Path = arg.as_ref();
+fn log_thread_pool(pool: &ThreadPool, threads: &ThreadRequest) {
+    if let Some(ref invalid) = threads.invalid_env {
+        warn!(
+            "Ignoring invalid thread count in {}: {:?}, expected a positive number.",
+            THREADS_ENV_VAR, invalid
+        );
+    }
+    if let Some(cpu_limit) = threads.cpu_limit {
+        info!("Detected container CPU limit of {} CPUs.", cpu_limit);
+    }
+    info!("Running with {} threads.", pool.current_num_threads());
+}
 
-    if path.is_dir() {
-        // If directory given, append default log filename
-        let mut path = path.canonicalize()?;
-        path.push(synthesize_datetime_log_filename(datetime));
-        Ok(path)
-    } else if path.is_file() {
-        // Existing, regular file, return canonicalized form for overwrite
-        Ok(path.canonicalize()?)
-    } else {
-        match path.parent() {
-            // Relative one-level path returns Ok(""), just create the file,
-            // under cwd since . is the implicit parent and always exists.
-            Some(parent) if parent.as_os_str().is_empty() => {
-                let mut new_path = current_dir()?.canonicalize()?;
-                new_path.push(&arg);
-                Ok(new_path)
-            }
-            // If immediate parent is an existing directory other than "",
-            // canonicalize it, and append the final path component again.
-            Some(parent) if parent.is_dir() => {
-                let mut new_path = parent.canonicalize()?;
-                new_path.push(path.file_name().unwrap());
-                Ok(new_path)
-            }
-            // Ok, some nonexisting parent, try to create it
-            Some(parent) => {
-                create_dir_all(parent).unwrap();
-                let mut new_path = parent.canonicalize()?;
-                new_path.push(path.file_name().unwrap());
-                Ok(new_path)
-            }
-            // Something about the path is wrong, stop trying
-            _ => Err(format_err!("Log file path \"{}\" cannot be resolved", arg)),
+/// Makes a flag that is set when the user presses Ctrl-C.
+///
+/// The handler can only be installed once per process, later calls only
+/// warn and return a flag that is never set.
+fn interrupt_on_ctrl_c() -> Arc<AtomicBool> {
+    let interrupt = Arc::new(AtomicBool::new(false));
+    {
+        let interrupt = interrupt.clone();
+        if let Err(err) = ctrlc::set_handler(move || interrupt.store(true, Ordering::SeqCst)) {
+            warn!(
+                "Could not install Ctrl-C handler, interrupting will lose partial results: {}",
+                err
+            );
         }
     }
+    interrupt
 }
 
-/// Synthesize a default filename if -l or --log is passed without an actual filename.
-fn synthesize_datetime_log_filename(datetime: &str) -> String {
-    format!("aitios-log-{datetime}.log", datetime = datetime)
-}
+/// Loads, describes and runs the simulation, after logging has been set up.
+fn simulate(
+    matches: &ArgMatches,
+    builder: SimulationBuilder,
+    interrupt: Arc<AtomicBool>,
+) -> Result<(), Error> {
+    if matches.is_present("check-assets") {
+        return check_assets(&builder);
+    }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use chrono::prelude::*;
-    use std::iter;
+    if matches.is_present("no-clobber") {
+        check_no_clobber(&builder)?;
+    }
 
-    #[test]
-    fn test_log_arg_with_datetime() {
-        let time = Local::now();
+    if !inspect_only(matches) {
+        probe_output_dirs(&builder)?;
+    }
 
-        let expected = {
-            let mut expected = current_dir().unwrap();
-            expected.push(format!(
-                "logovic-{datetime}.log",
-                datetime = fs_timestamp(time)
-            ));
-            expected
-        };
+    // Expanded before building, which consumes the builder
+    let output_dir = expanded_output_dir(matches, &builder);
 
-        let actual = log_arg_to_log_path("./logovic-{datetime}.log", &fs_timestamp(time)).unwrap();
+    // Hashed before building, which consumes the spec
+    let manifest_spec = if matches.is_present("manifest") {
+        Some(effective_spec(&builder)?)
+    } else {
+        None
+    };
+
+    if matches.is_present("print-bounds") {
+        // From the loaded scenes, without generating surfels
+        println!("{}", builder.bounds()?);
+        return Ok(());
+    }
+
+    // Estimated from the scenes before building, which allocates surfels
+    let inspects = ["plan", "explain"];
+    if let Some(max_bytes) = max_memory(matches) {
+        if !inspects.iter().any(|arg| matches.is_present(arg)) {
+            check_memory(builder.estimate_peak_memory()?, max_bytes)?;
+        }
+    }
+
+    info!("Simulation specification ready, preparing simulation...");
+    let mut runner = builder.build()?;
+
+    if matches.is_present("plan") {
+        let plan = runner.plan();
+        match matches.value_of("plan-format") {
+            Some("json") => println!("{}", serde_json::to_string_pretty(&plan)?),
+            _ => println!("{}", plan),
+        }
+        return Ok(());
+    }
+
+    if matches.is_present("explain") {
+        println!("{}", runner.explain());
+        return Ok(());
+    }
+
+    // Log the description line-wise
+    info!("Simulation ready.");
+    let description = match matches.value_of("describe") {
+        Some("full") => format!("{}", runner),
+        Some("none") => String::new(),
+        _ => runner.summary(),
+    };
+    for line in description.lines() {
+        info!("{}", line);
+    }
+
+    if matches.is_present("dry-run") {
+        info!("Dry run, exiting without simulating.");
+        return Ok(());
+    }
+
+    runner.set_retry(retry_policy(matches));
+
+    if let Some(preheat) = matches.value_of("preheat") {
+        // Can be unwrapped since validator checks this
+        runner.set_preheat(u32::from_str_radix(preheat, 10).unwrap());
+    }
+
+    if let Some(checksum_log) = matches.value_of("checksum-log") {
+        let checksum_log = create_file_recursively(checksum_log)
+            .with_context(|_| format!("Failed to create checksum log {}", checksum_log))?;
+        runner.set_checksum_log(checksum_log);
+    }
+
+    if let Some(stats_csv) = matches.value_of("stats-csv") {
+        let stats_file = create_file_recursively(stats_csv)
+            .with_context(|_| format!("Failed to create statistics log {}", stats_csv))?;
+        runner
+            .set_stats_log(stats_file)
+            .with_context(|_| format!("Failed to write statistics log {}", stats_csv))?;
+    }
+
+    if let Some(every) = matches.value_of("checkpoint-every") {
+        // Can be unwrapped since validator checks this and dir has a default
+        runner.set_checkpoints(
+            u32::from_str_radix(every, 10).unwrap(),
+            below_output_dir(matches, output_dir.as_ref(), "checkpoint-dir"),
+        );
+    }
+
+    if let Some(every) = matches.value_of("snapshot-every") {
+        // Can be unwrapped since validator checks this and dir has a default
+        runner.set_snapshots(
+            u32::from_str_radix(every, 10).unwrap(),
+            below_output_dir(matches, output_dir.as_ref(), "snapshot-dir"),
+        );
+        if let Some(cloud) = matches.value_of("emit-surfel-cloud") {
+            runner.set_snapshot_cloud(cloud);
+        }
+    }
+
+    runner.set_interrupt(interrupt);
+
+    if let Some(seconds) = matches.value_of("time-budget") {
+        // Can be unwrapped since validator checks this
+        runner.set_time_budget(Duration::from_secs(u64::from_str_radix(seconds, 10).unwrap()));
+    }
+
+    let mut progress = if show_progress_bar(matches) {
+        progress_bar(runner.iterations())
+    } else {
+        progress_log()
+    };
+    if let Some(fifo) = matches.value_of("progress-fifo") {
+        let fifo = progress_fifo(fifo)
+            .with_context(|_| format!("Failed to open progress FIFO {}", fifo))?;
+        progress = progress_both(progress, fifo);
+    }
+    runner.set_on_iteration(progress);
+
+    info!("Simulation running...");
+    let started = Utc::now();
+    let mut summary = runner.run();
+
+    if matches.is_present("bench") {
+        println!("{}", bench_line(&summary, runner.preheat()));
+    }
+
+    if let Some(cloud) = matches.value_of("emit-surfel-cloud") {
+        info!("Writing surfel cloud to {}...", cloud);
+        runner
+            .export_surfel_cloud(cloud)
+            .with_context(|_| format!("Failed to write surfel cloud {}", cloud))?;
+    }
+
+    if let Some(archive) = matches.value_of("archive") {
+        // Relative to the output directory, which may be outside of cwd
+        let cwd = current_dir()?;
+        let root = cwd.join(output_dir.as_ref().unwrap_or(&cwd));
+        let outputs: Vec<PathBuf> = runner.outputs().iter().map(|o| cwd.join(o)).collect();
+        info!("Archiving {} output files into {}...", outputs.len(), archive);
+        write_archive(archive, &root, &outputs)
+            .with_context(|_| format!("Failed to write output archive {}", archive))?;
+    }
+
+    // Include the surfel cloud in the outputs
+    summary.outputs = runner.outputs();
+    for line in format!("{}", summary).lines() {
+        info!("{}", line);
+    }
+
+    if let Some(profile) = runner.profile() {
+        for line in format!("{}", profile).lines() {
+            info!("{}", line);
+        }
+
+        if let Some(profile_out) = matches.value_of("profile-out") {
+            let mut profile_file = create_file_recursively(profile_out)
+                .with_context(|_| format!("Failed to create profile {}", profile_out))?;
+            writeln!(profile_file, "{}", profile)
+                .with_context(|_| format!("Failed to write profile {}", profile_out))?;
+        }
+    }
+
+    if let Some(summary_json) = matches.value_of("summary-json") {
+        let summary_file = create_file_recursively(summary_json)
+            .with_context(|_| format!("Failed to create run summary {}", summary_json))?;
+        serde_json::to_writer_pretty(summary_file, &summary)
+            .with_context(|_| format!("Failed to write run summary {}", summary_json))?;
+    }
+
+    if let Some(spec) = manifest_spec {
+        let path = manifest_path(output_dir.as_ref());
+        info!("Writing manifest to {}...", path.display());
+        let manifest = Manifest::new(
+            &spec,
+            current_num_threads(),
+            started,
+            Utc::now(),
+            &summary.outputs,
+        ).context("Failed to look up output files for manifest")?;
+        let manifest_file = create_file_recursively(&path)
+            .with_context(|_| format!("Failed to create manifest {}", path.display()))?;
+        serde_json::to_writer_pretty(manifest_file, &manifest)
+            .with_context(|_| format!("Failed to write manifest {}", path.display()))?;
+    }
+
+    if let RunStatus::TimeBudgetReached { iteration } = summary.status {
+        warn!(
+            "Time budget reached after iteration {} of {}, wrote partial results.",
+            iteration,
+            runner.iterations()
+        );
+    }
+
+    if let RunStatus::Interrupted { iteration } = summary.status {
+        warn!("Interrupted, wrote partial results.");
+        return Err(format_err!(
+            "Simulation interrupted after iteration {}",
+            iteration
+        ));
+    }
+
+    info!("Finished simulation, done.");
+
+    Ok(())
+}
+
+/// Checks if the run only inspects the simulation, e.g. with `--plan`, rather
+/// than writing outputs.
+fn inspect_only(matches: &ArgMatches) -> bool {
+    // Benchmarks write no outputs either
+    ["check-assets", "print-bounds", "plan", "explain", "dry-run", "bench"]
+        .iter()
+        .any(|arg| matches.is_present(arg))
+}
+
+/// Directory given with `--output-dir` for the simulation of the given
+/// builder, with `{spec}` and `{datetime}` expanded.
+fn expanded_output_dir(matches: &ArgMatches, builder: &SimulationBuilder) -> Option<PathBuf> {
+    matches.value_of("output-dir").map(|template| {
+        let datetime = log_timezone(matches).fs_timestamp(builder.creation_time());
+        PathBuf::from(expand_output_dir(template, builder.spec_name(), &datetime))
+    })
+}
+
+/// Replaces `{spec}`, or `{spec_name}` as in other paths, with the name of
+/// the spec and `{datetime}` with the given timestamp.
+fn expand_output_dir(template: &str, spec_name: &str, datetime: &str) -> String {
+    template
+        .replace("{spec_name}", spec_name)
+        .replace("{spec}", spec_name)
+        .replace("{datetime}", datetime)
+}
+
+/// Creates the directory given with `--output-dir` and its parents, unless
+/// the run only inspects the simulation.
+///
+/// Fails if the directory already exists, which means that an earlier run
+/// or another spec of the batch resolved to the same directory, unless
+/// `--force` is given.
+///
+/// Returns the expanded directory, if one was given.
+fn create_output_dir(
+    matches: &ArgMatches,
+    builder: &SimulationBuilder,
+) -> Result<Option<PathBuf>, Error> {
+    let dir = match expanded_output_dir(matches, builder) {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+
+    if inspect_only(matches) {
+        return Ok(Some(dir));
+    }
+
+    if matches.is_present("force") {
+        create_dir_all(&dir)
+            .with_context(|_| format!("Could not create output directory {:?}", dir))?;
+        return Ok(Some(dir));
+    }
+
+    if let Some(parent) = dir.parent() {
+        create_dir_all(parent)
+            .with_context(|_| format!("Could not create output directory {:?}", parent))?;
+    }
+    // Fails atomically, even if jobs of a batch resolve to the same directory
+    match create_dir(&dir) {
+        Err(ref err) if err.kind() == ErrorKind::AlreadyExists => Err(format_err!(
+            "Output directory {:?} already exists, use --force to write into it anyway",
+            dir
+        )),
+        result => {
+            result.with_context(|_| format!("Could not create output directory {:?}", dir))?;
+            Ok(Some(dir))
+        }
+    }
+}
+
+/// Directory given with the argument of the given name, which must have a
+/// default value, below the output directory if relative.
+fn below_output_dir(
+    matches: &ArgMatches,
+    output_dir: Option<&PathBuf>,
+    dir_arg: &str,
+) -> PathBuf {
+    // Can be unwrapped since there is a default
+    let dir = Path::new(matches.value_of(dir_arg).unwrap());
+    match output_dir {
+        Some(output_dir) if dir.is_relative() => output_dir.join(dir),
+        _ => dir.to_path_buf(),
+    }
+}
+
+/// Path of the manifest written with `--manifest`, in the output directory if
+/// given.
+fn manifest_path(output_dir: Option<&PathBuf>) -> PathBuf {
+    match output_dir {
+        Some(output_dir) => output_dir.join(MANIFEST_FILE_NAME),
+        None => PathBuf::from(MANIFEST_FILE_NAME),
+    }
+}
+
+/// Checks if a progress bar can be shown, which requires a terminal and
+/// human readable terminal logging.
+///
+/// Batch jobs running at the same time would draw their bars over each
+/// other, so they log their progress instead.
+fn show_progress_bar(matches: &ArgMatches) -> bool {
+    atty::is(Stream::Stdout)
+        && !matches.is_present("quiet")
+        && matches.value_of("log-format") != Some("json")
+        && batch_jobs(matches) == 1
+}
+
+/// Implements the validate subcommand, checking input assets and output
+/// directories, and loading the simulation without running it.
+///
+/// Lists the files the simulation would write if successful, otherwise lists
+/// the first problems and fails.
+fn validate(matches: &ArgMatches) -> Result<(), Error> {
+    // Warnings while loading are only shown if a terminal is available
+    init_logging_fallback().ok();
+
+    // Can be unwrapped since validator checks this and there is a default
+    let max_problems =
+        usize::from_str_radix(matches.value_of("max-problems").unwrap(), 10).unwrap();
+
+    let builder = init_simulation_builder(matches)?;
+    let assets = builder.check_assets();
+    let output_dirs = builder.check_output_dirs();
+
+    let mut problems: Vec<String> = assets
+        .problems
+        .iter()
+        .chain(output_dirs.problems.iter())
+        .map(|p| format!("{}", p))
+        .collect();
+
+    println!(
+        "Checked {} input assets and {} output directories.",
+        assets.checked, output_dirs.checked
+    );
+
+    // Only load if all assets are fine, otherwise loading would only
+    // report the first problem again
+    if problems.is_empty() {
+        match builder.build() {
+            Ok(runner) => {
+                let plan = runner.plan();
+                println!("Would write {} files:", plan.outputs.len());
+                for output in plan.outputs.iter() {
+                    println!("  {}", output.path);
+                }
+            }
+            Err(err) => problems.push(format!("{}", err)),
+        }
+    }
+
+    if problems.is_empty() {
+        println!("Simulation is valid.");
+        Ok(())
+    } else {
+        for problem in problems.iter().take(max_problems) {
+            println!("{}", problem);
+        }
+        if problems.len() > max_problems {
+            println!("... and {} more", problems.len() - max_problems);
+        }
+        Err(format_err!("Found {} problems.", problems.len()))
+    }
+}
+
+/// Collects the configuration of the command line layer in effect after
+/// resolving thread count, verbosity, log paths and defaults.
+fn effective_config(matches: &ArgMatches, builder: &SimulationBuilder) -> Result<CliConfig, Error> {
+    fn values(matches: &ArgMatches, name: &str) -> Vec<String> {
+        matches
+            .values_of(name)
+            .map(|v| v.map(String::from).collect())
+            .unwrap_or_else(Vec::new)
+    }
+
+    let spec_log = builder.spec().log.as_ref().map(|p| p.to_string_lossy());
+    let mut log_files: Vec<PathBuf> = canonical_log_file_paths(
+        matches,
+        spec_log.iter(),
+        &log_timezone(matches).fs_timestamp(builder.creation_time()),
+        builder.spec_name(),
+    )?.into_iter()
+    .collect();
+    log_files.sort();
+
+    let retry = retry_policy(matches);
+    let output_dir = expanded_output_dir(matches, builder);
+
+    Ok(CliConfig {
+        spec_files: values(matches, "SIMULATION_SPEC_FILE"),
+        inline_specs: values(matches, "spec"),
+        overrides: values(matches, "set"),
+        batch: matches.is_present("batch"),
+        fail_fast: matches.is_present("fail-fast"),
+        jobs: batch_jobs(matches),
+        watch: matches.is_present("watch"),
+        threads: current_num_threads(),
+        verbosity: format!("{}", verbosity(matches)).to_lowercase(),
+        verbose_targets: values(matches, "verbose-target"),
+        log_format: String::from(matches.value_of("log-format").unwrap()),
+        log_timezone: String::from(matches.value_of("log-timezone").unwrap()),
+        color: color_choice(matches).colorize(atty::is(Stream::Stdout)),
+        syslog: if matches.is_present("syslog") {
+            matches.value_of("syslog-facility").map(String::from)
+        } else {
+            None
+        },
+        log_level: format!("{}", file_log_level(matches)).to_lowercase(),
+        log_files,
+        region_mask: matches.value_of("region-mask").map(String::from),
+        output_dir: output_dir.clone(),
+        force: matches.is_present("force"),
+        describe: String::from(matches.value_of("describe").unwrap()),
+        retries: retry.retries,
+        retry_delay_ms: u64::from_str_radix(matches.value_of("retry-delay").unwrap(), 10)
+            .unwrap(),
+        on_complete: matches.value_of("on-complete").map(String::from),
+        on_failure: matches.value_of("on-failure").map(String::from),
+        hook_strict: matches.is_present("hook-strict"),
+        first_hit_only: matches.is_present("first-hit-only"),
+        bake_filter: matches.value_of("bake-filter").map(String::from),
+        bake_radius: matches.value_of("bake-radius").map(|r| r.parse().unwrap()),
+        output_bit_depth: builder.spec().output_bit_depth.unwrap_or(8),
+        strict: matches.is_present("strict"),
+        fail_on_warnings: matches.is_present("fail-on-warnings"),
+        iterations: matches
+            .value_of("iterations")
+            .map(|i| u32::from_str_radix(i, 10).unwrap()),
+        surfel_density: matches
+            .value_of("surfel-density")
+            .map(|d| d.parse().unwrap()),
+        gammaton_count: matches
+            .value_of("gammaton-count")
+            .map(|c| usize::from_str_radix(c, 10).unwrap()),
+        flow_direction: matches
+            .value_of("flow-direction")
+            .map(|d| parse_vector(d).unwrap()),
+        texture_resolution: matches
+            .value_of("texture-resolution")
+            .map(|r| parse_resolution(r).unwrap()),
+        triangle_limit: matches
+            .value_of("limit-triangles")
+            .map(|l| usize::from_str_radix(l, 10).unwrap()),
+        preheat: matches
+            .value_of("preheat")
+            .map(|p| u32::from_str_radix(p, 10).unwrap())
+            .unwrap_or(0),
+        checksum_log: matches.value_of("checksum-log").map(String::from),
+        stats_csv: matches.value_of("stats-csv").map(String::from),
+        surfel_cloud: matches.value_of("emit-surfel-cloud").map(String::from),
+        archive: matches.value_of("archive").map(String::from),
+        summary_json: matches.value_of("summary-json").map(String::from),
+        manifest: if matches.is_present("manifest") {
+            Some(manifest_path(output_dir.as_ref()))
+        } else {
+            None
+        },
+        checkpoint_every: matches
+            .value_of("checkpoint-every")
+            .map(|c| u32::from_str_radix(c, 10).unwrap()),
+        checkpoint_dir: below_output_dir(matches, output_dir.as_ref(), "checkpoint-dir"),
+        snapshot_every: matches
+            .value_of("snapshot-every")
+            .map(|s| u32::from_str_radix(s, 10).unwrap()),
+        snapshot_dir: below_output_dir(matches, output_dir.as_ref(), "snapshot-dir"),
+        max_memory: max_memory(matches),
+        time_budget: matches
+            .value_of("time-budget")
+            .map(|s| u64::from_str_radix(s, 10).unwrap()),
+        no_clobber: matches.is_present("no-clobber"),
+        profile: profiling(matches),
+        profile_out: matches.value_of("profile-out").map(String::from),
+    })
+}
+
+/// Throughput of a benchmark run as a single line of `key=value` pairs,
+/// counting preheat iterations as traced iterations.
+fn bench_line(summary: &RunSummary, preheat: u32) -> String {
+    let iterations = summary.iterations + preheat;
+    format!(
+        "bench gammatons_per_second={:.3} iterations_per_second={:.3} gammatons={} iterations={} seconds={:.3}",
+        summary.gammatons_traced as f64 / summary.duration_seconds,
+        f64::from(iterations) / summary.duration_seconds,
+        summary.gammatons_traced,
+        iterations,
+        summary.duration_seconds
+    )
+}
+
+/// Serializes the spec the builder would build, as printed by `--print-spec`.
+fn effective_spec(builder: &SimulationBuilder) -> Result<String, Error> {
+    Ok(serde_yaml::to_string(&builder.effective_spec())?)
+}
+
+fn retry_policy(matches: &ArgMatches) -> Retry {
+    match matches.value_of("retry") {
+        // Can be unwrapped since validators check this
+        Some(retries) => Retry {
+            retries: u32::from_str_radix(retries, 10).unwrap(),
+            delay: Duration::from_millis(
+                u64::from_str_radix(matches.value_of("retry-delay").unwrap(), 10).unwrap(),
+            ),
+        },
+        None => Retry::none(),
+    }
+}
+
+fn profiling(matches: &ArgMatches) -> bool {
+    matches.is_present("profile") || matches.is_present("profile-out")
+}
+
+fn max_memory(matches: &ArgMatches) -> Option<u64> {
+    matches
+        .value_of("max-memory")
+        .map(|m| u64::from_str_radix(m, 10).unwrap())
+}
+
+/// Logs the estimated peak memory of a simulation and fails if it exceeds
+/// the given limit, so that the simulation can be aborted before surfels,
+/// textures and surfel tables are allocated.
+fn check_memory(estimated_bytes: u64, max_bytes: u64) -> Result<(), Error> {
+    info!(
+        "Estimated peak memory: {} bytes ({:.1} MiB)",
+        estimated_bytes,
+        estimated_bytes as f64 / (1024.0 * 1024.0)
+    );
+
+    if estimated_bytes > max_bytes {
+        Err(format_err!(
+            "Estimated peak memory of {} bytes exceeds --max-memory of {} bytes.",
+            estimated_bytes,
+            max_bytes
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Fails with a listing of the output files of the effects that already
+/// exist, before loading any assets.
+fn check_no_clobber(builder: &SimulationBuilder) -> Result<(), Error> {
+    let existing: Vec<String> = builder
+        .existing_outputs()
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+
+    for path in existing.iter() {
+        error!("Output file {} already exists.", path);
+    }
+
+    if existing.is_empty() {
+        Ok(())
+    } else {
+        Err(format_err!(
+            "{} output files already exist, refusing to overwrite them with --no-clobber: {}",
+            existing.len(),
+            existing.join(", ")
+        ))
+    }
+}
+
+/// Fails if any output directory cannot be created or written, so that long
+/// runs do not fail only when writing their results.
+fn probe_output_dirs(builder: &SimulationBuilder) -> Result<(), Error> {
+    let report = builder.probe_output_dirs();
+
+    for problem in report.problems.iter() {
+        error!("Output directory {}", problem);
+    }
+
+    if report.problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format_err!(
+            "{} of {} output directories are not writable: {}",
+            report.problems.len(),
+            report.checked,
+            report
+                .problems
+                .iter()
+                .map(|p| format!("{:?}", p.path))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    }
+}
+
+/// Reports the results of opening all input assets and fails if any
+/// of them could not be loaded.
+fn check_assets(builder: &SimulationBuilder) -> Result<(), Error> {
+    let report = builder.check_assets();
+
+    for problem in report.problems.iter() {
+        println!("{}", problem);
+    }
+
+    if report.problems.is_empty() {
+        println!("All {} input assets loaded successfully.", report.checked);
+        Ok(())
+    } else {
+        Err(format_err!(
+            "{} of {} input assets failed to load.",
+            report.problems.len(),
+            report.checked
+        ))
+    }
+}
+
+/// Runs the command given with `--on-complete` or `--on-failure`, depending
+/// on the result of the simulation.
+///
+/// A failing hook is only logged, unless `--hook-strict` is set, in which
+/// case its error is returned.
+fn run_hooks(
+    matches: &ArgMatches,
+    result: &Result<(), Error>,
+    start_time: SystemTime,
+    output_dirs: &[PathBuf],
+) -> Result<(), Error> {
+    let hook = if result.is_ok() {
+        matches.value_of("on-complete")
+    } else {
+        matches.value_of("on-failure")
+    };
+
+    if let Some(hook) = hook {
+        let elapsed = start_time.elapsed().unwrap_or_default();
+        let mut env = vec![
+            (
+                "AITIOS_STATUS",
+                String::from(if result.is_ok() { "success" } else { "failure" }),
+            ),
+            (
+                "AITIOS_EXIT_STATUS",
+                String::from(if result.is_ok() { "0" } else { "1" }),
+            ),
+            (
+                "AITIOS_DURATION",
+                format!("{}.{:09}", elapsed.as_secs(), elapsed.subsec_nanos()),
+            ),
+            (
+                "AITIOS_OUTPUT_DIR",
+                hook_output_dir(output_dirs)?.to_string_lossy().into_owned(),
+            ),
+        ];
+
+        if let &Err(ref err) = result {
+            env.push(("AITIOS_ERROR", format!("{}", err)));
+        }
+
+        if let Err(hook_err) = run_hook(hook, &env) {
+            if matches.is_present("hook-strict") {
+                return Err(hook_err);
+            }
+            warn!("{}", hook_err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Directory passed to hooks in `AITIOS_OUTPUT_DIR`, which is the expanded
+/// `--output-dir` of the simulations, or the working directory without it.
+///
+/// If the simulations of a batch or of watched runs wrote to different
+/// directories, this is the innermost directory that contains all of them.
+fn hook_output_dir(output_dirs: &[PathBuf]) -> Result<PathBuf, Error> {
+    let cwd = current_dir()?;
+    let mut dirs = output_dirs.iter().map(|dir| cwd.join(dir));
+    let first = match dirs.next() {
+        Some(first) => first,
+        None => return Ok(cwd),
+    };
+
+    Ok(dirs.fold(first, |common, dir| {
+        common
+            .components()
+            .zip(dir.components())
+            .take_while(|&(a, b)| a == b)
+            .map(|(a, _)| a)
+            .collect()
+    }))
+}
+
+/// Environment variable with a thread count to use if `--threads` is not
+/// given.
+const THREADS_ENV_VAR: &str = "AITIOS_THREADS";
+
+/// Thread count to build the pool with and where it came from.
+#[derive(Debug, PartialEq)]
+struct ThreadRequest {
+    /// If `None`, rayon picks the number of virtual processors.
+    threads: Option<usize>,
+    /// CPU quota of the container the thread count was derived from, if any.
+    cpu_limit: Option<f64>,
+    /// Value of the environment variable if it was set but not a valid
+    /// thread count and hence ignored.
+    invalid_env: Option<String>,
+}
+
+/// Sets up a thread pool for simulating with the thread count from the
+/// command line or, if none is given, the CPU quota of the container, if any.
+///
+/// The global thread pool is left alone. Returns the request along with the
+/// pool, so that where the thread count came from can be logged once logging
+/// is ready.
+fn init_thread_pool(matches: &ArgMatches) -> Result<(ThreadPool, ThreadRequest), Error> {
+    let request = requested_thread_count(matches, var(THREADS_ENV_VAR).ok());
+
+    let mut pool = ThreadPoolBuilder::new().thread_name(|idx| format!("aitios-{}", idx));
+    if let Some(thread_count) = request.threads {
+        pool = pool.num_threads(thread_count);
+    }
+    let pool = pool
+        .build()
+        .context("Thread pool could not be set up with specified thread count.")?;
+
+    Ok((pool, request))
+}
+
+/// Determines the thread count from, in this order of precedence, the
+/// `--threads` flag, the given value of the environment variable, and the
+/// CPU quota of the container.
+fn requested_thread_count(matches: &ArgMatches, env_threads: Option<String>) -> ThreadRequest {
+    if let Some(thread_count) = matches.value_of("threads") {
+        return ThreadRequest {
+            // Can be unwrapped since validator checks this
+            threads: Some(usize::from_str_radix(&thread_count, 10).unwrap()),
+            cpu_limit: None,
+            invalid_env: None,
+        };
+    }
+
+    let invalid_env = match env_threads {
+        Some(env_threads) => match usize::from_str_radix(env_threads.trim(), 10) {
+            Ok(thread_count) if thread_count > 0 => {
+                return ThreadRequest {
+                    threads: Some(thread_count),
+                    cpu_limit: None,
+                    invalid_env: None,
+                }
+            }
+            _ => Some(env_threads),
+        },
+        None => None,
+    };
+
+    let cpu_limit = cgroup::cpu_limit();
+    ThreadRequest {
+        threads: cpu_limit.map(cgroup::threads_for_limit),
+        cpu_limit,
+        invalid_env,
+    }
+}
+
+fn init_simulation_builder(matches: &ArgMatches) -> Result<SimulationBuilder, Error> {
+    init_simulation_builder_from(matches, spec_file_args(matches))
+}
+
+/// Positional spec file arguments along with their argument indices.
+fn spec_file_args<'a>(matches: &'a ArgMatches) -> Vec<(usize, &'a str)> {
+    match matches.indices_of("SIMULATION_SPEC_FILE") {
+        Some(indices) => indices
+            .zip(
+                matches
+                    .values_of("SIMULATION_SPEC_FILE")
+                    .expect("Found simulation spec files but no indices"),
+            ).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Builds a simulation from the given spec files, interleaved with the inline
+/// specs in the order of their argument indices.
+fn init_simulation_builder_from(
+    matches: &ArgMatches,
+    spec_files: Vec<(usize, &str)>,
+) -> Result<SimulationBuilder, Error> {
+    let mut spec_file_paths = Some(spec_files.into_iter().peekable());
+
+    let mut inline_specs = matches.indices_of("spec").map(|i| {
+        i.zip(
+            matches
+                .values_of("spec")
+                .expect("Found spec indices but no values"),
+        ).peekable()
+    });
+
+    let mut builder = SimulationBuilder::new();
+
+    loop {
+        let advance_files = {
+            let next_file = spec_file_paths.as_mut().and_then(|f| f.peek());
+            let next_inline = inline_specs.as_mut().and_then(|i| i.peek());
+            match (next_file, next_inline) {
+                (None, None) => break,
+                (Some((file_idx, spec_file)), Some((inline_idx, spec_inline))) => {
+                    // Smaller idx first
+                    if file_idx < inline_idx {
+                        // Advance iterators, so we can terminate some time later
+                        builder = append_spec_file(builder, spec_file)?;
+                        true
+                    } else {
+                        builder = builder.append_spec_fragment_str(spec_inline)?;
+                        false
+                    }
+                }
+                (Some((_, spec_file)), None) => {
+                    builder = append_spec_file(builder, spec_file)?;
+                    true
+                }
+                (None, Some((_, spec_inline))) => {
+                    builder = builder.append_spec_fragment_str(spec_inline)?;
+                    false
+                }
+            }
+        };
+        if advance_files {
+            spec_file_paths
+                .as_mut()
+                .unwrap()
+                .next()
+                .expect("Could not advance iterator after peeking");
+        } else {
+            inline_specs
+                .as_mut()
+                .unwrap()
+                .next()
+                .expect("Could not advance iterator after peeking");
+        }
+    }
+
+    if let Some(assignments) = matches.values_of("set") {
+        for assignment in assignments {
+            // Can be unwrapped since validator checks this
+            let idx = assignment.find('=').unwrap();
+            builder = builder.set_field(&assignment[..idx], &assignment[(idx + 1)..])?;
+        }
+    }
+
+    if let Some(substances) = matches.values_of("only-substance") {
+        builder = builder.only_substances(substances);
+    }
+
+    if let Some(objects) = matches.values_of("object") {
+        builder = builder.select_objects(objects, matches.is_present("object-as-occluder"));
+    }
+
+    if matches.is_present("warn-unused-substances") {
+        builder = builder.warn_unused_substances(true);
+    }
+
+    if matches.is_present("first-hit-only") {
+        builder = builder.first_hit_only(true);
+    }
+
+    if profiling(matches) {
+        builder = builder.profile(true);
+    }
+
+    if matches.is_present("strict") {
+        builder = builder.strict(true);
+    }
+
+    if let Some(region_mask) = matches.value_of("region-mask") {
+        builder = builder.region_mask(region_mask)?;
+    }
+
+    if let Some(filter) = matches.value_of("bake-filter") {
+        let radius = matches.value_of("bake-radius").map(|r| r.parse().unwrap());
+        builder = builder.bake_filter(filter.parse().map_err(err_msg)?, radius);
+    }
+
+    if let Some(bits) = matches.value_of("output-bit-depth") {
+        // Can be unwrapped since clap checks the possible values
+        builder = builder.output_bit_depth(bits.parse().unwrap());
+    }
+
+    if let Some(iterations) = matches.value_of("iterations") {
+        // Can be unwrapped since validator checks this
+        builder = builder.iterations(u32::from_str_radix(iterations, 10).unwrap());
+    }
+
+    if let Some(density) = matches.value_of("surfel-density") {
+        // Can be unwrapped since validator checks this
+        builder = builder.surfel_density(density.parse().unwrap());
+    }
+
+    if let Some(resolution) = matches.value_of("texture-resolution") {
+        // Can be unwrapped since validator checks this
+        let (width, height) = parse_resolution(resolution).unwrap();
+        builder = builder.texture_resolution(width, height);
+    }
+
+    if let Some(limit) = matches.value_of("limit-triangles") {
+        // Can be unwrapped since validator checks this
+        builder = builder.limit_triangles(usize::from_str_radix(limit, 10).unwrap());
+    }
+
+    if matches.is_present("bench") {
+        builder = builder.skip_outputs(true);
+    }
+
+    if let Some(direction) = matches.value_of("flow-direction") {
+        // Can be unwrapped since validator checks this
+        builder = builder.flow_direction(parse_vector(direction).unwrap());
+    }
+
+    if let Some(count) = matches.value_of("gammaton-count") {
+        // Can be unwrapped since validator checks this
+        builder = builder.emission_count(usize::from_str_radix(count, 10).unwrap());
+    }
+
+    // After all fragments have been appended, so all of their outputs are relocated
+    if let Some(output_dir) = expanded_output_dir(matches, &builder) {
+        builder = builder.output_dir(output_dir);
+    }
+
+    Ok(builder)
+}
+
+/// Appends the spec file at the given path, or the spec read from stdin
+/// if the path is `-`.
+fn append_spec_file(
+    builder: SimulationBuilder,
+    spec_file: &str,
+) -> Result<SimulationBuilder, Error> {
+    if spec_file == "-" {
+        Ok(builder
+            .append_spec_fragment_reader(stdin())
+            .context("Failed to read simulation spec from stdin")?)
+    } else {
+        Ok(builder.append_spec_fragment_file(spec_file)?)
+    }
+}
+
+/// Initializes logging using the given argument matching result
+/// and an optional additional log path.
+///
+/// If the terminal logger cannot be set up, tries to set up fallback
+/// terminal logging instead and returns Ok(()) if successful.
+///
+/// Otherwise, tries to apply the logging config and returns Ok(()) if
+/// successful, or some Err value if e.g. a log file could not be created.
+fn init_logging(
+    matches: &ArgMatches,
+    additional_log_path: &Option<PathBuf>,
+    datetime: &str,
+    spec_name: &str,
+) -> Result<(), Error> {
+    let terminal = match terminal_logger(matches) {
+        Ok(terminal) => terminal,
+        Err(_) => return init_logging_fallback(),
+    };
+
+    configure_logging(
+        matches,
+        terminal,
+        additional_log_path
+            .as_ref()
+            .map(|p| p.to_string_lossy())
+            .iter(),
+        datetime,
+        spec_name,
+    )
+}
+
+/// Makes the only logger log to stdout as a fallback if logging setup did not
+/// work out as planned.
+///
+/// If a logger is already installed, e.g. when running more than once in the
+/// same process, it is kept.
+fn init_logging_fallback() -> Result<(), Error> {
+    let level = LevelFilter::Warn;
+    let terminal: Box<SharedLogger> = match TermLogger::new(level, Config::default()) {
+        Some(logger) => logger,
+        None => PlainTermLogger::new(level, Config::default()),
+    };
+    install_loggers(vec![terminal, WarningCounter::new()]);
+    Ok(())
+}
+
+/// Installs terminal logging and logging into the given log files, which
+/// can be opened later.
+///
+/// Falls back to terminal only logging if the terminal logger cannot be set
+/// up, like `init_logging`.
+fn init_batch_logging<W>(arg_matches: &ArgMatches, log_files: W) -> Result<(), Error>
+where
+    W: Write + Send + 'static,
+{
+    let terminal = match terminal_logger(arg_matches) {
+        Ok(terminal) => terminal,
+        Err(_) => return init_logging_fallback(),
+    };
+
+    let mut loggers = vec![terminal, file_logger(arg_matches, log_files), WarningCounter::new()];
+    loggers.extend(syslog_logger(arg_matches)?);
+    install_loggers(loggers);
+
+    Ok(())
+}
+
+/// Installs the given loggers as the only logger.
+///
+/// If a logger is already installed, e.g. when running more than once in the
+/// same process, it is kept.
+fn install_loggers(loggers: Vec<Box<SharedLogger>>) {
+    // An error means a logger is already installed, which is kept
+    let _ = CombinedLogger::init(loggers);
+}
+
+fn terminal_logger(arg_matches: &ArgMatches) -> Result<Box<SharedLogger>, Error> {
+    let targets = target_levels(arg_matches);
+    let level = terminal_log_level(arg_matches);
+    let max_level = targets.max_level(level);
+    let logger: Box<SharedLogger> = if arg_matches.value_of("log-format") == Some("json") {
+        JsonLogger::new(max_level, log_timezone(arg_matches), stderr())
+    } else if color_choice(arg_matches).colorize(atty::is(Stream::Stdout)) {
+        TermLogger::new(max_level, Config::default())
+            .ok_or(err_msg("Failed to set up logging to terminal."))?
+    } else {
+        PlainTermLogger::new(max_level, Config::default())
+    };
+    Ok(targets.filter(level, logger))
+}
+
+/// Levels for specific targets set with `--verbose-target`.
+fn target_levels(arg_matches: &ArgMatches) -> TargetLevels {
+    // Can be unwrapped since validator checks this
+    TargetLevels::parse(arg_matches.values_of("verbose-target").into_iter().flatten()).unwrap()
+}
+
+/// Colorization of terminal logs, as chosen with `--color` and `NO_COLOR`.
+fn color_choice(arg_matches: &ArgMatches) -> ColorChoice {
+    let no_color = var_os("NO_COLOR");
+    ColorChoice::from_arg(arg_matches.value_of("color"), no_color.as_ref().map(|v| v.as_os_str()))
+}
+
+fn file_logger<W>(arg_matches: &ArgMatches, sink: W) -> Box<SharedLogger>
+where
+    W: Write + Send + 'static,
+{
+    let targets = target_levels(arg_matches);
+    let level = file_log_level(arg_matches);
+    let max_level = targets.max_level(level);
+    let timezone = log_timezone(arg_matches);
+    let logger: Box<SharedLogger> = if arg_matches.value_of("log-format") == Some("json") {
+        JsonLogger::new(max_level, timezone, sink)
+    } else {
+        // Write each record at once so readers following the file never see
+        // partial lines, and to timestamp each record only once
+        let sink = WholeLines::new(Timestamped::new(sink, timezone));
+        WriteLogger::new(max_level, file_log_config(), sink)
+    };
+    targets.filter(level, logger)
+}
+
+/// Logger for syslog if enabled with `--syslog`.
+#[cfg(unix)]
+fn syslog_logger(arg_matches: &ArgMatches) -> Result<Option<Box<SharedLogger>>, Error> {
+    if !arg_matches.is_present("syslog") {
+        return Ok(None);
+    }
+
+    // Can be unwrapped since there is a default
+    let facility = arg_matches.value_of("syslog-facility").unwrap();
+    let targets = target_levels(arg_matches);
+    let level = file_log_level(arg_matches);
+    let logger = SyslogLogger::new(targets.max_level(level), facility).map_err(err_msg)?;
+    Ok(Some(targets.filter(level, logger)))
+}
+
+#[cfg(not(unix))]
+fn syslog_logger(arg_matches: &ArgMatches) -> Result<Option<Box<SharedLogger>>, Error> {
+    if arg_matches.is_present("syslog") {
+        Err(err_msg("Logging to syslog is only supported on Unix-like systems."))
+    } else {
+        Ok(None)
+    }
+}
+
+fn configure_logging<I, S>(
+    arg_matches: &ArgMatches,
+    terminal: Box<SharedLogger>,
+    additional_logs: I,
+    datetime: &str,
+    spec_name: &str,
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let loggers = loggers(arg_matches, terminal, additional_logs, datetime, spec_name)?;
+    install_loggers(loggers);
+
+    Ok(())
+}
+
+/// Loggers for the given terminal logger, syslog if enabled, and the log
+/// files given on the command line and in the spec.
+fn loggers<I, S>(
+    arg_matches: &ArgMatches,
+    terminal: Box<SharedLogger>,
+    additional_logs: I,
+    datetime: &str,
+    spec_name: &str,
+) -> Result<Vec<Box<SharedLogger>>, Error>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut loggers = vec![terminal, WarningCounter::new()];
+    loggers.extend(syslog_logger(arg_matches)?);
+
+    let log_paths =
+        canonical_log_file_paths(arg_matches, additional_logs, datetime, spec_name)?;
+    // Paths of logs that did not exist yet may still turn out to be the
+    // same after creating them, e.g. through symlinks
+    let logs = create_distinct_files(log_paths).context("Failed to create log file.")?;
+    for log in logs.into_iter() {
+        loggers.push(file_logger(arg_matches, log));
+    }
+
+    Ok(loggers)
+}
+
+fn log_timezone(arg_matches: &ArgMatches) -> LogTimezone {
+    LogTimezone::from_arg(arg_matches.value_of("log-timezone"))
+}
+
+fn verbosity(arg_matches: &ArgMatches) -> LevelFilter {
+    // Nothing => warn, -v => Info, -vv => Debug
+    match arg_matches.occurrences_of("verbose") {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        _ => LevelFilter::Debug,
+    }
+}
+
+/// Level of the terminal logger, `Off` with `--quiet`.
+fn terminal_log_level(arg_matches: &ArgMatches) -> LevelFilter {
+    if arg_matches.is_present("quiet") {
+        LevelFilter::Off
+    } else {
+        verbosity(arg_matches)
+    }
+}
+
+/// Level of log files, as set with `--log-level` or otherwise the same as
+/// for the terminal.
+fn file_log_level(arg_matches: &ArgMatches) -> LevelFilter {
+    match arg_matches.value_of("log-level") {
+        Some("off") => LevelFilter::Off,
+        Some("error") => LevelFilter::Error,
+        Some("warn") => LevelFilter::Warn,
+        Some("info") => LevelFilter::Info,
+        Some("debug") => LevelFilter::Debug,
+        Some("trace") => LevelFilter::Trace,
+        _ => verbosity(arg_matches),
+    }
+}
+
+fn canonical_log_file_paths<I, S>(
+    arg_matches: &ArgMatches,
+    additional_logs: I,
+    datetime: &str,
+    spec_name: &str,
+) -> Result<HashSet<PathBuf>, Error>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut log_files = Vec::new();
+
+    // First add log files explicitly specified with CLI args
+    if let Some(log_file_args) = arg_matches.values_of("log") {
+        log_files.extend(log_file_args.map(|a| log_arg_to_log_path(a, datetime, spec_name)))
+    }
+
+    // If more log arguments were specified than log file names,
+    // add exactly one additional log with a default log name.
+    //
+    // e.g. `aitios-cli sim.yml -l asdf.log -l` will log to both
+    // asdf.log and the default log filename below the cwd.
+    if (log_files.len() as u64) < (arg_matches.occurrences_of("log") as u64)
+        && !arg_matches.is_present("no-default-log")
+    {
+        log_files.push(log_arg_to_log_path(
+            &synthesize_datetime_log_filename(datetime),
+            datetime,
+            spec_name,
+        ));
+    }
+
+    // Finally add the additional log files, most likely coming from
+    // simulation configuration.
+    log_files.extend(
+        additional_logs
+            .into_iter()
+            .map(|l| log_arg_to_log_path(l.as_ref(), datetime, spec_name)),
+    );
+
+    // Canonicalize paths, filter out duplicates and abort on any errors
+    log_files.into_iter().collect()
+}
+
+fn log_arg_to_log_path(arg: &str, datetime: &str, spec_name: &str) -> Result<PathBuf, Error> {
+    // Replace {datetime} pattern with filename safe timestamp, and
+    // {spec_name} with the stem of the spec file
+    let arg = arg
+        .replace("{datetime}", datetime)
+        .replace("{spec_name}", spec_name);
+    let path: &Path = arg.as_ref();
+
+    if path.is_dir() {
+        // If directory given, append default log filename
+        let mut path = path.canonicalize()?;
+        path.push(synthesize_datetime_log_filename(datetime));
+        Ok(path)
+    } else if path.is_file() {
+        // Existing, regular file, return canonicalized form for overwrite
+        Ok(path.canonicalize()?)
+    } else {
+        match path.parent() {
+            // Relative one-level path returns Ok(""), just create the file,
+            // under cwd since . is the implicit parent and always exists.
+            Some(parent) if parent.as_os_str().is_empty() => {
+                let mut new_path = current_dir()?.canonicalize()?;
+                new_path.push(&arg);
+                Ok(new_path)
+            }
+            // If immediate parent is an existing directory other than "",
+            // canonicalize it, and append the final path component again.
+            Some(parent) if parent.is_dir() => {
+                let mut new_path = parent.canonicalize()?;
+                new_path.push(path.file_name().unwrap());
+                Ok(new_path)
+            }
+            // Ok, some nonexisting parent, try to create it
+            Some(parent) => {
+                create_dir_all(parent)
+                    .with_context(|_| format!("Could not create log directory {:?}", parent))?;
+                let mut new_path = parent.canonicalize()?;
+                new_path.push(path.file_name().unwrap());
+                Ok(new_path)
+            }
+            // Something about the path is wrong, stop trying
+            _ => Err(format_err!("Log file path \"{}\" cannot be resolved", arg)),
+        }
+    }
+}
+
+/// Synthesize a default filename if -l or --log is passed without an actual filename.
+fn synthesize_datetime_log_filename(datetime: &str) -> String {
+    format!("aitios-log-{datetime}.log", datetime = datetime)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::prelude::*;
+    use files::fs_timestamp;
+    use runner::BAKE_FILTER_NAMES;
+    use std::env::temp_dir;
+    use std::fs::{read_dir, read_to_string, remove_dir_all, write, File};
+    use std::iter;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_log_arg_with_datetime() {
+        let time = Local::now();
+
+        let expected = {
+            let mut expected = current_dir().unwrap();
+            expected.push(format!(
+                "logovic-{datetime}.log",
+                datetime = fs_timestamp(time)
+            ));
+            expected
+        };
+
+        let actual =
+            log_arg_to_log_path("./logovic-{datetime}.log", &fs_timestamp(time), "spec").unwrap();
+
+        assert_eq!(expected.as_os_str().len(), actual.as_os_str().len());
+        // when truncating until days, the test should always work,
+        // except if we are very unlucky and midnight passes inbetween the
+        // datetime formattings
+        let len_until_day_of_month =
+            current_dir().unwrap().to_str().unwrap().len() + "/logovic-2018-07-17T18_06_53".len();
+        assert_eq!(
+            expected
+                .to_str()
+                .unwrap()
+                .to_string()
+                .truncate(len_until_day_of_month),
+            actual
+                .to_str()
+                .unwrap()
+                .to_string()
+                .truncate(len_until_day_of_month)
+        )
+    }
+
+    #[test]
+    fn test_log_arg_with_dot() {
+        let time = Local::now();
+        assert_eq!(
+            {
+                let mut expected = current_dir().unwrap();
+                expected.push("loggy.log");
+                expected
+            },
+            log_arg_to_log_path("./loggy.log", &fs_timestamp(time), "spec").unwrap()
+        )
+    }
+
+    #[test]
+    fn test_log_arg_with_dotdot() {
+        let time = Local::now();
+        assert_eq!(
+            {
+                let mut expected = current_dir().unwrap();
+                expected.pop();
+                expected.push("loggy.log");
+                expected
+            },
+            log_arg_to_log_path("../loggy.log", &fs_timestamp(time), "spec").unwrap()
+        )
+    }
+
+    #[test]
+    fn no_log_file_when_no_log_arg() {
+        let matches =
+            new_app().get_matches_from(vec!["aitios-cli", "tests/examples/simulation.yml"]);
+
+        let log_file_paths = canonical_log_file_paths(
+            &matches,
+            iter::empty::<&str>(),
+            &fs_timestamp(Local::now()),
+            "spec",
+        ).expect("Expect canonical log file calculation to succeed with no log switch");
+
+        assert!(
+            log_file_paths.is_empty(),
+            "Expected no log files without log switch."
+        )
+    }
+
+    #[test]
+    fn no_default_log_name_added_if_disabled() {
+        let matches = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "tests/examples/simulation.yml",
+            "-l",
+            "--no-default-log",
+        ]);
+
+        let log_file_paths = canonical_log_file_paths(
+            &matches,
+            iter::empty::<&str>(),
+            &fs_timestamp(Local::now()),
+            "spec",
+        ).unwrap();
+
+        assert!(
+            log_file_paths.is_empty(),
+            "Expected no log files with --no-default-log, but got {:?}",
+            log_file_paths
+        );
+    }
+
+    #[test]
+    fn test_default_log_name_added() {
+        let matches =
+            new_app().get_matches_from(vec!["aitios-cli", "tests/examples/simulation.yml", "-l"]);
+
+        let log_file_paths = canonical_log_file_paths(
+            &matches,
+            iter::empty::<&str>(),
+            &fs_timestamp(Local::now()),
+            "spec",
+        ).expect(
+            "Expect canonical log file calculation to succeed with value-less log switch",
+        );
+
+        assert_eq!(
+            1,
+            log_file_paths.len(),
+            "Expected exactly one log file path, namely the default one."
+        );
 
-        assert_eq!(expected.as_os_str().len(), actual.as_os_str().len());
         // when truncating until days, the test should always work,
         // except if we are very unlucky and midnight passes inbetween the
         // datetime formattings
-        let len_until_day_of_month =
-            current_dir().unwrap().to_str().unwrap().len() + "/logovic-2018-07-17T18_06_53".len();
+        let time = Local::now();
+        let len_until_day_of_month = current_dir().unwrap().to_str().unwrap().len()
+            + "/".len()
+            + synthesize_datetime_log_filename(&fs_timestamp(time)).len()
+            - "_53.800963444+02_00.log".len();
+        let mut expected = {
+            let mut expected = current_dir().unwrap();
+            expected.push(synthesize_datetime_log_filename(&fs_timestamp(time)));
+            expected.to_str().unwrap().to_string()
+        };
+        let mut actual = log_file_paths
+            .into_iter()
+            .next()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
         assert_eq!(
-            expected
-                .to_str()
-                .unwrap()
-                .to_string()
-                .truncate(len_until_day_of_month),
-            actual
-                .to_str()
-                .unwrap()
-                .to_string()
-                .truncate(len_until_day_of_month)
-        )
+            expected.len(),
+            actual.len(),
+            "The default added log name has an unexpected length"
+        );
+
+        expected.truncate(len_until_day_of_month);
+        actual.truncate(len_until_day_of_month);
+        assert_eq!(
+            expected, actual,
+            "The default added log name looks different in the part until day of month"
+        );
     }
 
     #[test]
-    fn test_log_arg_with_dot() {
-        let time = Local::now();
+    fn directory_as_log_path() {
+        let matches = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "tests/examples/simulation.yml",
+            "-l",
+            "..",
+        ]);
+
+        let log_file_paths = canonical_log_file_paths(
+            &matches,
+            iter::empty::<&str>(),
+            &fs_timestamp(Local::now()),
+            "spec",
+        ).expect("Expect canonical log file calculation to succeed");
+
         assert_eq!(
-            {
-                let mut expected = current_dir().unwrap();
-                expected.push("loggy.log");
-                expected
-            },
-            log_arg_to_log_path("./loggy.log", &fs_timestamp(time)).unwrap()
-        )
+            1,
+            log_file_paths.len(),
+            "Expected 1 log file under the parent directory but got {:?}.",
+            &log_file_paths
+        );
+
+        let mut parent_dir = current_dir().unwrap();
+        parent_dir.pop();
+
+        let mut parent_of_log_file_path = log_file_paths.iter().next().unwrap().clone();
+        parent_of_log_file_path.pop();
+
+        assert_eq!(
+            parent_dir, parent_of_log_file_path,
+            "Expected generated default log file to be in parent directory but got {:?}.",
+            &log_file_paths
+        );
+    }
+
+    #[test]
+    fn create_intermediary_directories() {
+        let pattern = "/tmp/{datetime}/some/dir/logggg.log";
+        let timestamp = fs_timestamp(Local::now());
+        let expected_log_path = pattern.replace("{datetime}", &timestamp);
+        let matches = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "tests/examples/simulation.yml",
+            "-l",
+            pattern,
+        ]);
+
+        let log_file_paths =
+            canonical_log_file_paths(&matches, iter::empty::<&str>(), &timestamp, "spec")
+                .expect("Expect canonical log file calculation to succeed");
+
+        assert!(
+            Path::new(&expected_log_path).parent().unwrap().is_dir(),
+            "Expected canonical log file path lookup to create the necessary directories"
+        );
+
+        assert_eq!(1, log_file_paths.len());
+    }
+
+    #[test]
+    fn test_duplicate_log_file_removal() {
+        let matches = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "tests/examples/simulation.yml",
+            "-l",
+            "log1.log",
+            "-l",
+            "././log1.log",
+            "-l",
+            ".",
+            "-l",
+        ]);
+
+        let log_file_paths = canonical_log_file_paths(
+            &matches,
+            ["log2.log", "./log1.log", "."].iter(),
+            &fs_timestamp(Local::now()),
+            "spec",
+        ).expect("Expect canonical log file calculation to succeed");
+
+        assert_eq!(
+            3,
+            log_file_paths.len(),
+            "Expected 3 log files (log1.log, log2.log, aitios-log-DATE.log), but found {:?}.",
+            log_file_paths
+        );
+    }
+
+    #[test]
+    fn describe_defaults_to_summary() {
+        let matches =
+            new_app().get_matches_from(vec!["aitios-cli", "tests/examples/simulation.yml"]);
+        assert_eq!(Some("summary"), matches.value_of("describe"));
+
+        let matches = new_app().get_matches_from_safe(vec![
+            "aitios-cli",
+            "tests/examples/simulation.yml",
+            "--describe",
+            "everything",
+        ]);
+        assert!(matches.is_err(), "Expected unknown description detail to be rejected");
+    }
+
+    #[test]
+    fn effective_config_resolves_defaults() {
+        let matches =
+            new_app().get_matches_from(vec!["aitios-cli", "tests/examples/simulation.yml"]);
+
+        let config = effective_config(&matches, &SimulationBuilder::new())
+            .expect("Expected effective config to be resolvable without log switches");
+
+        assert!(config.threads > 0, "Expected resolved thread count");
+        assert_eq!("warn", config.verbosity);
+        assert_eq!(vec!["tests/examples/simulation.yml"], config.spec_files);
+        assert!(config.log_files.is_empty());
+        assert_eq!(0, config.retries);
+        assert_eq!(8, config.output_bit_depth);
+        assert!(serde_json::to_string(&config).is_ok());
+    }
+
+    #[test]
+    fn output_bit_depth_overrides_spec() {
+        let matches = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "--output-bit-depth",
+            "16",
+        ]);
+        let builder = init_simulation_builder(&matches).unwrap();
+        assert_eq!(Some(16), builder.spec().output_bit_depth);
+        assert_eq!(16, effective_config(&matches, &builder).unwrap().output_bit_depth);
+
+        let invalid = new_app().get_matches_from_safe(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "--output-bit-depth",
+            "12",
+        ]);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn threads_flag_is_used() {
+        let matches = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "-t",
+            "2",
+            "tests/examples/simulation.yml",
+        ]);
+
+        let request = requested_thread_count(&matches, None);
+        assert_eq!(Some(2), request.threads);
+        assert_eq!(None, request.cpu_limit);
+    }
+
+    #[test]
+    fn threads_from_env() {
+        let matches =
+            new_app().get_matches_from(vec!["aitios-cli", "tests/examples/simulation.yml"]);
+
+        let request = requested_thread_count(&matches, Some(String::from("3")));
+        assert_eq!(Some(3), request.threads);
+        assert_eq!(None, request.invalid_env);
+    }
+
+    #[test]
+    fn threads_flag_wins_over_env() {
+        let matches = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "-t",
+            "2",
+            "tests/examples/simulation.yml",
+        ]);
+
+        let request = requested_thread_count(&matches, Some(String::from("3")));
+        assert_eq!(Some(2), request.threads);
+    }
+
+    #[test]
+    fn invalid_threads_env_is_ignored() {
+        let matches =
+            new_app().get_matches_from(vec!["aitios-cli", "tests/examples/simulation.yml"]);
+
+        for invalid in vec!["0", "-1", "many", ""] {
+            let request = requested_thread_count(&matches, Some(String::from(invalid)));
+            assert_eq!(Some(String::from(invalid)), request.invalid_env);
+            assert_eq!(
+                cgroup::cpu_limit().map(cgroup::threads_for_limit),
+                request.threads
+            );
+        }
+    }
+
+    #[test]
+    fn uncreatable_log_directory_is_error() {
+        // Cannot create a directory below a regular file, even as root
+        let result = log_arg_to_log_path("Cargo.toml/logs/aitios.log", "now", "spec");
+        assert!(result.is_err());
+
+        let result = run_with_args(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "-l",
+            "Cargo.toml/logs/x.log",
+            "--dry-run",
+        ]);
+        let message = format!("{}", result.unwrap_err());
+        assert!(message.contains("Could not create log directory"), "{}", message);
+    }
+
+    #[test]
+    fn file_log_level_defaults_to_verbosity() {
+        let matches =
+            new_app().get_matches_from(vec!["aitios-cli", "-vv", "tests/examples/simulation.yml"]);
+        assert_eq!(LevelFilter::Debug, file_log_level(&matches));
+
+        let matches = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "-vv",
+            "--log-level",
+            "info",
+            "tests/examples/simulation.yml",
+            "-l",
+            "out.log",
+        ]);
+        assert_eq!(LevelFilter::Info, file_log_level(&matches));
+        assert_eq!(LevelFilter::Debug, verbosity(&matches));
+    }
+
+    #[test]
+    fn quiet_turns_terminal_logging_off() {
+        let matches =
+            new_app().get_matches_from(vec!["aitios-cli", "-q", "tests/examples/simulation.yml"]);
+        assert_eq!(LevelFilter::Off, terminal_log_level(&matches));
+        assert_eq!(LevelFilter::Warn, file_log_level(&matches));
+
+        let matches = new_app().get_matches_from_safe(vec![
+            "aitios-cli",
+            "-q",
+            "-v",
+            "tests/examples/simulation.yml",
+        ]);
+        assert!(matches.is_err(), "Expected --quiet and --verbose to conflict");
+    }
+
+    #[test]
+    fn dry_run_loads_without_simulating() {
+        let dir = temp_dir().join("aitios-dry-run-test");
+        remove_dir_all(&dir).ok();
+        create_dir_all(&dir).unwrap();
+        let cwd = current_dir().unwrap();
+        let spec = dir.join("dry.yml");
+        write(
+            &spec,
+            format!(
+                "scenes: [{:?}]\nsurfels_by_material:\n  _: {:?}\nsources: [{:?}]\nsurfel_distance: 2.0\neffects:\n  - dump_surfels:\n      obj_pattern: {:?}",
+                cwd.join("tests/assets/sky.obj"),
+                cwd.join("tests/examples/concrete.yml"),
+                cwd.join("tests/examples/rain.yml"),
+                dir.join("outputs/dry-{iteration}.obj")
+            ),
+        ).unwrap();
+
+        let result = run_with_args(vec!["aitios-cli", spec.to_str().unwrap(), "--dry-run"]);
+
+        assert!(result.is_ok(), "Expected dry run to succeed: {:?}", result);
+        // Not even the effects of iteration 0 have run
+        let files: Vec<_> = read_dir(&dir)
+            .unwrap()
+            .map(|f| f.unwrap().file_name())
+            .collect();
+        remove_dir_all(&dir).unwrap();
+        assert_eq!(vec![OsString::from("dry.yml")], files, "Expected no outputs to be written");
+    }
+
+    #[test]
+    fn list_effects_without_spec() {
+        let list = effect_list();
+        for effect in &["density", "export", "layer", "dump_surfels"] {
+            assert!(list.contains(effect), "Expected {} in {}", effect, list);
+        }
+
+        let listed = run_with_args(vec!["aitios-cli", "--list-effects"]);
+        assert!(listed.is_ok(), "Expected listing to succeed: {:?}", listed);
+    }
+
+    #[test]
+    fn version_json_without_spec() {
+        let printed = run_with_args(vec!["aitios-cli", "--version-json"]);
+        assert!(printed.is_ok(), "Expected version to be printed: {:?}", printed);
+    }
+
+    #[test]
+    fn config_schema_without_spec() {
+        let schema: serde_json::Value = serde_json::from_str(&spec_schema().unwrap()).unwrap();
+        assert!(schema["properties"]["effects"].is_object(), "{}", schema);
+
+        let dumped = run_with_args(vec!["aitios-cli", "--dump-config-schema"]);
+        assert!(dumped.is_ok(), "Expected schema dump to succeed: {:?}", dumped);
+    }
+
+    #[test]
+    fn validate_lists_problems() {
+        let valid = run_with_args(vec!["aitios-cli", "validate", "tests/examples/sky.yml"]);
+        assert!(valid.is_ok(), "Expected sky to be valid: {:?}", valid);
+
+        let invalid = run_with_args(vec![
+            "aitios-cli",
+            "validate",
+            "tests/examples/sky.yml",
+            "-s",
+            "scenes: [does-not-exist.obj]",
+        ]);
+        assert!(invalid.is_err(), "Expected missing scene to be a problem");
+    }
+
+    #[test]
+    fn output_dir_contains_all_outputs() {
+        let matches = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "--output-dir",
+            "/tmp/run1",
+        ]);
+        let runner = init_simulation_builder(&matches).unwrap().build().unwrap();
+
+        let outputs = runner.plan().outputs;
+        assert!(!outputs.is_empty());
+        for output in outputs {
+            assert!(
+                output.path.starts_with("/tmp/run1/"),
+                "Expected output below output dir: {}",
+                output.path
+            );
+        }
     }
 
     #[test]
-    fn test_log_arg_with_dotdot() {
-        let time = Local::now();
+    fn output_dir_template_is_expanded() {
         assert_eq!(
-            {
-                let mut expected = current_dir().unwrap();
-                expected.pop();
-                expected.push("loggy.log");
-                expected
-            },
-            log_arg_to_log_path("../loggy.log", &fs_timestamp(time)).unwrap()
-        )
+            "runs/sky/2018-06-01_12-00-00",
+            expand_output_dir("runs/{spec}/{datetime}", "sky", "2018-06-01_12-00-00")
+        );
+        assert_eq!("runs/sky", expand_output_dir("runs/{spec_name}", "sky", "now"));
+        assert_eq!("runs", expand_output_dir("runs", "sky", "now"));
     }
 
     #[test]
-    fn no_log_file_when_no_log_arg() {
-        let matches =
-            new_app().get_matches_from(vec!["aitios-cli", "tests/examples/simulation.yml"]);
+    fn existing_output_dir_requires_force() {
+        let dir = temp_dir().join("aitios-output-dir-collision-test");
+        if dir.exists() {
+            remove_dir_all(&dir).unwrap();
+        }
+        create_dir_all(&dir).unwrap();
+        let spec = write_batch_spec(&dir, "collide");
+        let output_dir = dir.join("runs/{spec}").to_string_lossy().into_owned();
+        let args = |force: bool| {
+            let mut args = vec![
+                "aitios-cli",
+                spec.as_str(),
+                "--output-dir",
+                output_dir.as_str(),
+                "--manifest",
+            ];
+            if force {
+                args.push("--force");
+            }
+            args
+        };
 
-        let log_file_paths =
-            canonical_log_file_paths(&matches, iter::empty::<&str>(), &fs_timestamp(Local::now()))
-                .expect("Expect canonical log file calculation to succeed with no log switch");
+        let first = run_with_args(args(false));
+        let expanded = dir.join("runs/collide/manifest.json").is_file();
+        let collision = run_with_args(args(false));
+        let forced = run_with_args(args(true));
+        remove_dir_all(&dir).unwrap();
 
-        assert!(
-            log_file_paths.is_empty(),
-            "Expected no log files without log switch."
-        )
+        assert!(first.is_ok(), "{:?}", first);
+        assert!(expanded, "Expected manifest in the expanded output directory");
+        let message = format!("{}", collision.unwrap_err());
+        assert!(message.contains("already exists"), "{}", message);
+        assert!(forced.is_ok(), "Expected --force to reuse the directory: {:?}", forced);
     }
 
     #[test]
-    fn test_default_log_name_added() {
-        let matches =
-            new_app().get_matches_from(vec!["aitios-cli", "tests/examples/simulation.yml", "-l"]);
+    fn batch_specs_resolving_to_same_output_dir_collide() {
+        let dir = temp_dir().join("aitios-batch-output-dir-test");
+        if dir.exists() {
+            remove_dir_all(&dir).unwrap();
+        }
+        create_dir_all(&dir).unwrap();
+        let a = write_batch_spec(&dir, "a");
+        let b = write_batch_spec(&dir, "b");
+        let per_spec = dir.join("{spec}").to_string_lossy().into_owned();
+        let shared = dir.join("shared").to_string_lossy().into_owned();
 
-        let log_file_paths =
-            canonical_log_file_paths(&matches, iter::empty::<&str>(), &fs_timestamp(Local::now()))
-                .expect(
-                    "Expect canonical log file calculation to succeed with value-less log switch",
-                );
+        let distinct = run_with_args(vec![
+            "aitios-cli",
+            "--batch",
+            a.as_str(),
+            b.as_str(),
+            "--output-dir",
+            per_spec.as_str(),
+            "--manifest",
+        ]);
+        let manifests = (
+            dir.join("a/manifest.json").is_file(),
+            dir.join("b/manifest.json").is_file(),
+        );
+        let colliding = run_with_args(vec![
+            "aitios-cli",
+            "--batch",
+            a.as_str(),
+            b.as_str(),
+            "--output-dir",
+            shared.as_str(),
+        ]);
+        remove_dir_all(&dir).unwrap();
 
-        assert_eq!(
-            1,
-            log_file_paths.len(),
-            "Expected exactly one log file path, namely the default one."
+        assert!(distinct.is_ok(), "{:?}", distinct);
+        assert_eq!((true, true), manifests);
+        let message = format!("{}", colliding.unwrap_err());
+        assert!(message.contains("1 of 2 simulations failed"), "{}", message);
+    }
+
+    /// Writes a spec for the sky example with absolute paths and its own log.
+    fn write_batch_spec(dir: &Path, name: &str) -> String {
+        let cwd = current_dir().unwrap();
+        let path = dir.join(format!("{}.yml", name));
+        let spec = format!(
+            "scenes: [{:?}]\nsurfels_by_material:\n  _: {:?}\nsources: [{:?}]\nsurfel_distance: 2.0\nlog: {:?}\neffects:\n  - dump_surfels:\n      obj_pattern: {:?}",
+            cwd.join("tests/assets/sky.obj"),
+            cwd.join("tests/examples/concrete.yml"),
+            cwd.join("tests/examples/rain.yml"),
+            dir.join(format!("{}.log", name)),
+            dir.join(format!("{}-{{iteration}}.obj", name))
         );
+        write(&path, spec).unwrap();
+        path.to_string_lossy().into_owned()
+    }
 
-        // when truncating until days, the test should always work,
-        // except if we are very unlucky and midnight passes inbetween the
-        // datetime formattings
-        let time = Local::now();
-        let len_until_day_of_month = current_dir().unwrap().to_str().unwrap().len()
-            + "/".len()
-            + synthesize_datetime_log_filename(&fs_timestamp(time)).len()
-            - "_53.800963444+02_00.log".len();
-        let mut expected = {
-            let mut expected = current_dir().unwrap();
-            expected.push(synthesize_datetime_log_filename(&fs_timestamp(time)));
-            expected.to_str().unwrap().to_string()
-        };
-        let mut actual = log_file_paths
-            .into_iter()
-            .next()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
+    #[test]
+    fn batch_runs_each_spec_with_own_log() {
+        let dir = temp_dir().join("aitios-batch-test");
+        remove_dir_all(&dir).ok();
+        create_dir_all(&dir).unwrap();
+        let a = write_batch_spec(&dir, "a");
+        let b = write_batch_spec(&dir, "b");
+
+        let result = run_with_args(vec![
+            "aitios-cli",
+            "--batch",
+            a.as_str(),
+            b.as_str(),
+            "--dry-run",
+        ]);
+
+        assert!(result.is_ok(), "Expected both specs to load: {:?}", result);
+        assert!(dir.join("a.log").is_file());
+        assert!(dir.join("b.log").is_file());
+    }
 
+    #[test]
+    fn batch_continues_unless_fail_fast() {
+        let dir = temp_dir().join("aitios-batch-fail-test");
+        remove_dir_all(&dir).ok();
+        create_dir_all(&dir).unwrap();
+        let a = write_batch_spec(&dir, "a");
+
+        let result = run_with_args(vec![
+            "aitios-cli",
+            "--batch",
+            "does-not-exist.yml",
+            a.as_str(),
+            "--dry-run",
+        ]);
         assert_eq!(
-            expected.len(),
-            actual.len(),
-            "The default added log name has an unexpected length"
+            "1 of 2 simulations failed.",
+            format!("{}", result.unwrap_err())
         );
 
-        expected.truncate(len_until_day_of_month);
-        actual.truncate(len_until_day_of_month);
-        assert_eq!(
-            expected, actual,
-            "The default added log name looks different in the part until day of month"
+        let result = run_with_args(vec![
+            "aitios-cli",
+            "--batch",
+            "--fail-fast",
+            "does-not-exist.yml",
+            a.as_str(),
+            "--dry-run",
+        ]);
+        assert_ne!(
+            "1 of 2 simulations failed.",
+            format!("{}", result.unwrap_err())
         );
     }
 
     #[test]
-    fn directory_as_log_path() {
+    fn max_memory_aborts_before_simulating() {
+        let generous = run_with_args(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "--dry-run",
+            "--max-memory",
+            "1000000000",
+        ]);
+        assert!(generous.is_ok(), "Expected sky to fit into 1 GB: {:?}", generous);
+
+        let tiny = run_with_args(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "--dry-run",
+            "--max-memory",
+            "1",
+        ]);
+        let message = format!("{}", tiny.unwrap_err());
+        assert!(message.contains("exceeds --max-memory of 1 bytes"), "{}", message);
+
+        // Building would fail on the version, but the estimate comes first
+        let unbuildable = run_with_args(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "--set",
+            "version=99",
+            "--strict",
+            "--max-memory",
+            "1",
+        ]);
+        let message = format!("{}", unbuildable.unwrap_err());
+        assert!(message.contains("exceeds --max-memory of 1 bytes"), "{}", message);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn syslog_logger_is_added() {
+        if !Path::new("/dev/log").exists() && !Path::new("/var/run/syslog").exists() {
+            // No syslog daemon to connect to
+            return;
+        }
+
         let matches = new_app().get_matches_from(vec![
             "aitios-cli",
             "tests/examples/simulation.yml",
-            "-l",
-            "..",
+            "--log-format",
+            "json",
+            "--syslog",
+        ]);
+        let without = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "tests/examples/simulation.yml",
+            "--log-format",
+            "json",
         ]);
 
-        let log_file_paths =
-            canonical_log_file_paths(&matches, iter::empty::<&str>(), &fs_timestamp(Local::now()))
-                .expect("Expect canonical log file calculation to succeed");
+        let datetime = fs_timestamp(Local::now());
+        let terminal = terminal_logger(&matches).unwrap();
+        let with_syslog =
+            loggers(&matches, terminal, iter::empty::<&str>(), &datetime, "spec").unwrap();
+        let terminal = terminal_logger(&without).unwrap();
+        let without_syslog =
+            loggers(&without, terminal, iter::empty::<&str>(), &datetime, "spec").unwrap();
+        assert_eq!(without_syslog.len() + 1, with_syslog.len());
+    }
+
+    #[test]
+    fn print_spec_reflects_overrides() {
+        let matches = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "--set",
+            "iterations=7",
+            "--print-spec",
+        ]);
+        let builder = init_simulation_builder(&matches).unwrap();
+
+        let yaml = effective_spec(&builder).unwrap();
+        assert!(yaml.contains("iterations: 7"), "{}", yaml);
+        assert!(yaml.contains("name: Sky"), "{}", yaml);
+
+        let printed: SimulationSpec = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(Some(7), printed.iterations);
+    }
+
+    #[test]
+    fn print_spec_reflects_load_options() {
+        let matches = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "--iterations",
+            "5",
+            "--surfel-density",
+            "4",
+            "--print-spec",
+        ]);
+        let builder = init_simulation_builder(&matches).unwrap();
+
+        let printed: SimulationSpec =
+            serde_yaml::from_str(&effective_spec(&builder).unwrap()).unwrap();
+        assert_eq!(Some(5), printed.iterations);
+        assert_eq!(Some(0.5), printed.surfel_distance);
+    }
+
+    #[test]
+    fn continue_on_error_attempts_all_specs() {
+        let dir = temp_dir().join("aitios-batch-continue-test");
+        remove_dir_all(&dir).ok();
+        create_dir_all(&dir).unwrap();
+        let good = write_batch_spec(&dir, "good");
+        let broken = dir.join("broken.yml");
+        write(&broken, "iteratons: 1").unwrap();
+        let broken = broken.to_string_lossy().into_owned();
+
+        let result = run_with_args(vec![
+            "aitios-cli",
+            "--batch",
+            "--continue-on-error",
+            broken.as_str(),
+            good.as_str(),
+            "--dry-run",
+        ]);
 
         assert_eq!(
-            1,
-            log_file_paths.len(),
-            "Expected 1 log file under the parent directory but got {:?}.",
-            &log_file_paths
+            "1 of 2 simulations failed.",
+            format!("{}", result.unwrap_err())
         );
+        // The good spec after the broken one has still been loaded
+        assert!(dir.join("good.log").is_file());
+    }
 
-        let mut parent_dir = current_dir().unwrap();
-        parent_dir.pop();
+    #[test]
+    fn batch_summary_lists_outcomes() {
+        let summary = batch_summary(&[("a.yml", true), ("b.yml", false)], 3);
+        assert_eq!(
+            "Batch finished, 1 of 3 simulations succeeded.\n  ok      a.yml\n  failed  b.yml\n  1 simulation was not attempted.",
+            summary
+        );
+        let stopped = batch_summary(&[("a.yml", false)], 3);
+        assert!(stopped.ends_with("2 simulations were not attempted."), "{}", stopped);
+        assert!(!batch_summary(&[("a.yml", true)], 1).contains("not attempted"));
+    }
 
-        let mut parent_of_log_file_path = log_file_paths.iter().next().unwrap().clone();
-        parent_of_log_file_path.pop();
+    #[test]
+    fn continue_on_error_conflicts_with_fail_fast() {
+        let matches = new_app().get_matches_from_safe(vec![
+            "aitios-cli",
+            "--batch",
+            "--continue-on-error",
+            "--fail-fast",
+            "tests/examples/sky.yml",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn bench_line_has_positive_throughput() {
+        let matches =
+            new_app().get_matches_from(vec!["aitios-cli", "tests/examples/sky.yml", "--bench"]);
+        let mut runner = init_simulation_builder(&matches).unwrap().build().unwrap();
+        let summary = runner.run();
+        assert!(summary.outputs.is_empty(), "{:?}", summary.outputs);
 
+        let line = bench_line(&summary, runner.preheat());
+        let mut fields = line.split_whitespace();
+        assert_eq!(Some("bench"), fields.next());
+        let values: Vec<(&str, f64)> = fields
+            .map(|field| {
+                let mut key_value = field.splitn(2, '=');
+                let key = key_value.next().unwrap();
+                (key, key_value.next().unwrap().parse().unwrap())
+            })
+            .collect();
+        let keys: Vec<&str> = values.iter().map(|&(key, _)| key).collect();
         assert_eq!(
-            parent_dir, parent_of_log_file_path,
-            "Expected generated default log file to be in parent directory but got {:?}.",
-            &log_file_paths
+            vec![
+                "gammatons_per_second",
+                "iterations_per_second",
+                "gammatons",
+                "iterations",
+                "seconds"
+            ],
+            keys
         );
+        for &(key, value) in values.iter() {
+            assert!(value > 0.0, "Expected positive {} in {}", key, line);
+        }
     }
 
     #[test]
-    fn create_intermediary_directories() {
-        let pattern = "/tmp/{datetime}/some/dir/logggg.log";
-        let timestamp = fs_timestamp(Local::now());
-        let expected_log_path = pattern.replace("{datetime}", &timestamp);
+    fn no_clobber_refuses_existing_outputs() {
+        let dir = temp_dir().join("aitios-no-clobber-test");
+        remove_dir_all(&dir).ok();
+        create_dir_all(&dir).unwrap();
+        let spec = write_batch_spec(&dir, "clobber");
+        let existing = dir.join("clobber-1.obj");
+        write(&existing, "earlier results").unwrap();
+
+        let result = run_with_args(vec![
+            "aitios-cli",
+            spec.as_str(),
+            "--no-clobber",
+            "--dry-run",
+        ]);
+        let message = format!("{}", result.unwrap_err());
+        assert!(message.starts_with("1 output files already exist"), "{}", message);
+        assert!(message.contains("clobber-1.obj"), "{}", message);
+
+        let result = run_with_args(vec!["aitios-cli", spec.as_str(), "--overwrite", "--dry-run"]);
+        assert!(result.is_ok(), "Expected --overwrite to be allowed: {:?}", result);
+        assert_eq!("earlier results", read_to_string(&existing).unwrap());
+    }
+
+    #[test]
+    fn overwrite_replaces_outputs_that_block_no_clobber() {
+        let dir = temp_dir().join("aitios-overwrite-test");
+        create_dir_all(&dir).unwrap();
+        let spec = write_batch_spec(&dir, "overwrite");
+        let existing = dir.join("overwrite-1.obj");
+        write(&existing, "earlier results").unwrap();
+
+        let result = run_with_args(vec!["aitios-cli", spec.as_str(), "--no-clobber"]);
+        let message = format!("{}", result.unwrap_err());
+        assert!(message.contains("overwrite-1.obj"), "{}", message);
+        assert_eq!("earlier results", read_to_string(&existing).unwrap());
+
+        let result = run_with_args(vec!["aitios-cli", spec.as_str(), "--overwrite"]);
+        assert!(result.is_ok(), "Expected --overwrite to run: {:?}", result);
+        assert_ne!("earlier results", read_to_string(&existing).unwrap());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn no_clobber_conflicts_with_overwrite() {
+        let matches = new_app().get_matches_from_safe(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "--no-clobber",
+            "--overwrite",
+        ]);
+        assert!(matches.is_err());
+    }
+
+    #[test]
+    fn wind_sets_flow_direction() {
         let matches = new_app().get_matches_from(vec![
             "aitios-cli",
-            "tests/examples/simulation.yml",
-            "-l",
-            pattern,
+            "tests/examples/sky.yml",
+            "--wind",
+            "1,-1,0",
         ]);
+        let config = effective_config(&matches, &SimulationBuilder::new()).unwrap();
+        assert_eq!(Some([1.0, -1.0, 0.0]), config.flow_direction);
+        assert!(init_simulation_builder(&matches).unwrap().build().is_ok());
 
-        let log_file_paths = canonical_log_file_paths(&matches, iter::empty::<&str>(), &timestamp)
-            .expect("Expect canonical log file calculation to succeed");
+        let malformed = new_app().get_matches_from_safe(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "--wind",
+            "1,2",
+        ]);
+        let message = format!("{}", malformed.unwrap_err());
+        assert!(message.contains("Invalid flow direction specified: 1,2"), "{}", message);
+    }
 
-        assert!(
-            Path::new(&expected_log_path).parent().unwrap().is_dir(),
-            "Expected canonical log file path lookup to create the necessary directories"
+    #[test]
+    fn manifest_lists_every_output() {
+        let dir = temp_dir().join("aitios-manifest-test");
+        remove_dir_all(&dir).ok();
+        create_dir_all(&dir).unwrap();
+        let spec = write_batch_spec(&dir, "m");
+        let summary_json = dir.join("summary.json").to_string_lossy().into_owned();
+        let output_dir = dir.join("out");
+
+        run_with_args(vec![
+            "aitios-cli",
+            spec.as_str(),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--manifest",
+            "--summary-json",
+            summary_json.as_str(),
+        ]).unwrap();
+
+        let manifest = read_to_string(output_dir.join("manifest.json")).unwrap();
+        let summary = read_to_string(&summary_json).unwrap();
+        remove_dir_all(&dir).unwrap();
+
+        let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+        let summary: serde_json::Value = serde_json::from_str(&summary).unwrap();
+        assert_eq!(env!("CARGO_PKG_VERSION"), manifest["version"]);
+        assert_eq!(serde_json::Value::Null, manifest["seed"]);
+
+        let listed: Vec<&serde_json::Value> = manifest["outputs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|output| &output["path"])
+            .collect();
+        let produced: Vec<&serde_json::Value> =
+            summary["outputs"].as_array().unwrap().iter().collect();
+        assert!(!produced.is_empty());
+        assert_eq!(produced, listed);
+    }
+
+    #[test]
+    fn zero_iterations_exit_successfully_with_baseline() {
+        let dir = temp_dir().join("aitios-zero-iterations-test");
+        create_dir_all(&dir).unwrap();
+        let spec = write_batch_spec(&dir, "z");
+
+        let result = run_with_args(vec!["aitios-cli", spec.as_str(), "--iterations", "0"]);
+        let baseline = dir.join("z-0.obj").is_file();
+        let traced = dir.join("z-1.obj").exists();
+        remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert!(baseline, "Expected baseline surfels to be dumped");
+        assert!(!traced, "Expected no iteration to be simulated");
+    }
+
+    #[test]
+    fn fail_on_warnings_fails_runs_that_warned() {
+        let dir = temp_dir().join("aitios-fail-on-warnings-test");
+        create_dir_all(&dir).unwrap();
+        // Warns about the unsupported spec version
+        let spec = write_batch_spec(&dir, "mismatch");
+        let yaml = read_to_string(&spec).unwrap();
+        write(&spec, format!("{}\nversion: 99", yaml)).unwrap();
+
+        let lenient = run_with_args(vec!["aitios-cli", spec.as_str(), "--dry-run"]);
+        let failing = run_with_args(vec![
+            "aitios-cli",
+            spec.as_str(),
+            "--dry-run",
+            "--fail-on-warnings",
+        ]);
+        remove_dir_all(&dir).unwrap();
+
+        assert!(lenient.is_ok(), "Expected warnings to be tolerated: {:?}", lenient);
+        assert!(failing.is_err(), "Expected run with warnings to fail");
+    }
+
+    #[test]
+    fn fail_on_warnings_fails_only_batch_specs_that_warned() {
+        let dir = temp_dir().join("aitios-batch-warnings-test");
+        remove_dir_all(&dir).ok();
+        create_dir_all(&dir).unwrap();
+        let clean = write_batch_spec(&dir, "clean");
+        let mismatch = write_batch_spec(&dir, "mismatch");
+        let yaml = read_to_string(&mismatch).unwrap();
+        write(&mismatch, format!("{}\nversion: 99", yaml)).unwrap();
+
+        let result = run_with_args(vec![
+            "aitios-cli",
+            "--batch",
+            "--jobs",
+            "2",
+            "--fail-on-warnings",
+            mismatch.as_str(),
+            clean.as_str(),
+            "--dry-run",
+        ]);
+        remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            "1 of 2 simulations failed.",
+            format!("{}", result.unwrap_err())
         );
+    }
 
-        assert_eq!(1, log_file_paths.len());
+    #[test]
+    fn batch_jobs_run_concurrently_with_own_outputs() {
+        let dir = temp_dir().join("aitios-batch-jobs-test");
+        create_dir_all(&dir).unwrap();
+        let specs: Vec<String> = ["a", "b", "c"]
+            .iter()
+            .map(|name| write_batch_spec(&dir, name))
+            .collect();
+
+        let mut args = vec!["aitios-cli", "--batch", "--jobs", "2", "--iterations", "0"];
+        args.extend(specs.iter().map(|spec| spec.as_str()));
+        let result = run_with_args(args);
+        let outputs: Vec<bool> = ["a-0.obj", "b-0.obj", "c-0.obj", "a.log", "b.log", "c.log"]
+            .iter()
+            .map(|file| dir.join(file).is_file())
+            .collect();
+        remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_ok(), "Expected all specs to complete: {:?}", result);
+        assert_eq!(vec![true; 6], outputs, "Expected surfels and log for every spec");
     }
 
     #[test]
-    fn test_duplicate_log_file_removal() {
+    fn batch_jobs_are_limited_to_specs() {
         let matches = new_app().get_matches_from(vec![
             "aitios-cli",
+            "--batch",
+            "--jobs",
+            "4",
+            "tests/examples/sky.yml",
             "tests/examples/simulation.yml",
-            "-l",
-            "log1.log",
-            "-l",
-            "././log1.log",
-            "-l",
-            ".",
-            "-l",
         ]);
+        assert_eq!(2, batch_jobs(&matches));
 
-        let log_file_paths = canonical_log_file_paths(
-            &matches,
-            ["log2.log", "./log1.log", "."].iter(),
-            &fs_timestamp(Local::now()),
-        ).expect("Expect canonical log file calculation to succeed");
+        let single = new_app().get_matches_from(vec!["aitios-cli", "tests/examples/sky.yml"]);
+        assert_eq!(1, batch_jobs(&single));
+        // Bars of concurrent jobs would overwrite each other
+        assert!(!show_progress_bar(&matches));
+    }
 
+    #[test]
+    fn spec_name_in_log_path() {
+        let path = log_arg_to_log_path("{spec_name}-{datetime}.log", "now", "a").unwrap();
+        assert_eq!(Some("a-now.log"), path.file_name().and_then(|n| n.to_str()));
+    }
+
+
+    #[test]
+    fn archive_is_relative_to_output_dir() {
+        let dir = temp_dir().join("aitios-archive-output-dir-test");
+        remove_dir_all(&dir).ok();
+        create_dir_all(&dir).unwrap();
+        let cwd = current_dir().unwrap();
+        let spec = dir.join("relative.yml");
+        write(
+            &spec,
+            format!(
+                "scenes: [{:?}]\nsurfels_by_material:\n  _: {:?}\nsources: [{:?}]\nsurfel_distance: 2.0\neffects:\n  - dump_surfels:\n      obj_pattern: \"surfels-{{iteration}}.obj\"",
+                cwd.join("tests/assets/sky.obj"),
+                cwd.join("tests/examples/concrete.yml"),
+                cwd.join("tests/examples/rain.yml"),
+            ),
+        ).unwrap();
+        let output_dir = dir.join("out").to_string_lossy().into_owned();
+        let archive = dir.join("outputs.zip").to_string_lossy().into_owned();
+
+        let result = run_with_args(vec![
+            "aitios-cli",
+            spec.to_str().unwrap(),
+            "--output-dir",
+            output_dir.as_str(),
+            "--archive",
+            archive.as_str(),
+        ]);
+        assert!(result.is_ok(), "Expected archive to be written: {:?}", result);
+
+        let mut zip = ZipArchive::new(File::open(&archive).unwrap()).unwrap();
+        let names: Vec<String> = (0..zip.len())
+            .map(|i| zip.by_index(i).unwrap().name().to_string())
+            .collect();
+        remove_dir_all(&dir).unwrap();
+        assert!(names.contains(&"surfels-1.obj".to_string()), "{:?}", names);
+    }
+
+    #[test]
+    fn hook_output_dir_contains_all_output_dirs() {
+        let cwd = current_dir().unwrap();
+        assert_eq!(cwd, hook_output_dir(&[]).unwrap());
         assert_eq!(
-            3,
-            log_file_paths.len(),
-            "Expected 3 log files (log1.log, log2.log, aitios-log-DATE.log), but found {:?}.",
-            log_file_paths
+            cwd.join("runs/a"),
+            hook_output_dir(&[PathBuf::from("runs/a")]).unwrap()
         );
+        assert_eq!(
+            cwd.join("runs"),
+            hook_output_dir(&[PathBuf::from("runs/a"), PathBuf::from("runs/b/c")]).unwrap()
+        );
+        assert_eq!(
+            PathBuf::from("/tmp/runs"),
+            hook_output_dir(&[PathBuf::from("/tmp/runs/a"), PathBuf::from("/tmp/runs/b")])
+                .unwrap()
+        );
+    }
+
+
+    #[test]
+    fn bake_filter_with_radius() {
+        let unknown = new_app().get_matches_from_safe(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "--bake-filter",
+            "smooth",
+        ]);
+        let message = format!("{}", unknown.unwrap_err());
+        for name in BAKE_FILTER_NAMES.iter() {
+            assert!(message.contains(name), "Expected {} in {}", name, message);
+        }
+
+        let matches = new_app().get_matches_from(vec![
+            "aitios-cli",
+            "tests/examples/sky.yml",
+            "--bake-filter",
+            "gaussian",
+            "--bake-radius",
+            "0.5",
+        ]);
+        let config = effective_config(&matches, &SimulationBuilder::new()).unwrap();
+        assert_eq!(Some(String::from("gaussian")), config.bake_filter);
+        assert_eq!(Some(0.5), config.bake_radius);
+        assert!(init_simulation_builder(&matches).unwrap().build().is_ok());
     }
 }
@@ -0,0 +1,64 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use simplelog::{Config, SharedLogger};
+use std::process;
+use std::str::FromStr;
+use syslog::{self, BasicLogger, Facility, Formatter3164};
+
+/// Logger that forwards records to the local syslog daemon, for `--syslog`.
+pub struct SyslogLogger {
+    level: LevelFilter,
+    inner: BasicLogger,
+}
+
+impl SyslogLogger {
+    /// Connects to the local syslog socket, logging with the facility of the
+    /// given name, e.g. `user` or `local0`.
+    pub fn new(level: LevelFilter, facility: &str) -> Result<Box<Self>, String> {
+        let facility = Facility::from_str(facility)
+            .map_err(|_| format!("Unknown syslog facility {:?}", facility))?;
+        let formatter = Formatter3164 {
+            facility,
+            hostname: None,
+            process: String::from("aitios"),
+            pid: process::id() as i32,
+        };
+
+        let logger =
+            syslog::unix(formatter).map_err(|e| format!("Could not connect to syslog: {}", e))?;
+
+        Ok(Box::new(SyslogLogger {
+            level,
+            inner: BasicLogger::new(logger),
+        }))
+    }
+}
+
+impl Log for SyslogLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+impl SharedLogger for SyslogLogger {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<Log> {
+        Box::new(*self)
+    }
+}
@@ -0,0 +1,214 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use simplelog::{Config, SharedLogger};
+
+/// Log levels for specific targets, e.g. `aitios_surf=trace`, that apply on
+/// top of the level of each logger, as set with `--verbose-target`.
+#[derive(Debug, Clone, Default)]
+pub struct TargetLevels {
+    levels: Vec<(String, LevelFilter)>,
+}
+
+impl TargetLevels {
+    /// Parses levels in the form `target=level`, failing on the first one
+    /// that cannot be parsed.
+    pub fn parse<'a, I>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let levels = args
+            .into_iter()
+            .map(parse_target_level)
+            .collect::<Result<_, _>>()?;
+        Ok(TargetLevels { levels })
+    }
+
+    /// Level for records of the given target, which is the level of the
+    /// most specific matching target, or the given level if none matches.
+    ///
+    /// A target matches its own records and those of its submodules, e.g.
+    /// `aitios_surf` also matches `aitios_surf::sample`.
+    pub fn level_for(&self, target: &str, level: LevelFilter) -> LevelFilter {
+        self.levels
+            .iter()
+            .filter(|&&(ref prefix, _)| {
+                target == prefix
+                    || (target.starts_with(prefix.as_str())
+                        && target[prefix.len()..].starts_with("::"))
+            })
+            .max_by_key(|&&(ref prefix, _)| prefix.len())
+            .map(|&(_, target_level)| target_level)
+            .unwrap_or(level)
+    }
+
+    /// Level to build a logger with before wrapping it with `filter`, so
+    /// the logger itself lets through records at the raised levels.
+    ///
+    /// Loggers that are off, e.g. the terminal with `--quiet`, stay off.
+    pub fn max_level(&self, level: LevelFilter) -> LevelFilter {
+        if level == LevelFilter::Off {
+            return level;
+        }
+
+        self.levels
+            .iter()
+            .map(|&(_, target_level)| target_level)
+            .fold(level, |max, target_level| max.max(target_level))
+    }
+
+    /// Wraps a logger built with `max_level` so that it only logs records
+    /// at the given level, unless a level for their target applies.
+    pub fn filter(&self, level: LevelFilter, logger: Box<SharedLogger>) -> Box<SharedLogger> {
+        if self.levels.is_empty() || level == LevelFilter::Off {
+            logger
+        } else {
+            Box::new(TargetFilter {
+                level,
+                targets: self.clone(),
+                inner: logger,
+            })
+        }
+    }
+}
+
+/// Parses a single `target=level`, e.g. `aitios_surf=trace`.
+pub fn parse_target_level(arg: &str) -> Result<(String, LevelFilter), String> {
+    let mut parts = arg.splitn(2, '=');
+    let target = parts.next().unwrap_or("").trim();
+    let level = parts
+        .next()
+        .ok_or_else(|| String::from("Expected target=level, e.g. aitios_surf=trace"))?;
+
+    if target.is_empty() {
+        return Err(String::from("Target must not be empty"));
+    }
+
+    let level = level.trim().parse::<LevelFilter>().map_err(|_| {
+        format!(
+            "Unknown level {}, expected off, error, warn, info, debug or trace",
+            level
+        )
+    })?;
+    Ok((String::from(target), level))
+}
+
+/// Logger that passes records on to another logger if their level is
+/// enabled for their target.
+struct TargetFilter {
+    level: LevelFilter,
+    targets: TargetLevels,
+    inner: Box<SharedLogger>,
+}
+
+impl Log for TargetFilter {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.targets.level_for(metadata.target(), self.level)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.inner.log(record)
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+impl SharedLogger for TargetFilter {
+    fn level(&self) -> LevelFilter {
+        self.targets.max_level(self.level)
+    }
+
+    fn config(&self) -> Option<&Config> {
+        self.inner.config()
+    }
+
+    fn as_log(self: Box<Self>) -> Box<Log> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use log::Level;
+    use simplelog::WriteLogger;
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn raises_level_of_target_only() {
+        let targets = TargetLevels::parse(vec!["aitios_surf=trace"]).unwrap();
+        let buffer = Buffer::default();
+        let max_level = targets.max_level(LevelFilter::Warn);
+        let logger = targets.filter(
+            LevelFilter::Warn,
+            WriteLogger::new(max_level, Config::default(), buffer.clone()),
+        );
+
+        for &(target, message) in [
+            ("aitios_surf::sample", "surf trace"),
+            ("aitios_cli::runner", "runner trace"),
+            ("aitios_surface", "surface trace"),
+        ].iter()
+        {
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("{}", message))
+                    .level(Level::Trace)
+                    .target(target)
+                    .build(),
+            );
+        }
+
+        let logged = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(LevelFilter::Trace, logger.level());
+        assert!(logged.contains("surf trace"), "{}", logged);
+        assert!(!logged.contains("runner trace"), "{}", logged);
+        assert!(!logged.contains("surface trace"), "{}", logged);
+    }
+
+    #[test]
+    fn most_specific_target_wins() {
+        let targets =
+            TargetLevels::parse(vec!["aitios_surf=debug", "aitios_surf::sample=off"]).unwrap();
+
+        assert_eq!(
+            LevelFilter::Off,
+            targets.level_for("aitios_surf::sample", LevelFilter::Warn)
+        );
+        assert_eq!(
+            LevelFilter::Debug,
+            targets.level_for("aitios_surf::tree", LevelFilter::Warn)
+        );
+        assert_eq!(
+            LevelFilter::Warn,
+            targets.level_for("aitios_sim", LevelFilter::Warn)
+        );
+    }
+
+    #[test]
+    fn invalid_target_levels() {
+        assert!(parse_target_level("aitios_surf").is_err());
+        assert!(parse_target_level("=trace").is_err());
+        assert!(parse_target_level("aitios_surf=loud").is_err());
+        assert_eq!(
+            Ok((String::from("aitios_surf"), LevelFilter::Trace)),
+            parse_target_level("aitios_surf=TRACE")
+        );
+    }
+}
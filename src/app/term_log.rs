@@ -0,0 +1,120 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use simplelog::{Config, SharedLogger, WriteLogger};
+use std::ffi::OsStr;
+use std::io::{stderr, stdout, Stderr, Stdout};
+
+/// Whether human readable terminal logs are colorized, as chosen with
+/// `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Choice for the given value of `--color` and of the `NO_COLOR`
+    /// environment variable.
+    ///
+    /// A non-empty `NO_COLOR` turns the default of `auto` into `never`, an
+    /// explicit `--color always` still wins.
+    pub fn from_arg(arg: Option<&str>, no_color: Option<&OsStr>) -> Self {
+        match arg {
+            Some("always") => ColorChoice::Always,
+            Some("never") => ColorChoice::Never,
+            _ if no_color.map(|v| !v.is_empty()).unwrap_or(false) => ColorChoice::Never,
+            _ => ColorChoice::Auto,
+        }
+    }
+
+    /// Checks if logs should be colorized, with `auto` colorizing only if
+    /// logging to a terminal.
+    pub fn colorize(self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => is_terminal,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Terminal logger without colors for `--color never`.
+///
+/// Writes the same lines as `TermLogger`, with errors on stderr and all
+/// other records on stdout, but without the escape sequences for coloring
+/// level labels.
+pub struct PlainTermLogger {
+    level: LevelFilter,
+    config: Config,
+    err: Box<WriteLogger<Stderr>>,
+    out: Box<WriteLogger<Stdout>>,
+}
+
+impl PlainTermLogger {
+    pub fn new(level: LevelFilter, config: Config) -> Box<Self> {
+        Box::new(PlainTermLogger {
+            level,
+            config,
+            err: WriteLogger::new(level, config, stderr()),
+            out: WriteLogger::new(level, config, stdout()),
+        })
+    }
+}
+
+impl Log for PlainTermLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() == Level::Error {
+            self.err.log(record)
+        } else {
+            self.out.log(record)
+        }
+    }
+
+    fn flush(&self) {
+        self.err.flush();
+        self.out.flush();
+    }
+}
+
+impl SharedLogger for PlainTermLogger {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&Config> {
+        Some(&self.config)
+    }
+
+    fn as_log(self: Box<Self>) -> Box<Log> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::OsString;
+
+    #[test]
+    fn never_and_no_color_disable_coloring() {
+        let no_color = OsString::from("1");
+
+        assert!(!ColorChoice::from_arg(Some("never"), None).colorize(true));
+        assert!(!ColorChoice::from_arg(None, Some(&no_color)).colorize(true));
+        assert!(!ColorChoice::from_arg(Some("auto"), Some(&no_color)).colorize(true));
+    }
+
+    #[test]
+    fn auto_detects_terminal() {
+        let empty = OsString::new();
+
+        assert!(ColorChoice::from_arg(None, None).colorize(true));
+        assert!(!ColorChoice::from_arg(Some("auto"), None).colorize(false));
+        assert!(ColorChoice::from_arg(None, Some(&empty)).colorize(true));
+        assert!(ColorChoice::from_arg(Some("always"), None).colorize(false));
+    }
+}
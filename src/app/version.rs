@@ -0,0 +1,44 @@
+/// Build information printed by `--version-json`.
+#[derive(Debug, Serialize)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    /// Commit the binary was built from, if it was built from a git
+    /// checkout with git available.
+    pub git_commit: Option<&'static str>,
+    /// Cargo profile of the build, e.g. `debug` or `release`.
+    pub build_profile: &'static str,
+}
+
+impl VersionInfo {
+    /// Information about the running build.
+    pub fn current() -> Self {
+        // Only used if built without the build script
+        let assumed_profile = if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        };
+
+        VersionInfo {
+            version: crate_version!(),
+            git_commit: option_env!("AITIOS_GIT_COMMIT"),
+            build_profile: option_env!("AITIOS_BUILD_PROFILE").unwrap_or(assumed_profile),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn version_info_as_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&VersionInfo::current()).unwrap()).unwrap();
+
+        assert_eq!(crate_version!(), json["version"]);
+        assert!(json["build_profile"].is_string());
+        assert!(json["git_commit"].is_string() || json["git_commit"].is_null());
+    }
+}
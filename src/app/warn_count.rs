@@ -0,0 +1,120 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use simplelog::{Config, SharedLogger};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Warnings and errors logged so far in this process.
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Additional count of the batch job of the current thread, if any, see
+    /// `count_job_warnings`.
+    static JOB_WARNINGS: RefCell<Option<Arc<AtomicUsize>>> = RefCell::new(None);
+}
+
+/// Number of records logged at warning level or above so far, for
+/// `--fail-on-warnings`.
+///
+/// Only one logger can be installed per process, so the count is global.
+/// Compare the counts from before and after a run to get its warnings.
+pub fn warning_count() -> usize {
+    WARNINGS.load(Ordering::SeqCst)
+}
+
+/// Makes warnings and errors logged on the calling thread also count into
+/// the given counter, usually in the start handler of the thread pool of a
+/// batch job, so that the warnings of simulations running at the same time
+/// can be told apart.
+pub fn count_job_warnings(counter: Arc<AtomicUsize>) {
+    JOB_WARNINGS.with(|job| *job.borrow_mut() = Some(counter));
+}
+
+/// Logger that writes nothing, but counts warnings and errors so they can
+/// be reported after a run.
+pub struct WarningCounter;
+
+impl WarningCounter {
+    pub fn new() -> Box<Self> {
+        Box::new(WarningCounter)
+    }
+}
+
+impl Log for WarningCounter {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            WARNINGS.fetch_add(1, Ordering::SeqCst);
+            JOB_WARNINGS.with(|job| {
+                if let Some(ref job) = *job.borrow() {
+                    job.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+impl SharedLogger for WarningCounter {
+    fn level(&self) -> LevelFilter {
+        LevelFilter::Warn
+    }
+
+    fn config(&self) -> Option<&Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<Log> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn counts_warnings_and_errors_only() {
+        let counter = WarningCounter::new();
+        let before = warning_count();
+
+        for &level in [Level::Error, Level::Warn, Level::Info, Level::Debug].iter() {
+            counter.log(&Record::builder().args(format_args!("Test")).level(level).build());
+        }
+
+        // Other tests may log warnings at the same time
+        assert!(warning_count() - before >= 2);
+        assert!(counter.enabled(&Metadata::builder().level(Level::Warn).build()));
+        assert!(!counter.enabled(&Metadata::builder().level(Level::Info).build()));
+    }
+
+    fn warn_once() {
+        WarningCounter::new().log(
+            &Record::builder()
+                .args(format_args!("Test"))
+                .level(Level::Warn)
+                .build(),
+        );
+    }
+
+    #[test]
+    fn counts_job_warnings_on_job_threads_only() {
+        let job = Arc::new(AtomicUsize::new(0));
+        let job_thread = {
+            let job = job.clone();
+            thread::spawn(move || {
+                count_job_warnings(job);
+                warn_once();
+            })
+        };
+        job_thread.join().unwrap();
+        warn_once();
+
+        assert_eq!(1, job.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,121 @@
+use failure::{Error, ResultExt};
+use notify::{watcher, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+/// Time to wait for more changes after a file changed, so that a save that
+/// touches a file multiple times results in a single run.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+/// Interval to check the interrupt flag while waiting for changes.
+const POLL_INTERRUPT: Duration = Duration::from_millis(200);
+
+/// Watches input files of a simulation for changes, for `--watch`.
+///
+/// The directories of the files are watched rather than the files
+/// themselves, so that editors that save by replacing files are supported.
+pub struct InputWatcher {
+    files: HashSet<PathBuf>,
+    events: Receiver<DebouncedEvent>,
+    // Stops watching when dropped
+    _watcher: RecommendedWatcher,
+}
+
+impl InputWatcher {
+    /// Starts watching the given files, ignoring files that do not exist.
+    pub fn new<I, P>(files: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let files: HashSet<PathBuf> = files
+            .into_iter()
+            .filter_map(|f| f.as_ref().canonicalize().ok())
+            .collect();
+
+        let (sender, events) = channel();
+        let mut watcher = watcher(sender, DEBOUNCE).context("Failed to set up file watcher.")?;
+
+        let dirs: HashSet<&Path> = files.iter().filter_map(|f| f.parent()).collect();
+        for dir in dirs {
+            watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .with_context(|_| format!("Failed to watch {:?} for changes.", dir))?;
+        }
+
+        Ok(InputWatcher {
+            files,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Number of watched files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Blocks until a watched file has been changed, including changes since
+    /// the watcher was created, and returns `true`, or returns `false` as
+    /// soon as the given flag is set.
+    pub fn wait_for_change(&self, interrupt: &AtomicBool) -> bool {
+        while !interrupt.load(Ordering::SeqCst) {
+            match self.events.recv_timeout(POLL_INTERRUPT) {
+                Ok(DebouncedEvent::Create(ref path))
+                | Ok(DebouncedEvent::Write(ref path))
+                | Ok(DebouncedEvent::Rename(_, ref path))
+                    if self.files.contains(path) =>
+                {
+                    return true
+                }
+                Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+                // Watcher is gone, nothing will ever change
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn change_is_detected() {
+        let dir = temp_dir().join("aitios-watch-test");
+        create_dir_all(&dir).unwrap();
+        let spec = dir.join("spec.yml");
+        let output = dir.join("output.obj");
+        write(&spec, "iterations: 1").unwrap();
+
+        let watcher = InputWatcher::new(vec![&spec]).unwrap();
+        assert_eq!(1, watcher.len());
+
+        let interrupt = Arc::new(AtomicBool::new(false));
+        {
+            let interrupt = interrupt.clone();
+            thread::spawn(move || {
+                // Outputs next to the spec do not count as changes
+                write(&output, "o").unwrap();
+                thread::sleep(Duration::from_millis(100));
+                write(&spec, "iterations: 2").unwrap();
+                // Give up eventually if the change is never detected
+                thread::sleep(Duration::from_secs(10));
+                interrupt.store(true, Ordering::SeqCst);
+            });
+        }
+
+        assert!(
+            watcher.wait_for_change(&interrupt),
+            "Expected change of spec to be detected"
+        );
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -14,6 +14,8 @@ pub fn append(first: SimulationSpec, second: &SimulationSpec) -> SimulationSpec
     }
 
     SimulationSpec {
+        version: second.version.or(first.version),
+        include: Vec::new(),
         name: append_textual(&first.name, &second.name, "-"),
         description: append_textual(&first.description, &second.description, "\n\n"),
         scenes: append_list(first.scenes, second.scenes.iter()),
@@ -32,7 +34,9 @@ pub fn append(first: SimulationSpec, second: &SimulationSpec) -> SimulationSpec
         benchmark: append_benchmark(&first.benchmark, &second.benchmark),
         transport: second.transport.or(first.transport),
         flat_filtering: second.flat_filtering.or(first.flat_filtering),
+        output_bit_depth: second.output_bit_depth.or(first.output_bit_depth),
         rules: append_list(first.rules, second.rules.iter()),
+        region_mask: second.region_mask.clone().or(first.region_mask),
     }
 }
 
@@ -1,23 +1,41 @@
-use builder::{append, canonicalize, instantiate, Error, ResolveErrorKind};
+use builder::{
+    append, canonicalize, check_assets, check_output_dirs, estimate_bounds,
+    estimate_peak_memory, existing_outputs, instantiate, map_outputs, override_spec, parse_spec,
+    probe_output_dirs, relocated, set_field, AssetReport, Error, LoadOptions, ResolveErrorKind,
+    SpecFormat, SubstanceTable,
+};
 use chrono::*;
-use files::Resolver;
-use runner::SimulationRunner;
-use serde_yaml;
+use files::{fs_timestamp, read_to_string_gunzipped, read_to_string_sniffed, Resolver};
+use runner::{BakeFilter, Bounds, SimulationRunner};
+use serde_yaml::{self, Value};
 use spec::SimulationSpec;
+use std::collections::HashMap;
 use std::default::Default;
 use std::env::current_dir;
-use std::fs::File;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Replaces `{spec_name}` if no spec file was appended.
+const DEFAULT_SPEC_NAME: &str = "spec";
 
 pub struct SimulationBuilder {
     spec: SimulationSpec,
     /// Precedence:
     /// 1. Absolute paths that do also exist,
-    /// 2. Current working directory,
-    /// 3. Relative to directory that contains simulation spec fragment,
-    ///    in the order they were added.
+    /// 2. Relative to directory that contains the simulation spec fragment
+    ///    file, if appending a file,
+    /// 3. Current working directory,
+    /// 4. Relative to added base paths, in the order they were added,
+    /// 5. Relative to directories of included fragments.
     resolv: Resolver,
     creation_time: DateTime<Local>,
+    /// Stem of the first appended spec file, if any.
+    spec_name: Option<String>,
+    /// Outputs that were relative in appended spec files and now point into
+    /// the directory of their spec, mapped to their relative path, so that
+    /// `output_dir` can move them below another directory.
+    spec_dir_outputs: HashMap<String, String>,
+    options: LoadOptions,
 }
 
 /// Builds simulations from specifications or specification fragments stored in files
@@ -31,6 +49,9 @@ impl SimulationBuilder {
             spec: Default::default(),
             resolv: local_resolver(),
             creation_time: Local::now(),
+            spec_name: None,
+            spec_dir_outputs: HashMap::new(),
+            options: Default::default(),
         }
     }
 
@@ -42,9 +63,10 @@ impl SimulationBuilder {
     ///
     /// The precedence for reference is:
     /// 1. Absolute paths that do also exist,
-    /// 2. relative to current working directory,
-    /// 3. relative to directories added with this function,
-    /// 4. relative to directory that contains current simulation spec fragment, if adding with a path.
+    /// 2. relative to directory that contains current simulation spec fragment, if
+    ///    adding with a path,
+    /// 3. relative to current working directory,
+    /// 4. relative to directories added with this function.
     #[allow(unused)]
     pub fn add_base_path<P>(mut self, base: P) -> Result<Self, Error>
     where
@@ -59,7 +81,10 @@ impl SimulationBuilder {
 
     /// Derives a new resolver from the builder-global resolver
     /// that also resolves relative to the parent of the given
-    /// fragment path.
+    /// fragment path, with precedence over the working directory.
+    ///
+    /// This makes a spec runnable from anywhere, e.g. with
+    /// `aitios projects/foo/sim.yml`.
     ///
     /// The builder-global resolver is left unchanged. This function
     /// can be used to temporarily resolve from one additional path
@@ -71,7 +96,7 @@ impl SimulationBuilder {
         if let Some(spec_parent) = spec_path.parent() {
             if !spec_parent.as_os_str().is_empty() {
                 resolver
-                    .add_base(spec_parent)
+                    .add_base_first(spec_parent)
                     .map_err(|e| Error::resolve(e, ResolveErrorKind::Simulation))?;
             }
         }
@@ -82,38 +107,85 @@ impl SimulationBuilder {
     /// Appends a simulation spec YAML file to the mix.
     /// If the file defines already defined properties, they will get merged with previous ones, e.g.
     /// new ton sources will be appended to the existing ones.
-    pub fn append_spec_fragment_file<P>(self, simulation_spec_file: P) -> Result<Self, Error>
+    ///
+    /// Relative inputs and outputs are resolved against the directory of
+    /// the spec file, inputs falling back to the working directory.
+    ///
+    /// Gzipped files, e.g. `sim.yml.gz`, are decompressed before parsing.
+    pub fn append_spec_fragment_file<P>(mut self, simulation_spec_file: P) -> Result<Self, Error>
     where
         P: AsRef<Path>,
     {
         let simulation_spec_file = simulation_spec_file.as_ref();
 
-        // Resolve relative to cwd and relative to this spec.
-        let resolv = self.resolver_for(&simulation_spec_file)?;
-
-        // Allow relative files relative to parent of spec.
-        let spec_path = resolv
+        let spec_path = self
+            .resolv
             .resolve(simulation_spec_file)
             .map_err(|e| Error::resolve(e, ResolveErrorKind::Simulation))?;
 
-        let spec = serde_yaml::from_reader(
-            // The resolved path should be always openable,
-            // except with permission errors
-            File::open(&spec_path)?,
-        )?;
+        // Resolve relative to this spec and relative to cwd.
+        let mut resolv = self.resolver_for(&spec_path)?;
+
+        // The resolved path should be always openable,
+        // except with permission errors
+        let text = read_to_string_gunzipped(&spec_path)?;
+
+        // Includes are only looked up relative to the including spec
+        let mut includes = Resolver::new();
+        if let Some(spec_dir) = spec_path.parent() {
+            includes
+                .add_base(spec_dir)
+                .map_err(|e| Error::resolve(e, ResolveErrorKind::Include))?;
+        }
+        let format = SpecFormat::from_path(&spec_path);
+        let (spec, included_dirs) = parse_spec(&text, format, Some(&spec_path), &includes)?;
 
         // Resolve relative paths in the spec to absolute ones with a temporary
         // resolver that takes the local neighbourhood of the spec fragment
-        // into account.
+        // into account, as well as the neighbourhood of included fragments
+        // with lower priority.
         // Not modifying self.resolv avoids hard to track down bugs when files
         // are resolved relative to some earlier spec.
-        let spec = canonicalize(spec, &resolv)?;
+        for dir in included_dirs {
+            resolv
+                .add_base(dir)
+                .map_err(|e| Error::resolve(e, ResolveErrorKind::Include))?;
+        }
+        let mut spec = canonicalize(spec, &resolv)?;
+
+        // Written next to the spec, like inputs are read from there
+        if let Some(spec_dir) = spec_path.parent() {
+            let spec_dir_outputs = &mut self.spec_dir_outputs;
+            map_outputs(&mut spec, |output| {
+                let located = relocated(spec_dir, output);
+                if located != output {
+                    spec_dir_outputs.insert(located.clone(), String::from(output));
+                }
+                located
+            });
+        }
 
+        if self.spec_name.is_none() {
+            self.spec_name = Some(spec_file_stem(&spec_path));
+        }
+        self.append_spec_fragment(&spec)
+    }
+
+    /// Appends a simulation spec YAML read from the given source, e.g. stdin.
+    /// Relative paths in the spec are resolved like for specs from strings,
+    /// i.e. relative to the working directory and added base paths.
+    ///
+    /// Gzipped specs are detected by their magic number and decompressed.
+    /// Whether the spec is YAML or TOML is guessed, see `SpecFormat::sniff`.
+    pub fn append_spec_fragment_reader<R: Read>(self, reader: R) -> Result<Self, Error> {
+        let text = read_to_string_sniffed(reader)?;
+        let (spec, _) = parse_spec(&text, SpecFormat::sniff(&text), None, &self.resolv)?;
+        let spec = canonicalize(spec, &self.resolv)?;
         self.append_spec_fragment(&spec)
     }
 
     pub fn append_spec_fragment_str(self, spec: &str) -> Result<Self, Error> {
-        let spec = serde_yaml::from_str(spec)?;
+        let (spec, _) = parse_spec(spec, SpecFormat::Yaml, None, &self.resolv)?;
         let spec = canonicalize(spec, &self.resolv)?;
         self.append_spec_fragment(&spec)
     }
@@ -123,21 +195,299 @@ impl SimulationBuilder {
         Ok(self)
     }
 
+    /// Replaces a single field of the spec fragments appended so far, given
+    /// as a dotted path, e.g. `benchmark.iterations` or `effects.0.width`,
+    /// with the given value in YAML syntax.
+    ///
+    /// In contrast to appending a fragment, lists are replaced rather than
+    /// appended to.
+    pub fn set_field(mut self, path: &str, value: &str) -> Result<Self, Error> {
+        let value: Value =
+            serde_yaml::from_str(value).map_err(|e| Error::spec_override(path, e))?;
+
+        let mut spec =
+            serde_yaml::to_value(&self.spec).map_err(|e| Error::spec_override(path, e))?;
+        set_field(&mut spec, path, value).map_err(|e| Error::spec_override(path, e))?;
+        let spec = serde_yaml::from_value(spec).map_err(|e| Error::spec_override(path, e))?;
+
+        // Paths may have been changed and need to be resolved again
+        self.spec = canonicalize(spec, &self.resolv)?;
+        Ok(self)
+    }
+
+    /// Overrides how substance concentrations of surfels near a texel are
+    /// combined when baking textures. If `flat`, each texel gets the
+    /// concentration of its nearest surfel, otherwise closer surfels are
+    /// weighted higher.
+    pub fn flat_filtering(mut self, flat: bool) -> Self {
+        self.spec.flat_filtering = Some(flat);
+        self
+    }
+
+    /// Overrides the filter that bakes surfel concentrations into density
+    /// textures when building, replacing `flat_filtering`. The radius of
+    /// linear and gaussian filters defaults to the surfel distance.
+    pub fn bake_filter(mut self, filter: BakeFilter, radius: Option<f32>) -> Self {
+        self.options.bake_filter = Some(filter);
+        self.options.bake_radius = radius;
+        self
+    }
+
+    /// Overrides the bits per channel of density textures written as PNG,
+    /// which must be 8 or 16 for the simulation to build.
+    pub fn output_bit_depth(mut self, bits: u8) -> Self {
+        self.spec.output_bit_depth = Some(bits);
+        self
+    }
+
+    /// Restricts weathering to the bright regions of the mask image at the
+    /// given path, replacing a region mask that may be set in the spec.
+    ///
+    /// See `RegionMask` for how the mask maps onto the scene.
+    pub fn region_mask<P>(mut self, mask: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mask = self
+            .resolv
+            .resolve(mask)
+            .map_err(|e| Error::resolve(e, ResolveErrorKind::RegionMask))?;
+
+        self.spec.region_mask = Some(mask);
+        Ok(self)
+    }
+
+    /// Restricts the simulation to the entities with the given names.
+    ///
+    /// If `unselected_as_occluders` is set, the other entities still obstruct
+    /// gammatons, but no surfels are generated for them and they are not
+    /// exported. Otherwise, they are ignored completely.
+    ///
+    /// Building fails if any of the names does not occur in the scene, or if
+    /// none of the selected entities has a material with a surfel spec.
+    pub fn select_objects<I, S>(mut self, names: I, unselected_as_occluders: bool) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.objects.extend(names.into_iter().map(Into::into));
+        self.options.unselected_as_occluders = unselected_as_occluders;
+        self
+    }
+
+    /// Restricts the textures baked by density effects to the substances
+    /// with the given names. Can be called multiple times.
+    ///
+    /// Building fails if any of the names is not a substance of the spec.
+    pub fn only_substances<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.substances.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Enables or disables a warning when building a simulation with
+    /// substances that are mentioned in the specs, but are never emitted
+    /// by a source, present on a surface initially, or produced by a rule.
+    pub fn warn_unused_substances(mut self, warn: bool) -> Self {
+        self.options.warn_unused_substances = warn;
+        self
+    }
+
+    /// Fails building instead of warning about problems like a spec version
+    /// that does not match the supported one, or substances that are never
+    /// used, which are checked under strict mode even without
+    /// `warn_unused_substances`.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.options.strict = strict;
+        self
+    }
+
+    /// Records the time spent in each phase of loading and running the
+    /// simulation, see `SimulationRunner::profile`.
+    pub fn profile(mut self, profile: bool) -> Self {
+        self.options.profile = profile;
+        self
+    }
+
+    /// Overrides the bounce probabilities of all gammaton sources with zero,
+    /// so that gammatons settle on the first surface they hit.
+    pub fn first_hit_only(mut self, first_hit_only: bool) -> Self {
+        self.options.first_hit_only = first_hit_only;
+        self
+    }
+
+    /// Prepends the given directory to all relative output paths of the spec
+    /// fragments appended so far, so effects, benchmarks and the log of the
+    /// spec are written below it.
+    ///
+    /// Outputs of spec files, which are resolved against the directory of
+    /// the spec, are moved below the given directory with their original
+    /// relative path instead.
+    pub fn output_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        let dir = dir.as_ref();
+        let spec_dir_outputs = &self.spec_dir_outputs;
+        map_outputs(&mut self.spec, |output| match spec_dir_outputs.get(output) {
+            Some(original) => dir.join(original).to_string_lossy().into_owned(),
+            None => relocated(dir, output),
+        });
+        self
+    }
+
+    /// Overrides the number of iterations of the spec when building, even if
+    /// later fragments specify iterations.
+    pub fn iterations(mut self, iterations: u32) -> Self {
+        self.options.iterations = Some(iterations);
+        self
+    }
+
+    /// Overrides the surfel distance of the spec when building with one that
+    /// yields roughly the given number of surfels per square unit.
+    pub fn surfel_density(mut self, density: f32) -> Self {
+        self.options.surfel_density = Some(density);
+        self
+    }
+
+    /// Overrides the number of gammatons emitted per iteration by each of
+    /// the gammaton sources of the spec when building.
+    pub fn emission_count(mut self, emission_count: usize) -> Self {
+        self.options.emission_count = Some(emission_count);
+        self
+    }
+
+    /// Builds a runner that only traces, without performing effects or
+    /// writing benchmarks, e.g. to measure tracing throughput.
+    pub fn skip_outputs(mut self, skip_outputs: bool) -> Self {
+        self.options.skip_outputs = skip_outputs;
+        self
+    }
+
+    /// Overrides the flow direction of all gammaton sources of the spec
+    /// when building.
+    pub fn flow_direction(mut self, direction: [f32; 3]) -> Self {
+        self.options.flow_direction = Some(direction);
+        self
+    }
+
+    /// Only keeps the given number of triangles of the simulated entities
+    /// when building, for quick previews at the cost of accuracy.
+    pub fn limit_triangles(mut self, limit: usize) -> Self {
+        self.options.triangle_limit = Some(limit);
+        self
+    }
+
+    /// Overrides width and height of all textures baked by density and
+    /// layer effects when building.
+    pub fn texture_resolution(mut self, width: usize, height: usize) -> Self {
+        self.options.texture_resolution = Some((width, height));
+        self
+    }
+
+    /// Replaces all options set so far, e.g. with `select_objects` or
+    /// `iterations`, with the given ones.
+    pub fn options(mut self, options: LoadOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     /// Gets the current state of the underlying spec being mutated.
     pub fn spec(&self) -> &SimulationSpec {
         &self.spec
     }
 
+    /// Copy of the current spec with the options that replace values of the
+    /// spec applied, i.e. iterations, surfel density, texture resolution and
+    /// skipping outputs, as it will be built.
+    pub fn effective_spec(&self) -> SimulationSpec {
+        let mut spec = self.spec.clone();
+        override_spec(&mut spec, &self.options);
+        spec
+    }
+
     /// Time of instantiation of this builder.
     pub fn creation_time(&self) -> DateTime<Local> {
         self.creation_time
     }
 
+    /// Name that replaces `{spec_name}` in output and log paths, which is
+    /// the stem of the first appended spec file, e.g. `a` for `a.yml` or
+    /// `a.yml.gz`, or `spec` if no spec file was appended, e.g. for specs
+    /// from stdin.
+    pub fn spec_name(&self) -> &str {
+        self.spec_name.as_ref().map(|n| n.as_str()).unwrap_or(DEFAULT_SPEC_NAME)
+    }
+
+    /// Opens and parses all input assets of the current spec without
+    /// building a simulation and reports all that failed to load.
+    pub fn check_assets(&self) -> AssetReport {
+        check_assets(&self.spec, &self.resolv)
+    }
+
+    /// Loads the gammaton sources and surfel specs of the current spec and
+    /// lists the parameters of all substances they mention.
+    pub fn substances(&self) -> Result<SubstanceTable, Error> {
+        SubstanceTable::load(&self.spec, &self.resolv)
+    }
+
+    /// Checks that the directories of all output files, benchmarks and logs
+    /// of the current spec are writable or can be created.
+    pub fn check_output_dirs(&self) -> AssetReport {
+        check_output_dirs(&self.spec)
+    }
+
+    /// Creates the directories of all output files, benchmarks and logs of
+    /// the current spec and checks that a file can be written into each.
+    pub fn probe_output_dirs(&self) -> AssetReport {
+        probe_output_dirs(&self.spec)
+    }
+
+    /// Lists the existing files that the effects of the current spec would
+    /// overwrite. Placeholders other than `{datetime}` and `{spec_name}`
+    /// match any text, so files of iterations or substances that the run
+    /// would not write are listed, too.
+    pub fn existing_outputs(&self) -> Vec<PathBuf> {
+        existing_outputs(
+            &self.spec,
+            &fs_timestamp(self.creation_time),
+            self.spec_name(),
+        )
+    }
+
+    /// Loads the scenes of the current spec and estimates the peak memory of
+    /// the simulation before surfels are generated and textures allocated,
+    /// see `estimate_peak_memory`.
+    pub fn estimate_peak_memory(&self) -> Result<u64, Error> {
+        estimate_peak_memory(&self.spec, &self.resolv, &self.options)
+    }
+
+    /// Loads the scenes of the current spec and calculates the bounds of the
+    /// entities to simulate before surfels are generated, see
+    /// `estimate_bounds`.
+    pub fn bounds(&self) -> Result<Bounds, Error> {
+        estimate_bounds(&self.spec, &self.resolv, &self.options)
+    }
+
     pub fn build(self) -> Result<SimulationRunner, Error> {
-        instantiate(self.spec, &self.resolv, self.creation_time)
+        let spec_name = String::from(self.spec_name());
+        instantiate(self.spec, &self.resolv, self.creation_time, &spec_name, &self.options)
     }
 }
 
+/// File name of the given spec file without the format extension and a
+/// `.gz` extension for gzipped specs.
+fn spec_file_stem(spec_path: &Path) -> String {
+    let decompressed = match spec_path.extension() {
+        Some(ext) if ext == "gz" => spec_path.with_extension(""),
+        _ => spec_path.to_path_buf(),
+    };
+    decompressed
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from(DEFAULT_SPEC_NAME))
+}
+
 /// Resolver that resolves absolute files and files relative
 /// to local directory. Panics if working directory cannot be
 /// canonicalized.
@@ -157,6 +507,15 @@ fn local_resolver() -> Resolver {
 #[cfg(test)]
 mod test {
     use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use spec::EffectSpec;
+    use std::env;
+    use std::f32::{INFINITY, NEG_INFINITY};
+    use std::fs::{copy, create_dir_all, read_dir, read_to_string, remove_dir_all, write};
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::process::Command;
 
     #[test]
     fn append_str() {
@@ -166,4 +525,515 @@ mod test {
 
         assert_eq!("Funny Test Simulation", &builder.spec().name)
     }
+
+    #[test]
+    fn append_reader() {
+        let spec = "name: Piped\nscenes: [\"tests/assets/sky.obj\"]".as_bytes();
+        let builder = SimulationBuilder::new()
+            .append_spec_fragment_reader(spec)
+            .unwrap();
+
+        assert_eq!("Piped", &builder.spec().name);
+        assert!(
+            builder.spec().scenes[0].is_absolute(),
+            "Expected scene to be resolved relative to working directory"
+        );
+    }
+
+    #[test]
+    fn set_fields() {
+        let builder = SimulationBuilder::new()
+            .append_spec_fragment_file("tests/examples/sky.yml")
+            .unwrap()
+            .set_field("iterations", "20")
+            .unwrap()
+            .set_field("benchmark.iterations", "bench.csv")
+            .unwrap();
+
+        assert_eq!(Some(20), builder.spec().iterations);
+        assert_eq!(
+            Some(PathBuf::from("bench.csv")),
+            builder.spec().benchmark.as_ref().unwrap().iterations
+        );
+
+        match builder.set_field("iterations", "many") {
+            Err(Error::Override { ref path, .. }) => assert_eq!("iterations", path),
+            Err(err) => panic!("Expected override error, but got {}", err),
+            Ok(_) => panic!("Expected type mismatch to be an error"),
+        }
+    }
+
+    #[test]
+    fn select_unknown_object() {
+        let result = SimulationBuilder::new()
+            .append_spec_fragment_str(
+                "scenes: [\"tests/assets/buddha.obj\"]\nsurfels_by_material:\n  _: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 0.5\neffects:\n  - export:\n      obj_pattern: \"/tmp/aitios-select-test.obj\"\n      mtl_pattern: \"/tmp/aitios-select-test.mtl\"",
+            )
+            .unwrap()
+            .select_objects(vec!["statue_that_does_not_exist"], false)
+            .build();
+
+        match result {
+            Err(Error::UnknownObjects { unknown, available }) => {
+                assert_eq!(vec![String::from("statue_that_does_not_exist")], unknown);
+                assert!(!available.is_empty(), "Expected available objects to be listed");
+            }
+            Err(err) => panic!("Expected unknown object error, but got {}", err),
+            Ok(_) => panic!("Expected selecting a nonexistent object to fail"),
+        }
+    }
+
+    #[test]
+    fn selecting_only_unmapped_objects_fails() {
+        let result = SimulationBuilder::new()
+            .append_spec_fragment_str(
+                "scenes: [\"tests/assets/sky.obj\"]\nsurfels_by_material:\n  marble: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 2.0",
+            )
+            .unwrap()
+            .select_objects(vec!["emitter_Icosphere"], false)
+            .build();
+
+        match result {
+            Err(Error::NothingSelected(objects)) => {
+                assert_eq!(vec![String::from("emitter_Icosphere")], objects)
+            }
+            Err(err) => panic!("Expected nothing selected error, but got {}", err),
+            Ok(_) => panic!("Expected selecting only objects without surfel spec to fail"),
+        }
+    }
+
+    #[test]
+    fn invalid_output_bit_depth() {
+        let result = SimulationBuilder::new()
+            .append_spec_fragment_file("tests/examples/sky.yml")
+            .unwrap()
+            .output_bit_depth(12)
+            .build();
+
+        match result {
+            Err(Error::InvalidBitDepth(12)) => (),
+            Err(err) => panic!("Expected invalid bit depth error, but got {}", err),
+            Ok(_) => panic!("Expected 12 bits per channel to be rejected"),
+        }
+    }
+
+    #[test]
+    fn strict_rejects_unused_substances() {
+        // Rust is absorbed by the sky, but never present anywhere
+        let result = SimulationBuilder::new()
+            .append_spec_fragment_file("tests/examples/sky.yml")
+            .unwrap()
+            .strict(true)
+            .build();
+
+        match result {
+            Err(Error::UnusedSubstances(unused)) => assert_eq!(vec![String::from("rust")], unused),
+            Err(err) => panic!("Expected unused substance error, but got {}", err),
+            Ok(_) => panic!("Expected strict mode to reject unused substances"),
+        }
+    }
+
+    #[test]
+    fn only_target_objects_get_surfels() {
+        let dir = env::temp_dir().join("aitios-target-object-test");
+        create_dir_all(&dir).unwrap();
+        copy("tests/assets/sky.mtl", dir.join("sky.mtl")).unwrap();
+        let sky = read_to_string("tests/assets/sky.obj").unwrap();
+        // Moved away from the icosphere, so that surfels can be told apart
+        let moved: Vec<String> = sky
+            .lines()
+            .map(|line| match obj_vertex(line) {
+                Some(v) => format!("v {} {} {}", v[0] + 100.0, v[1], v[2]),
+                None => line.replace("o emitter_Icosphere", "o pedestal"),
+            })
+            .collect();
+        let pedestal = dir.join("pedestal.obj");
+        write(&pedestal, moved.join("\n")).unwrap();
+
+        let spec = format!(
+            "scenes: [\"tests/assets/sky.obj\", {:?}]\nsurfels_by_material:\n  _: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 2.0",
+            pedestal
+        );
+        let load = |objects: Vec<&str>| {
+            SimulationBuilder::new()
+                .append_spec_fragment_str(&spec)
+                .unwrap()
+                .select_objects(objects, true)
+                .build()
+        };
+        let surfels = |objects: Vec<&str>| load(objects).unwrap().checkpoint().surfels.len();
+
+        let all = surfels(Vec::new());
+        let target = surfels(vec!["emitter_Icosphere"]);
+        let cloud = dir.join("target.xyz");
+        load(vec!["emitter_Icosphere"])
+            .unwrap()
+            .export_surfel_cloud(&cloud)
+            .unwrap();
+        let positions: Vec<[f32; 3]> = read_to_string(&cloud)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| {
+                let c: Vec<f32> = line
+                    .split_whitespace()
+                    .take(3)
+                    .map(|c| c.parse().unwrap())
+                    .collect();
+                [c[0], c[1], c[2]]
+            })
+            .collect();
+        let icosphere_bounds = obj_bounds(&sky);
+        let pedestal_bounds = obj_bounds(&moved.join("\n"));
+        let unknown = load(vec!["statue"]);
+        remove_dir_all(&dir).unwrap();
+
+        assert!(target > 0, "Expected surfels on the target object");
+        assert!(target < all, "Expected no surfels on the occluding pedestal");
+        assert_eq!(target, positions.len());
+        for position in positions.iter() {
+            assert!(
+                within(position, &icosphere_bounds),
+                "Expected {:?} on the icosphere",
+                position
+            );
+            assert!(
+                !within(position, &pedestal_bounds),
+                "Expected no surfel on the pedestal, but got {:?}",
+                position
+            );
+        }
+        match unknown {
+            Err(Error::UnknownObjects { available, .. }) => assert_eq!(
+                vec![String::from("emitter_Icosphere"), String::from("pedestal")],
+                available
+            ),
+            Err(err) => panic!("Expected unknown object error, but got {}", err),
+            Ok(_) => panic!("Expected selecting a nonexistent object to fail"),
+        }
+    }
+
+    /// Position of an OBJ vertex line.
+    fn obj_vertex(line: &str) -> Option<Vec<f32>> {
+        if line.starts_with("v ") {
+            Some(line[2..].split_whitespace().map(|c| c.parse().unwrap()).collect())
+        } else {
+            None
+        }
+    }
+
+    /// Minimum and maximum of the vertices of an OBJ file.
+    fn obj_bounds(obj: &str) -> ([f32; 3], [f32; 3]) {
+        let mut bounds = ([INFINITY; 3], [NEG_INFINITY; 3]);
+        for vertex in obj.lines().filter_map(obj_vertex) {
+            for axis in 0..3 {
+                bounds.0[axis] = bounds.0[axis].min(vertex[axis]);
+                bounds.1[axis] = bounds.1[axis].max(vertex[axis]);
+            }
+        }
+        bounds
+    }
+
+    fn within(position: &[f32; 3], &(min, max): &([f32; 3], [f32; 3])) -> bool {
+        // Tolerates rounding of the positions in the point cloud
+        (0..3).all(|axis| {
+            position[axis] >= min[axis] - 0.001 && position[axis] <= max[axis] + 0.001
+        })
+    }
+
+    #[test]
+    fn override_iterations() {
+        let runner = SimulationBuilder::new()
+            .append_spec_fragment_file("tests/examples/sky.yml")
+            .unwrap()
+            .append_spec_fragment_str("iterations: 10")
+            .unwrap()
+            .iterations(1)
+            .build()
+            .unwrap();
+
+        assert_eq!(Some(1), runner.spec().iterations);
+    }
+
+    #[test]
+    fn missing_scene_is_named() {
+        let result = SimulationBuilder::new().append_spec_fragment_str(
+            "scenes: [\"tests/assets/sky.obj\", \"tests/assets/missing.obj\"]",
+        );
+
+        match result {
+            Err(Error::InputsMissing(missing)) => {
+                assert_eq!(vec![PathBuf::from("tests/assets/missing.obj")], missing)
+            }
+            Err(err) => panic!("Expected missing input error, but got {}", err),
+            Ok(_) => panic!("Expected missing scene to be an error"),
+        }
+    }
+
+    #[test]
+    fn misspelled_field_is_located() {
+        let result = SimulationBuilder::new().append_spec_fragment_str("name: Typo\niteratons: 2");
+
+        match result {
+            Err(err @ Error::Parse { .. }) => {
+                let message = format!("{}", err);
+                assert!(message.contains("line 2"), "{}", message);
+                assert!(message.contains("(did you mean `iterations`?)"), "{}", message);
+            }
+            Err(err) => panic!("Expected parse error, but got {}", err),
+            Ok(_) => panic!("Expected misspelled field to be an error"),
+        }
+    }
+
+    #[test]
+    fn environment_variables_are_expanded() {
+        env::set_var("AITIOS_BUILDER_TEST_NAME", "Expanded");
+        let builder = SimulationBuilder::new()
+            .append_spec_fragment_str("name: ${AITIOS_BUILDER_TEST_NAME} $$1")
+            .unwrap();
+        assert_eq!("Expanded $1", builder.spec().name);
+
+        let result = SimulationBuilder::new()
+            .append_spec_fragment_str("name: ${AITIOS_BUILDER_TEST_UNSET}");
+        match result {
+            Err(Error::UnsetVariable(ref name)) => assert_eq!("AITIOS_BUILDER_TEST_UNSET", name),
+            _ => panic!("Expected unset variable to be an error"),
+        }
+    }
+
+    /// Variable with the spec for `load_spec_from_other_cwd`.
+    const OTHER_CWD_SPEC_VAR: &str = "AITIOS_OTHER_CWD_TEST_SPEC";
+
+    #[test]
+    fn paths_resolve_relative_to_spec_first() {
+        let project = env::temp_dir().join("aitios-spec-dir-test");
+        let elsewhere = env::temp_dir().join("aitios-spec-dir-test-cwd");
+        remove_dir_all(&project).ok();
+        remove_dir_all(&elsewhere).ok();
+        create_dir_all(&elsewhere).unwrap();
+        let files = [
+            "assets/sky.obj",
+            "assets/sky.mtl",
+            "examples/concrete.yml",
+            "examples/rain.yml",
+        ];
+        for file in files.iter() {
+            let copied = project.join("tests").join(file);
+            create_dir_all(copied.parent().unwrap()).unwrap();
+            copy(Path::new("tests").join(file), copied).unwrap();
+        }
+        let spec = project.join("sim.yml");
+        write(
+            &spec,
+            "scenes: [\"tests/assets/sky.obj\"]\nsurfels_by_material:\n  _: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 2.0\niterations: 1\neffects:\n  - dump_surfels:\n      obj_pattern: iteration-{iteration}.obj",
+        )
+        .unwrap();
+
+        // In a child process, since changing the working directory would
+        // affect the other tests, which run in parallel
+        let status = Command::new(env::current_exe().unwrap())
+            .args(&[
+                "--ignored",
+                "--exact",
+                "builder::builder::test::load_spec_from_other_cwd",
+            ])
+            .env(OTHER_CWD_SPEC_VAR, &spec)
+            .current_dir(&elsewhere)
+            .status()
+            .unwrap();
+        let written = project.join("iteration-1.obj").is_file();
+        let in_cwd = read_dir(&elsewhere).unwrap().count();
+        remove_dir_all(&project).unwrap();
+        remove_dir_all(&elsewhere).unwrap();
+
+        assert!(status.success(), "Expected spec to load from another directory");
+        assert!(written, "Expected surfels to be written next to the spec");
+        assert_eq!(0, in_cwd, "Expected nothing to be written into the working directory");
+    }
+
+    /// Run by `paths_resolve_relative_to_spec_first` in a working directory
+    /// other than the one of the spec.
+    #[test]
+    #[ignore]
+    fn load_spec_from_other_cwd() {
+        let spec = match env::var_os(OTHER_CWD_SPEC_VAR) {
+            Some(spec) => PathBuf::from(spec),
+            None => return,
+        };
+        let project = spec.parent().unwrap().canonicalize().unwrap();
+
+        let builder = SimulationBuilder::new()
+            .append_spec_fragment_file(&spec)
+            .unwrap();
+        assert_eq!(vec![project.join("tests/assets/sky.obj")], builder.spec().scenes);
+        assert_eq!(vec![project.join("tests/examples/rain.yml")], builder.spec().sources);
+        assert_eq!(
+            vec![project.join("iteration-{iteration}.obj")],
+            dumped_patterns(builder.spec())
+        );
+
+        // Moved below the output directory instead, with their relative path
+        let relocated = SimulationBuilder::new()
+            .append_spec_fragment_file(&spec)
+            .unwrap()
+            .output_dir("/tmp/runs");
+        assert_eq!(
+            vec![PathBuf::from("/tmp/runs/iteration-{iteration}.obj")],
+            dumped_patterns(relocated.spec())
+        );
+
+        builder.build().unwrap().run();
+    }
+
+    fn dumped_patterns(spec: &SimulationSpec) -> Vec<PathBuf> {
+        spec.effects
+            .iter()
+            .filter_map(|effect| match effect {
+                &EffectSpec::DumpSurfels { ref obj_pattern } => Some(PathBuf::from(obj_pattern)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gzipped_spec_loads_like_plain() {
+        let yaml = "name: Compressed\nscenes: [\"tests/assets/sky.obj\"]\nsurfels_by_material:\n  _: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 2.0\niterations: 3\neffects:\n  - dump_surfels:\n      obj_pattern: iteration-{iteration}.obj";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(yaml.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let dir = env::temp_dir().join("aitios-gzip-spec-test");
+        create_dir_all(&dir).unwrap();
+        let gzipped_file = dir.join("sim.yml.gz");
+        write(&gzipped_file, &gzipped).unwrap();
+
+        let plain = SimulationBuilder::new().append_spec_fragment_str(yaml).unwrap();
+        let from_file = SimulationBuilder::new()
+            .append_spec_fragment_file(&gzipped_file)
+            .unwrap();
+        let from_reader = SimulationBuilder::new()
+            .append_spec_fragment_reader(&gzipped[..])
+            .unwrap();
+        remove_dir_all(&dir).unwrap();
+
+        let plain = serde_yaml::to_string(plain.spec()).unwrap();
+        assert_eq!(plain, serde_yaml::to_string(from_file.spec()).unwrap());
+        assert_eq!(plain, serde_yaml::to_string(from_reader.spec()).unwrap());
+    }
+
+    #[test]
+    fn toml_spec_loads_like_yaml() {
+        let yaml = "name: Tomled\nscenes: [\"tests/assets/sky.obj\"]\nsurfels_by_material:\n  _: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 2.0\niterations: 3\neffects:\n  - dump_surfels:\n      obj_pattern: iteration-{iteration}.obj";
+        let toml = "name = \"Tomled\"\nscenes = [\"tests/assets/sky.obj\"]\nsources = [\"tests/examples/rain.yml\"]\nsurfel_distance = 2.0\niterations = 3\n\n[surfels_by_material]\n_ = \"tests/examples/concrete.yml\"\n\n[[effects]]\n[effects.dump_surfels]\nobj_pattern = \"iteration-{iteration}.obj\"\n";
+
+        let dir = env::temp_dir().join("aitios-toml-spec-test");
+        create_dir_all(&dir).unwrap();
+        let toml_file = dir.join("sim.toml");
+        write(&toml_file, toml).unwrap();
+
+        let plain = SimulationBuilder::new().append_spec_fragment_str(yaml).unwrap();
+        let from_file = SimulationBuilder::new()
+            .append_spec_fragment_file(&toml_file)
+            .unwrap();
+        let from_reader = SimulationBuilder::new()
+            .append_spec_fragment_reader(toml.as_bytes())
+            .unwrap();
+        remove_dir_all(&dir).unwrap();
+
+        let plain = serde_yaml::to_string(plain.spec()).unwrap();
+        assert_eq!(plain, serde_yaml::to_string(from_file.spec()).unwrap());
+        assert_eq!(plain, serde_yaml::to_string(from_reader.spec()).unwrap());
+        assert!(from_file.build().is_ok());
+    }
+
+    #[test]
+    fn surfel_density_overrides_spec() {
+        let surfel_count = |density: f32| {
+            SimulationBuilder::new()
+                .append_spec_fragment_str("scenes: [\"tests/assets/sky.obj\"]\nsurfels_by_material:\n  _: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 2.0\neffects:\n  - dump_surfels:\n      obj_pattern: iteration-{iteration}.obj")
+                .unwrap()
+                .surfel_density(density)
+                .build()
+                .unwrap()
+                .checkpoint()
+                .surfels
+                .len()
+        };
+
+        let sparse = surfel_count(0.05);
+        let dense = surfel_count(0.5);
+        assert!(sparse > 0);
+        assert!(
+            dense > 2 * sparse,
+            "Expected ten times the density to yield many more surfels, got {} and {}",
+            sparse,
+            dense
+        );
+    }
+
+    #[test]
+    fn limited_triangles_yield_fewer_surfels() {
+        let surfel_count = |limit: Option<usize>| {
+            let builder = SimulationBuilder::new()
+                .append_spec_fragment_str("scenes: [\"tests/assets/sky.obj\"]\nsurfels_by_material:\n  _: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 0.5\neffects:\n  - dump_surfels:\n      obj_pattern: iteration-{iteration}.obj")
+                .unwrap();
+            let builder = match limit {
+                Some(limit) => builder.limit_triangles(limit),
+                None => builder,
+            };
+            builder.build().unwrap().checkpoint().surfels.len()
+        };
+
+        let full = surfel_count(None);
+        let preview = surfel_count(Some(1));
+        assert!(preview > 0);
+        assert!(
+            preview < full,
+            "Expected a single triangle to yield less surfels than the whole sky, got {} and {}",
+            preview,
+            full
+        );
+        assert_eq!(full, surfel_count(Some(1_000_000)));
+    }
+
+    #[test]
+    fn substances_of_sources_and_materials_are_listed() {
+        let table = SimulationBuilder::new()
+            .append_spec_fragment_file("tests/examples/sky.yml")
+            .unwrap()
+            .substances()
+            .unwrap();
+
+        let listing = format!("{}", table);
+        let lines: Vec<&str> = listing.lines().collect();
+        assert!(lines[0].starts_with("SUBSTANCE  SPEC"), "{}", listing);
+        assert!(
+            lines.iter().any(|l| l.starts_with("humidity   material _")
+                && l.ends_with("deteriorate -0.5")),
+            "{}",
+            listing
+        );
+        assert!(
+            lines.iter().any(|l| l.starts_with("humidity   source Rain")),
+            "{}",
+            listing
+        );
+        assert_eq!(5, lines.len(), "{}", listing);
+    }
+
+    #[test]
+    fn spec_name_is_stem_of_first_spec_file() {
+        let builder = SimulationBuilder::new()
+            .append_spec_fragment_str("name: Inline")
+            .unwrap();
+        assert_eq!("spec", builder.spec_name());
+
+        let builder = builder
+            .append_spec_fragment_file("tests/examples/sky.yml")
+            .unwrap()
+            .append_spec_fragment_file("tests/examples/simulation.yml")
+            .unwrap();
+        assert_eq!("sky", builder.spec_name());
+        assert_eq!("sim", spec_file_stem(Path::new("specs/sim.yml.gz")));
+    }
 }
@@ -1,4 +1,4 @@
-use builder::{Error, ResolveErrorKind};
+use builder::{missing_inputs, Error, ResolveErrorKind};
 use files::Resolver;
 use spec::{EffectSpec, SimulationSpec, Stop};
 use std::collections::HashMap;
@@ -7,14 +7,23 @@ use std::path::PathBuf;
 /// Makes relative paths in the spec fragment absolute using the given resolver.
 ///
 /// Useful when combining specs with slightly different base paths for resolving.
+///
+/// Fails with a list of all input files that cannot be found or read, before
+/// any of them is loaded.
 pub fn canonicalize(
     mut spec: SimulationSpec,
     resolver: &Resolver,
 ) -> Result<SimulationSpec, Error> {
+    let missing = missing_inputs(&spec, resolver);
+    if !missing.is_empty() {
+        return Err(Error::InputsMissing(missing));
+    }
+
     resolve_scenes(&mut spec.scenes, resolver)?;
     resolve_ton_source_specs(&mut spec.sources, resolver)?;
     resolve_surfel_specs(&mut spec.surfels_by_material, resolver)?;
     resolve_effect_spec_paths(&mut spec.effects, resolver)?;
+    resolve_region_mask(&mut spec.region_mask, resolver)?;
     // FIXME resolving outputs works differently
     // resolve_benchmarks(&mut spec.benchmark, resolver)?;
     Ok(spec)
@@ -59,6 +68,16 @@ fn resolve_surfel_specs(
     Ok(())
 }
 
+fn resolve_region_mask(mask: &mut Option<PathBuf>, resolver: &Resolver) -> Result<(), Error> {
+    if let Some(mask) = mask.as_mut() {
+        *mask = resolver
+            .resolve(&mask)
+            .map_err(|e| Error::resolve(e, ResolveErrorKind::RegionMask))?;
+    }
+
+    Ok(())
+}
+
 fn resolve_effect_spec_paths(
     specs: &mut Vec<EffectSpec>,
     resolver: &Resolver,
@@ -0,0 +1,600 @@
+use asset::obj;
+use files::Resolver;
+use scene::Entity;
+use serde::de::DeserializeOwned;
+use serde_yaml;
+use spec::{EffectSpec, SimulationSpec, SurfelSpec, TonSourceSpec};
+use std::fmt;
+use std::fs::{create_dir_all, read_dir, remove_file, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tex;
+
+/// Outcome of opening and parsing all input assets of a simulation spec.
+pub struct AssetReport {
+    /// Number of assets that were opened, including the failed ones.
+    pub checked: usize,
+    pub problems: Vec<AssetProblem>,
+}
+
+/// An input asset that could not be opened or parsed.
+#[derive(Debug)]
+pub struct AssetProblem {
+    pub path: PathBuf,
+    pub problem: String,
+}
+
+impl AssetReport {
+    fn new() -> Self {
+        Self {
+            checked: 0,
+            problems: Vec::new(),
+        }
+    }
+
+    fn record<P: AsRef<Path>, E: fmt::Display>(&mut self, path: P, result: Result<(), E>) {
+        self.checked += 1;
+        if let Err(err) = result {
+            self.problems.push(AssetProblem {
+                path: path.as_ref().to_path_buf(),
+                problem: format!("{}", err),
+            });
+        }
+    }
+
+    fn check_image<P: AsRef<Path>>(&mut self, path: P) {
+        let result = tex::open(path.as_ref()).map(|_| ());
+        self.record(path, result);
+    }
+
+    fn check_scene<P: AsRef<Path>>(&mut self, path: P) -> Option<Vec<Entity>> {
+        match obj::load(path.as_ref()) {
+            Ok(entities) => {
+                self.record::<_, String>(path, Ok(()));
+                Some(entities)
+            }
+            Err(err) => {
+                self.record(path, Err(err));
+                None
+            }
+        }
+    }
+
+    fn check_yaml<S, P>(&mut self, path: P) -> Option<S>
+    where
+        P: AsRef<Path>,
+        S: DeserializeOwned,
+    {
+        let parsed = File::open(path.as_ref())
+            .map_err(|e| format!("{}", e))
+            .and_then(|f| serde_yaml::from_reader(f).map_err(|e| format!("{}", e)));
+
+        match parsed {
+            Ok(parsed) => {
+                self.record::<_, String>(path, Ok(()));
+                Some(parsed)
+            }
+            Err(err) => {
+                self.record(path, Err(err));
+                None
+            }
+        }
+    }
+}
+
+impl fmt::Display for AssetProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}: {}", self.path, self.problem)
+    }
+}
+
+/// Opens and parses every input asset referenced by the given spec, that is
+/// scenes, textures of scene materials, gammaton source and surfel specs,
+/// emission meshes, blend samples and the region mask.
+///
+/// In contrast to building a simulation, this does not stop at the first
+/// asset that fails to load, but reports every problem found.
+pub fn check_assets(spec: &SimulationSpec, resolver: &Resolver) -> AssetReport {
+    let mut report = AssetReport::new();
+
+    for scene in spec.scenes.iter() {
+        if let Some(entities) = report.check_scene(scene) {
+            let mut textures = Vec::new();
+            for entity in entities.iter() {
+                let material = &entity.material;
+                textures.extend(
+                    [
+                        material.diffuse_color_map(),
+                        material.normal_map(),
+                        material.displacement_map(),
+                        material.metallic_map(),
+                        material.roughness_map(),
+                    ].iter()
+                    .filter_map(|t| t.cloned()),
+                );
+            }
+            textures.sort();
+            textures.dedup();
+
+            for texture in textures {
+                report.check_image(texture);
+            }
+        }
+    }
+
+    for source in spec.sources.iter() {
+        let source_spec: Option<TonSourceSpec> = report.check_yaml(source);
+        if let Some(source_spec) = source_spec {
+            match resolver.resolve_next_to(source, &source_spec.mesh) {
+                Ok(mesh) => {
+                    report.check_scene(mesh);
+                }
+                Err(err) => report.record(&source_spec.mesh, Err(err)),
+            }
+        }
+    }
+
+    for surfel_spec in spec.surfels_by_material.values() {
+        let _: Option<SurfelSpec> = report.check_yaml(surfel_spec);
+    }
+
+    for effect in spec.effects.iter() {
+        if let &EffectSpec::Layer {
+            ref normal,
+            ref displacement,
+            ref albedo,
+            ref metallicity,
+            ref roughness,
+            ..
+        } = effect
+        {
+            for blend in [normal, displacement, albedo, metallicity, roughness]
+                .iter()
+                .filter_map(|b| b.as_ref())
+            {
+                for sample in blend.stops.iter().filter_map(|s| s.sample.as_ref()) {
+                    report.check_image(sample);
+                }
+            }
+        }
+    }
+
+    if let Some(ref region_mask) = spec.region_mask {
+        report.check_image(region_mask);
+    }
+
+    report
+}
+
+/// Paths of the input files referenced directly by the given spec, that is
+/// scenes, gammaton source and surfel specs, blend samples and the region
+/// mask, without the assets referenced by these files in turn.
+pub fn input_paths(spec: &SimulationSpec) -> Vec<PathBuf> {
+    let mut inputs: Vec<PathBuf> = spec
+        .scenes
+        .iter()
+        .chain(spec.sources.iter())
+        .cloned()
+        .chain(spec.surfels_by_material.values().map(PathBuf::from))
+        .collect();
+
+    for effect in spec.effects.iter() {
+        if let &EffectSpec::Layer {
+            ref normal,
+            ref displacement,
+            ref albedo,
+            ref metallicity,
+            ref roughness,
+            ..
+        } = effect
+        {
+            for blend in [normal, displacement, albedo, metallicity, roughness]
+                .iter()
+                .filter_map(|b| b.as_ref())
+            {
+                inputs.extend(blend.stops.iter().filter_map(|s| s.sample.clone()));
+            }
+        }
+    }
+
+    inputs.extend(spec.region_mask.iter().cloned());
+    inputs
+}
+
+/// Input files referenced directly by the given spec that cannot be found
+/// with the given resolver or cannot be opened for reading, in the order
+/// they occur in the spec.
+pub fn missing_inputs(spec: &SimulationSpec, resolver: &Resolver) -> Vec<PathBuf> {
+    let mut missing: Vec<PathBuf> = Vec::new();
+    for input in input_paths(spec) {
+        let readable = resolver
+            .resolve(&input)
+            .map(|resolved| File::open(resolved).is_ok())
+            .unwrap_or(false);
+        if !readable && !missing.contains(&input) {
+            missing.push(input);
+        }
+    }
+    missing
+}
+
+/// Checks that the directories that output files, benchmarks and logs will
+/// be written to either exist and are writable, or can be created below
+/// their closest existing, writable ancestor.
+///
+/// Placeholders like `{datetime}` are not expanded, the directory above the
+/// first placeholder is checked instead.
+pub fn check_output_dirs(spec: &SimulationSpec) -> AssetReport {
+    let mut report = AssetReport::new();
+    for dir in output_dirs(spec) {
+        let result = check_writable_dir(&dir);
+        report.record(dir, result);
+    }
+    report
+}
+
+/// Creates the directories that output files, benchmarks and logs will be
+/// written to and writes and removes a probe file in each, to find out
+/// before simulating whether the outputs can be written.
+///
+/// In contrast to `check_output_dirs`, this modifies the file system, but
+/// also detects full disks and read-only mounts.
+pub fn probe_output_dirs(spec: &SimulationSpec) -> AssetReport {
+    let mut report = AssetReport::new();
+    for dir in output_dirs(spec) {
+        let result = probe_dir(&dir);
+        report.record(dir, result);
+    }
+    report
+}
+
+/// Lists the existing files that the effects of the spec would overwrite.
+///
+/// `{datetime}` and `{spec_name}` are replaced with the given values, and
+/// other placeholders like `{iteration}` or `{substance}` match anything,
+/// so this may list files that a run would not actually write, e.g. for
+/// iterations beyond the last one of the spec.
+pub fn existing_outputs(spec: &SimulationSpec, datetime: &str, spec_name: &str) -> Vec<PathBuf> {
+    let mut existing: Vec<PathBuf> = effect_patterns(spec)
+        .iter()
+        .map(|p| p.replace("{datetime}", datetime).replace("{spec_name}", spec_name))
+        .flat_map(|p| matching_files(&p))
+        .collect();
+    existing.sort();
+    existing.dedup();
+    existing
+}
+
+/// Existing files matching a pattern, walking the directories of each
+/// component with a placeholder.
+fn matching_files(pattern: &str) -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::new()];
+
+    for component in Path::new(pattern).components() {
+        let component = component.as_os_str().to_string_lossy();
+        if component.contains('{') {
+            candidates = candidates
+                .iter()
+                .flat_map(|dir| {
+                    let listed = if dir.as_os_str().is_empty() {
+                        read_dir(".")
+                    } else {
+                        read_dir(dir)
+                    };
+                    listed
+                        .into_iter()
+                        .flat_map(|entries| entries.filter_map(|e| e.ok()))
+                        .map(|e| e.file_name().to_string_lossy().into_owned())
+                        .filter(|name| matches_placeholders(&component, name))
+                        .map(|name| dir.join(name))
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+        } else {
+            candidates.iter_mut().for_each(|c| c.push(component.as_ref()));
+        }
+    }
+
+    candidates.retain(|c| c.is_file());
+    candidates
+}
+
+/// Checks if a file name matches a path component, where each placeholder
+/// in the component matches any text, including none.
+fn matches_placeholders(component: &str, name: &str) -> bool {
+    let mut literals = Vec::new();
+    let mut rest = component;
+    while let Some(start) = rest.find('{') {
+        literals.push(&rest[..start]);
+        rest = match rest[start..].find('}') {
+            Some(end) => &rest[start + end + 1..],
+            None => "",
+        };
+    }
+    literals.push(rest);
+
+    if literals.len() == 1 {
+        return name == component;
+    }
+
+    let first = literals[0];
+    let last = literals[literals.len() - 1];
+    if name.len() < first.len() + last.len() || !name.starts_with(first) || !name.ends_with(last) {
+        return false;
+    }
+
+    let mut between = &name[first.len()..name.len() - last.len()];
+    for literal in literals[1..literals.len() - 1].iter() {
+        match between.find(literal) {
+            Some(idx) => between = &between[idx + literal.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Output patterns of all effects of the spec.
+fn effect_patterns(spec: &SimulationSpec) -> Vec<String> {
+    let mut patterns: Vec<String> = Vec::new();
+
+    for effect in spec.effects.iter() {
+        match effect {
+            &EffectSpec::Density {
+                ref tex_pattern,
+                ref obj_pattern,
+                ref mtl_pattern,
+                ..
+            } => {
+                patterns.push(tex_pattern.clone());
+                patterns.extend(obj_pattern.iter().chain(mtl_pattern.iter()).cloned());
+            }
+            &EffectSpec::Layer {
+                ref normal,
+                ref displacement,
+                ref albedo,
+                ref metallicity,
+                ref roughness,
+                ..
+            } => patterns.extend(
+                [normal, displacement, albedo, metallicity, roughness]
+                    .iter()
+                    .filter_map(|b| b.as_ref())
+                    .map(|b| b.tex_pattern.clone()),
+            ),
+            &EffectSpec::Export {
+                ref obj_pattern,
+                ref mtl_pattern,
+            } => patterns.extend(obj_pattern.iter().chain(mtl_pattern.iter()).cloned()),
+            &EffectSpec::DumpSurfels { ref obj_pattern } => patterns.push(obj_pattern.clone()),
+        }
+    }
+
+    patterns
+}
+
+/// Directories of all output files, benchmarks and logs of the spec, up to
+/// the first placeholder.
+fn output_dirs(spec: &SimulationSpec) -> Vec<PathBuf> {
+    let mut patterns = effect_patterns(spec);
+
+    if let Some(ref benchmark) = spec.benchmark {
+        patterns.extend(
+            [
+                &benchmark.setup,
+                &benchmark.iterations,
+                &benchmark.tracing,
+                &benchmark.synthesis,
+            ].iter()
+            .filter_map(|p| p.as_ref())
+            .map(|p| p.to_string_lossy().into_owned()),
+        );
+    }
+
+    patterns.extend(spec.log.iter().map(|p| p.to_string_lossy().into_owned()));
+
+    let mut dirs: Vec<PathBuf> = patterns.iter().map(|p| output_dir(p)).collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+/// Directory that a file with the given pattern will be written to, up to
+/// the first placeholder.
+fn output_dir(pattern: &str) -> PathBuf {
+    let fixed = match pattern.find('{') {
+        // Cut off the partial component before the placeholder, too
+        Some(placeholder) => match pattern[..placeholder].rfind(|c: char| c == '/' || c == '\\') {
+            Some(separator) => &pattern[..separator],
+            None => "",
+        },
+        None => Path::new(pattern)
+            .parent()
+            .and_then(|p| p.to_str())
+            .unwrap_or(""),
+    };
+
+    if fixed.is_empty() {
+        PathBuf::from(".")
+    } else {
+        PathBuf::from(fixed)
+    }
+}
+
+fn check_writable_dir(dir: &Path) -> Result<(), String> {
+    let existing = dir
+        .ancestors()
+        .map(|a| if a.as_os_str().is_empty() { Path::new(".") } else { a })
+        .find(|a| a.exists())
+        .unwrap_or(Path::new("."));
+
+    if !existing.is_dir() {
+        return Err(format!("{:?} exists, but is not a directory", existing));
+    }
+
+    match existing.metadata() {
+        Ok(ref metadata) if metadata.permissions().readonly() => {
+            Err(format!("{:?} is not writable", existing))
+        }
+        Ok(_) => Ok(()),
+        Err(err) => Err(format!("{:?} cannot be accessed: {}", existing, err)),
+    }
+}
+
+fn probe_dir(dir: &Path) -> Result<(), String> {
+    create_dir_all(dir).map_err(|e| format!("cannot be created: {}", e))?;
+
+    let probe = dir.join(".aitios-write-probe");
+    let written =
+        File::create(&probe).and_then(|mut f| f.write_all(b"probe\n").and_then(|_| f.sync_all()));
+    let _ = remove_file(&probe);
+    written.map_err(|e| format!("is not writable: {}", e))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::{current_dir, temp_dir};
+    use std::fs::{read_dir, remove_dir_all, set_permissions, write};
+
+    #[test]
+    fn reports_all_corrupt_assets() {
+        let mut resolver = Resolver::new();
+        resolver.add_base(current_dir().unwrap()).unwrap();
+
+        let mut spec = SimulationSpec::default();
+        // Neither of these is what it is supposed to be
+        spec.region_mask = Some(PathBuf::from("tests/examples/rain.yml"));
+        spec.surfels_by_material.insert(
+            String::from("_"),
+            String::from("tests/examples/white_pixel.png"),
+        );
+        spec.surfels_by_material.insert(
+            String::from("bronze"),
+            String::from("tests/examples/iron.yml"),
+        );
+
+        let report = check_assets(&spec, &resolver);
+
+        assert_eq!(3, report.checked);
+        assert_eq!(
+            2,
+            report.problems.len(),
+            "Expected corrupt mask and surfel spec to be reported, but got {:?}",
+            report.problems
+        );
+    }
+
+    #[test]
+    fn lists_all_missing_inputs() {
+        let mut resolver = Resolver::new();
+        resolver.add_base(current_dir().unwrap()).unwrap();
+
+        let mut spec = SimulationSpec::default();
+        spec.scenes = vec![
+            PathBuf::from("tests/assets/sky.obj"),
+            PathBuf::from("tests/assets/missing.obj"),
+        ];
+        spec.sources = vec![PathBuf::from("tests/examples/drizzle.yml")];
+
+        assert_eq!(
+            vec![
+                PathBuf::from("tests/assets/missing.obj"),
+                PathBuf::from("tests/examples/drizzle.yml"),
+            ],
+            missing_inputs(&spec, &resolver)
+        );
+    }
+
+    #[test]
+    fn output_dir_stops_at_placeholder() {
+        assert_eq!(
+            PathBuf::from("out"),
+            output_dir("out/test-{datetime}/iteration-{iteration}/{id}.png")
+        );
+        assert_eq!(PathBuf::from("out/textures"), output_dir("out/textures/a.png"));
+        assert_eq!(PathBuf::from("."), output_dir("{datetime}.png"));
+        assert_eq!(PathBuf::from("."), output_dir("blent.obj"));
+    }
+
+    #[test]
+    fn probes_read_only_output_dir() {
+        let dir = temp_dir().join("aitios-read-only-output-test");
+        create_dir_all(&dir).unwrap();
+        let mut permissions = dir.metadata().unwrap().permissions();
+        permissions.set_readonly(true);
+        set_permissions(&dir, permissions.clone()).unwrap();
+
+        let mut spec = SimulationSpec::default();
+        spec.effects.push(EffectSpec::DumpSurfels {
+            obj_pattern: format!("{}/surfels-{{iteration}}.obj", dir.display()),
+        });
+        let report = probe_output_dirs(&spec);
+        // Privileged users can write read-only directories anyway
+        let privileged = File::create(dir.join("privileged")).is_ok();
+
+        permissions.set_readonly(false);
+        set_permissions(&dir, permissions).unwrap();
+        remove_dir_all(&dir).unwrap();
+
+        if !privileged {
+            assert_eq!(1, report.problems.len());
+            assert_eq!(dir, report.problems[0].path);
+        }
+    }
+
+    #[test]
+    fn probe_file_is_removed() {
+        let dir = temp_dir().join("aitios-probe-output-test/textures");
+        let mut spec = SimulationSpec::default();
+        spec.log = Some(dir.join("sim.log"));
+
+        assert!(probe_output_dirs(&spec).problems.is_empty());
+        assert_eq!(0, read_dir(&dir).unwrap().count());
+        remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn output_below_file_is_reported() {
+        assert!(check_writable_dir(Path::new("test-output/not/yet/created")).is_ok());
+        assert!(check_writable_dir(Path::new("Cargo.toml/textures")).is_err());
+    }
+
+    #[test]
+    fn existing_outputs_expand_known_placeholders() {
+        let dir = temp_dir().join("aitios-existing-outputs-test");
+        let _ = remove_dir_all(&dir);
+        let runs = ["sky-now/iteration-1", "sky-now/iteration-2", "sky-earlier/iteration-1"];
+        for run in runs.iter() {
+            create_dir_all(dir.join(run)).unwrap();
+            write(dir.join(run).join("surfels.obj"), "o surfels").unwrap();
+        }
+        create_dir_all(dir.join("sky-now/iteration-3")).unwrap();
+
+        let mut spec = SimulationSpec::default();
+        spec.effects.push(EffectSpec::DumpSurfels {
+            obj_pattern: dir
+                .join("{spec_name}-{datetime}/iteration-{iteration}/surfels.obj")
+                .to_string_lossy()
+                .into_owned(),
+        });
+
+        assert_eq!(
+            vec![
+                dir.join("sky-now/iteration-1/surfels.obj"),
+                dir.join("sky-now/iteration-2/surfels.obj"),
+            ],
+            existing_outputs(&spec, "now", "sky")
+        );
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn placeholders_match_any_text() {
+        assert!(matches_placeholders("rust-{iteration}.png", "rust-12.png"));
+        assert!(matches_placeholders("{substance}-{iteration}.png", "rust-1.png"));
+        assert!(matches_placeholders("{id}.obj", ".obj"));
+        assert!(!matches_placeholders("rust-{iteration}.png", "rust-1.exr"));
+        assert!(!matches_placeholders("rust-{iteration}-{id}.png", "rust-1.png"));
+        assert!(!matches_placeholders("surfels.obj", "surfels.obj.bak"));
+    }
+}
@@ -1,13 +1,30 @@
 use asset::err::AssetError;
+use builder::suggest::did_you_mean;
 use files::ResolveError;
 use serde_yaml::Error as SerdeYamlError;
 use std::fmt;
 use std::io;
+use std::path::{Path, PathBuf};
+use toml::de::Error as TomlError;
 
 #[derive(Fail, Debug)]
 pub enum Error {
-    #[fail(display = "Simulation spec failed to parse.")]
-    Parse(#[cause] SerdeYamlError),
+    #[fail(display = "Simulation spec failed to parse: {}", message)]
+    Parse {
+        /// Message of the cause with its location, along with the spec file
+        /// and a suggestion for misspelled names, if available.
+        message: String,
+        #[cause]
+        cause: SerdeYamlError,
+    },
+    #[fail(display = "Simulation spec failed to parse: {}", message)]
+    TomlParse {
+        /// Message of the cause with its location, along with the spec file
+        /// and a suggestion for misspelled names, if available.
+        message: String,
+        #[cause]
+        cause: TomlError,
+    },
     #[fail(display = "{} could not be resolved.", kind)]
     Resolve {
         #[cause]
@@ -36,12 +53,84 @@ pub enum Error {
     SubstancesMissing,
     #[fail(display = "Surfel distance has been set to {:?}", _0)]
     InvalidSurfelDistance(Option<f32>),
+    #[fail(display = "Output bit depth has been set to {}, expected 8 or 16", _0)]
+    InvalidBitDepth(u8),
+    #[fail(
+        display = "Objects {:?} were selected but do not exist in the scene. Available objects: {:?}",
+        unknown,
+        available
+    )]
+    UnknownObjects {
+        unknown: Vec<String>,
+        available: Vec<String>,
+    },
+    #[fail(
+        display = "Objects {:?} were selected, but none of them has a material with a surfel spec, nothing to simulate.",
+        _0
+    )]
+    NothingSelected(Vec<String>),
+    #[fail(
+        display = "Substances {:?} were selected but are not defined in the spec. Available substances: {:?}",
+        unknown,
+        available
+    )]
+    UnknownSubstances {
+        unknown: Vec<String>,
+        available: Vec<String>,
+    },
+    #[fail(
+        display = "Substances {:?} are never emitted by a source, present on a surface initially or produced by a rule. They will stay zero throughout the simulation.",
+        _0
+    )]
+    UnusedSubstances(Vec<String>),
+    #[fail(display = "Region mask {:?} could not be loaded: {}", path, reason)]
+    RegionMask { path: PathBuf, reason: String },
+    #[fail(
+        display = "Simulation spec was written for spec version {}, but this build of aitios supports version {}. Upgrade the spec or use a matching version of aitios.",
+        declared,
+        supported
+    )]
+    SpecVersionMismatch { declared: u32, supported: u32 },
+    #[fail(
+        display = "Simulation spec references input files that do not exist or cannot be read: {:?}",
+        _0
+    )]
+    InputsMissing(Vec<PathBuf>),
+    #[fail(
+        display = "Simulation spec references environment variable {} which is not set.",
+        _0
+    )]
+    UnsetVariable(String),
+    #[fail(display = "Simulation spec includes itself: {:?}", _0)]
+    IncludeCycle(Vec<PathBuf>),
+    #[fail(display = "Spec field {} could not be set: {}", path, problem)]
+    Override { path: String, problem: String },
 }
 
 impl Error {
+    /// Parse error of the spec in the given file, or of a spec from memory
+    /// or stdin if `None`.
+    pub fn parse(cause: SerdeYamlError, file: Option<&Path>) -> Self {
+        let message = parse_message(&cause, file);
+        Error::Parse { message, cause }
+    }
+
+    /// Like `parse`, but for specs in TOML.
+    pub fn toml_parse(cause: TomlError, file: Option<&Path>) -> Self {
+        let message = parse_message(&cause, file);
+        Error::TomlParse { message, cause }
+    }
+
     pub fn resolve(cause: ResolveError, kind: ResolveErrorKind) -> Self {
         Error::Resolve { cause, kind }
     }
+
+    pub fn spec_override<P: ToString>(path: &str, problem: P) -> Self {
+        Error::Override {
+            path: String::from(path),
+            problem: problem.to_string(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -55,6 +144,8 @@ pub enum ResolveErrorKind {
     Scene,
     Layer,
     Benchmark,
+    RegionMask,
+    Include,
 }
 
 impl fmt::Display for ResolveErrorKind {
@@ -71,14 +162,27 @@ impl fmt::Display for ResolveErrorKind {
                 &ResolveErrorKind::Scene => "Scene to simulate",
                 &ResolveErrorKind::Layer => "Texture sample referenced by layer effect",
                 &ResolveErrorKind::Benchmark => "Benchmarking CSV",
+                &ResolveErrorKind::RegionMask => "Region mask",
+                &ResolveErrorKind::Include => "Included simulation specification",
             }
         )
     }
 }
 
+fn parse_message<E: fmt::Display>(cause: &E, file: Option<&Path>) -> String {
+    let mut message = format!("{}", cause);
+    if let Some(name) = did_you_mean(&message) {
+        message = format!("{} (did you mean `{}`?)", message, name);
+    }
+    if let Some(file) = file {
+        message = format!("{:?}: {}", file, message);
+    }
+    message
+}
+
 impl From<SerdeYamlError> for Error {
     fn from(error: SerdeYamlError) -> Self {
-        Error::Parse(error)
+        Error::parse(error, None)
     }
 }
 
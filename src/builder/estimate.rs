@@ -0,0 +1,210 @@
+use builder::instantiate::{
+    limit_triangles, load_entities, load_source_specs, surfel_distance_for_density,
+    surfel_specs_by_material_name, unique_substance_names,
+};
+use builder::{Error, LoadOptions};
+use files::Resolver;
+use geom::TupleTriangle;
+use runner::{Bounds, SURFEL_BYTES};
+use scene::{Entity, Mesh};
+use spec::{Blend, EffectSpec, SimulationSpec, SurfelLookup};
+use std::collections::HashSet;
+
+/// Bounding box and surface area of the entities that would be simulated
+/// with the given spec, as printed by `--print-bounds`, computed from the
+/// loaded scenes without generating surfels.
+///
+/// The surfel count is estimated from the surface area and the surfel
+/// distance, like for `estimate_peak_memory`.
+pub fn estimate_bounds(
+    spec: &SimulationSpec,
+    resolver: &Resolver,
+    options: &LoadOptions,
+) -> Result<Bounds, Error> {
+    let surfel_specs = surfel_specs_by_material_name(spec, resolver)?;
+    let (mut entities, _) = load_entities(&spec.scenes, &surfel_specs, options)?;
+    if let Some(limit) = options.triangle_limit {
+        entities = limit_triangles(entities, limit);
+    }
+
+    let bounds = entity_bounds(&entities);
+    let surfel_distance = surfel_distance(spec, options);
+    let surfel_count = estimate_surfel_count(&bounds, surfel_distance);
+    Ok(bounds.estimated_surfels(surfel_distance, surfel_count as usize))
+}
+
+/// Estimates the peak memory of a simulation for the given spec from its
+/// scenes and effects, without generating surfels or allocating textures,
+/// so that a simulation that would not fit can be rejected before building
+/// it.
+///
+/// The surfel count is derived from the surface area of the simulated
+/// entities and the surfel distance. Layer textures without an explicit
+/// size take the size of the material textures, which are not opened for
+/// the estimate and hence left out.
+pub fn estimate_peak_memory(
+    spec: &SimulationSpec,
+    resolver: &Resolver,
+    options: &LoadOptions,
+) -> Result<u64, Error> {
+    let surfel_specs = surfel_specs_by_material_name(spec, resolver)?;
+    let (mut entities, _) = load_entities(&spec.scenes, &surfel_specs, options)?;
+    if let Some(limit) = options.triangle_limit {
+        entities = limit_triangles(entities, limit);
+    }
+    let source_specs = load_source_specs(&spec.sources, resolver)?;
+    let substance_count = unique_substance_names(&surfel_specs, &source_specs).len() as u64;
+
+    let bounds = entity_bounds(&entities);
+    let surfel_count = estimate_surfel_count(&bounds, surfel_distance(spec, options));
+
+    // Surfel tables are cached for the whole run, one per distinct
+    // combination of entity, size and lookup
+    let mut surfel_tables = HashSet::new();
+    let mut largest_texture_bytes = 0;
+    let channel_bytes = u64::from(spec.output_bit_depth.unwrap_or(8) / 8);
+
+    for effect in spec.effects.iter() {
+        match effect {
+            &EffectSpec::Density {
+                width,
+                height,
+                surfel_lookup,
+                ..
+            } => {
+                let (width, height) = options.texture_resolution.unwrap_or((width, height));
+                let texture_bytes = 4 * channel_bytes * (width * height) as u64;
+                largest_texture_bytes = largest_texture_bytes.max(texture_bytes);
+                for entity_idx in 0..entities.len() {
+                    surfel_tables.insert((entity_idx, width, height, lookup_count(surfel_lookup)));
+                }
+            }
+            &EffectSpec::Layer {
+                ref materials,
+                surfel_lookup,
+                ref normal,
+                ref displacement,
+                ref albedo,
+                ref metallicity,
+                ref roughness,
+                ..
+            } => {
+                let blends = [normal, displacement, albedo, metallicity, roughness];
+                for blend in blends.iter().filter_map(|b| b.as_ref()) {
+                    let (width, height) = match options.texture_resolution {
+                        Some(resolution) => resolution,
+                        None => match blend_size(blend) {
+                            Some(size) => size,
+                            None => continue,
+                        },
+                    };
+                    // Original, stops and result are in memory at once
+                    let texture_bytes = 4 * (width * height) as u64;
+                    largest_texture_bytes = largest_texture_bytes
+                        .max(texture_bytes * (blend.stops.len() as u64 + 2));
+
+                    let applicable = entities.iter().enumerate().filter(|&(_, e)| {
+                        materials.is_empty()
+                            || materials.iter().any(|m| m == "_" || m == e.material.name())
+                    });
+                    for (entity_idx, _) in applicable {
+                        surfel_tables.insert((
+                            entity_idx,
+                            width,
+                            height,
+                            lookup_count(surfel_lookup),
+                        ));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let surfel_bytes = surfel_count * (SURFEL_BYTES + 8 * substance_count);
+    // Each texel stores distance and index for each of its nearest surfels
+    let table_bytes: u64 = surfel_tables
+        .iter()
+        .map(|&(_, width, height, count)| (width * height) as u64 * (24 + 16 * count as u64))
+        .sum();
+
+    Ok(surfel_bytes + table_bytes + largest_texture_bytes)
+}
+
+fn entity_bounds(entities: &[Entity]) -> Bounds {
+    let mut bounds = Bounds::new();
+    for TupleTriangle(v0, v1, v2) in entities.iter().flat_map(|e| e.mesh.triangles()) {
+        bounds.add_triangle(
+            [v0.position.x, v0.position.y, v0.position.z],
+            [v1.position.x, v1.position.y, v1.position.z],
+            [v2.position.x, v2.position.y, v2.position.z],
+        );
+    }
+    bounds
+}
+
+/// Surfel distance of the spec, or the one for the surfel density given in
+/// the options instead.
+fn surfel_distance(spec: &SimulationSpec, options: &LoadOptions) -> Option<f32> {
+    options
+        .surfel_density
+        .map(surfel_distance_for_density)
+        .or(spec.surfel_distance)
+}
+
+fn estimate_surfel_count(bounds: &Bounds, surfel_distance: Option<f32>) -> u64 {
+    // Each surfel covers a square with the surfel distance as side length
+    match surfel_distance {
+        Some(distance) if distance > 0.0 => (bounds.area() / (distance * distance)).ceil() as u64,
+        _ => 0,
+    }
+}
+
+/// Explicit output size of a blend, if any.
+fn blend_size(blend: &Blend) -> Option<(usize, usize)> {
+    match (blend.width, blend.height) {
+        (Some(w), Some(h)) => Some((w, h)),
+        (Some(w), None) => Some((w, w)),
+        (None, Some(h)) => Some((h, h)),
+        (None, None) => None,
+    }
+}
+
+fn lookup_count(surfel_lookup: SurfelLookup) -> usize {
+    match surfel_lookup {
+        SurfelLookup::Nearest { count } => count,
+        SurfelLookup::Within { .. } => 0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use builder::SimulationBuilder;
+
+    #[test]
+    fn denser_surfels_need_more_memory() {
+        let builder = SimulationBuilder::new()
+            .append_spec_fragment_file("tests/examples/sky.yml")
+            .unwrap();
+        let sparse = builder.estimate_peak_memory().unwrap();
+        let dense = builder.surfel_density(4.0).estimate_peak_memory().unwrap();
+
+        assert!(sparse > 0);
+        assert!(dense > sparse, "Expected {} > {}", dense, sparse);
+    }
+
+    #[test]
+    fn bounds_match_built_simulation() {
+        let builder = SimulationBuilder::new()
+            .append_spec_fragment_file("tests/examples/sky.yml")
+            .unwrap();
+        let estimated = builder.bounds().unwrap();
+        let built = builder.build().unwrap().bounds();
+
+        assert_eq!(built.triangle_count(), estimated.triangle_count());
+        assert_eq!(built.min(), estimated.min());
+        assert_eq!(built.max(), estimated.max());
+        assert!(format!("{}", estimated).contains("Surfels (estimate): "));
+    }
+}
@@ -0,0 +1,71 @@
+use std::path::Path;
+
+/// Language a simulation spec fragment is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecFormat {
+    Yaml,
+    Toml,
+}
+
+impl SpecFormat {
+    /// Format of a spec file by its extension, ignoring a trailing `.gz`.
+    /// Files ending in `.toml` are TOML, all others YAML.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let path = if path.extension().map(|ext| ext == "gz").unwrap_or(false) {
+            Path::new(path.file_stem().unwrap_or_default())
+        } else {
+            path
+        };
+
+        match path.extension() {
+            Some(ext) if ext == "toml" => SpecFormat::Toml,
+            _ => SpecFormat::Yaml,
+        }
+    }
+
+    /// Guesses the format of a spec without a file name, e.g. from stdin.
+    ///
+    /// TOML is assumed if the first line that is neither empty nor a comment
+    /// starts a table, e.g. `[benchmark]`, or assigns with `=` before any
+    /// `:`, e.g. `name = "Rust"`. A top-level YAML spec is always a mapping,
+    /// so neither can start a valid YAML spec.
+    pub fn sniff(spec: &str) -> Self {
+        let first = spec
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'));
+
+        match first {
+            Some(line) if line.starts_with('[') => SpecFormat::Toml,
+            Some(line) => match (line.find('='), line.find(':')) {
+                (Some(_), None) => SpecFormat::Toml,
+                (Some(assign), Some(colon)) if assign < colon => SpecFormat::Toml,
+                _ => SpecFormat::Yaml,
+            },
+            None => SpecFormat::Yaml,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_by_extension() {
+        assert_eq!(SpecFormat::Toml, SpecFormat::from_path("specs/sim.toml"));
+        assert_eq!(SpecFormat::Toml, SpecFormat::from_path("specs/sim.toml.gz"));
+        assert_eq!(SpecFormat::Yaml, SpecFormat::from_path("specs/sim.yml"));
+        assert_eq!(SpecFormat::Yaml, SpecFormat::from_path("specs/sim.yaml.gz"));
+        assert_eq!(SpecFormat::Yaml, SpecFormat::from_path("specs/sim"));
+    }
+
+    #[test]
+    fn sniffed_format() {
+        assert_eq!(SpecFormat::Toml, SpecFormat::sniff("# Rust\nname = \"Rust: fast\"\n"));
+        assert_eq!(SpecFormat::Toml, SpecFormat::sniff("\n[benchmark]\ntracing = \"t.csv\""));
+        assert_eq!(SpecFormat::Yaml, SpecFormat::sniff("name: a = b\n"));
+        assert_eq!(SpecFormat::Yaml, SpecFormat::sniff("# Empty"));
+    }
+}
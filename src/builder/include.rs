@@ -0,0 +1,200 @@
+use builder::{interpolate_env, Error, ResolveErrorKind, SpecFormat};
+use files::{read_to_string_gunzipped, Resolver};
+use serde_yaml::{self, Value};
+use spec::SimulationSpec;
+use std::path::{Path, PathBuf};
+use toml;
+
+/// Parses a spec fragment from YAML or TOML, merges the files listed in its
+/// `include` key into it and expands environment variables in its string
+/// fields, e.g. `${OUT_DIR}/weathered.png`.
+///
+/// Includes are looked up with the given resolver, and includes of included
+/// files relative to the file that includes them. Keys of the including
+/// fragment take precedence over included ones, and later includes over
+/// earlier ones. `file` is the resolved path of the fragment, if it was read
+/// from a file, and is used to report errors and cycles. The format of
+/// included files is chosen by their extension, see `SpecFormat::from_path`.
+///
+/// Returns the spec along with the directories of all included files, which
+/// relative paths in the spec can be resolved against.
+pub fn parse_spec(
+    text: &str,
+    format: SpecFormat,
+    file: Option<&Path>,
+    includes: &Resolver,
+) -> Result<(SimulationSpec, Vec<PathBuf>), Error> {
+    let mut stack: Vec<PathBuf> = file.into_iter().map(Path::to_path_buf).collect();
+    let mut dirs = Vec::new();
+
+    let value = parse_value(text, format, file, includes, &mut stack, &mut dirs)?;
+    let spec = serde_yaml::from_value(value).map_err(|e| Error::parse(e, file))?;
+
+    Ok((spec, dirs))
+}
+
+fn parse_value(
+    text: &str,
+    format: SpecFormat,
+    file: Option<&Path>,
+    includes: &Resolver,
+    stack: &mut Vec<PathBuf>,
+    dirs: &mut Vec<PathBuf>,
+) -> Result<Value, Error> {
+    // The text is deserialized directly first, so that errors in the schema
+    // are reported with their line and column
+    let mut value: Value = match format {
+        SpecFormat::Yaml => {
+            let _: SimulationSpec =
+                serde_yaml::from_str(text).map_err(|e| Error::parse(e, file))?;
+            serde_yaml::from_str(text).map_err(|e| Error::parse(e, file))?
+        }
+        SpecFormat::Toml => {
+            let _: SimulationSpec =
+                toml::from_str(text).map_err(|e| Error::toml_parse(e, file))?;
+            toml::from_str(text).map_err(|e| Error::toml_parse(e, file))?
+        }
+    };
+    interpolate_env(&mut value).map_err(Error::UnsetVariable)?;
+
+    let include_paths = take_includes(&mut value).map_err(|e| Error::parse(e, file))?;
+    let mut merged = Value::Null;
+    for include_path in include_paths {
+        let path = includes
+            .resolve(&include_path)
+            .map_err(|e| Error::resolve(e, ResolveErrorKind::Include))?;
+
+        if let Some(start) = stack.iter().position(|p| *p == path) {
+            let mut cycle = stack[start..].to_vec();
+            cycle.push(path);
+            return Err(Error::IncludeCycle(cycle));
+        }
+
+        let included_text = read_to_string_gunzipped(&path)?;
+
+        // Resolved paths are canonical and always have a parent
+        let dir = path.parent().unwrap().to_path_buf();
+        let mut included_includes = Resolver::new();
+        included_includes
+            .add_base(&dir)
+            .map_err(|e| Error::resolve(e, ResolveErrorKind::Include))?;
+
+        stack.push(path.clone());
+        let included = parse_value(
+            &included_text,
+            SpecFormat::from_path(&path),
+            Some(&path),
+            &included_includes,
+            stack,
+            dirs,
+        )?;
+        stack.pop();
+
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+        merge(&mut merged, included);
+    }
+    merge(&mut merged, value);
+
+    Ok(merged)
+}
+
+/// Removes the top-level `include` key from the spec and returns the listed
+/// paths.
+fn take_includes(spec: &mut Value) -> Result<Vec<PathBuf>, serde_yaml::Error> {
+    match *spec {
+        Value::Mapping(ref mut mapping) => {
+            match mapping.remove(&Value::String(String::from("include"))) {
+                Some(includes) => serde_yaml::from_value(includes),
+                None => Ok(Vec::new()),
+            }
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Merges the overlay into the base, with fields of the overlay replacing
+/// those of the base, except for mappings, which are merged recursively.
+fn merge(base: &mut Value, overlay: Value) {
+    if overlay.is_null() {
+        return;
+    }
+
+    match (base, overlay) {
+        (&mut Value::Mapping(ref mut base), Value::Mapping(ref overlay)) => {
+            for (key, value) in overlay.iter() {
+                if base.contains_key(key) {
+                    merge(base.get_mut(key).unwrap(), value.clone());
+                } else {
+                    base.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+
+    fn spec_dir(name: &str, files: &[(&str, &str)]) -> (PathBuf, Resolver) {
+        let dir = temp_dir().join(name);
+        if dir.exists() {
+            remove_dir_all(&dir).unwrap();
+        }
+        create_dir_all(&dir).unwrap();
+        for &(file, yaml) in files {
+            write(dir.join(file), yaml).unwrap();
+        }
+
+        let mut resolver = Resolver::new();
+        resolver.add_base(&dir).unwrap();
+        (resolver.resolve("main.yml").unwrap(), resolver)
+    }
+
+    #[test]
+    fn including_file_takes_precedence() {
+        let (main, resolver) = spec_dir(
+            "aitios-include-merge-test",
+            &[
+                ("base.yml", "name: Base\niterations: 3\nbenchmark:\n  iterations: it.csv\n"),
+                ("main.yml", "include: [base.yml]\nname: Main\nbenchmark:\n  tracing: tr.csv\n"),
+            ],
+        );
+
+        let yaml = read_to_string(&main).unwrap();
+        let (spec, dirs) = parse_spec(&yaml, SpecFormat::Yaml, Some(&main), &resolver).unwrap();
+
+        assert_eq!("Main", spec.name);
+        assert_eq!(Some(3), spec.iterations);
+        let benchmark = spec.benchmark.unwrap();
+        assert_eq!(Some(PathBuf::from("it.csv")), benchmark.iterations);
+        assert_eq!(Some(PathBuf::from("tr.csv")), benchmark.tracing);
+        assert_eq!(vec![main.parent().unwrap().to_path_buf()], dirs);
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let (main, resolver) = spec_dir(
+            "aitios-include-cycle-test",
+            &[
+                ("main.yml", "include: [other.yml]\nname: Main\n"),
+                ("other.yml", "include: [main.yml]\n"),
+            ],
+        );
+
+        let yaml = read_to_string(&main).unwrap();
+        match parse_spec(&yaml, SpecFormat::Yaml, Some(&main), &resolver) {
+            Err(Error::IncludeCycle(cycle)) => {
+                let names: Vec<_> = cycle.iter().map(|p| p.file_name().unwrap()).collect();
+                assert_eq!(vec!["main.yml", "other.yml", "main.yml"], names);
+            }
+            Err(err) => panic!("Expected include cycle, but got {}", err),
+            Ok(_) => panic!("Expected include cycle to be an error"),
+        }
+    }
+}
@@ -1,14 +1,18 @@
 use asset::obj;
-use builder::{Error, ResolveErrorKind};
+use builder::{Error, LoadOptions, RegionMask, ResolveErrorKind};
 use chrono::*;
 use files::{create_file_recursively, fs_timestamp, Resolver};
 use geom::{TupleTriangle, Vec3, Vertex};
-use runner::SimulationRunner;
+use runner::{PhaseTimer, Profile, SimulationRunner};
 use scene::DeinterleavedIndexedMeshBuf;
 use scene::{Entity, Mesh};
 use serde_yaml;
 use sim::{Config, Simulation, SurfelData, SurfelRule, TonSource, TonSourceBuilder, Transport};
-use spec::{BenchSpec, SimulationSpec, SurfelRuleSpec, SurfelSpec, TonSourceSpec, Transport::*};
+use spec::{
+    BenchSpec, EffectSpec, SimulationSpec, SurfelRuleSpec, SurfelSpec, TonSourceSpec, Transport::*,
+    SPEC_VERSION,
+};
+use std::cell::RefCell;
 use std::cmp::Eq;
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
@@ -27,17 +31,69 @@ use surf::{Surface, SurfaceBuilder, Surfel, SurfelSampling};
 /// TODO this resolving business needs to be removed, since canonicalize
 ///      is now responsible for this.
 pub fn instantiate(
-    spec: SimulationSpec,
+    mut spec: SimulationSpec,
     resolver: &Resolver,
     creation_time: DateTime<Local>,
+    spec_name: &str,
+    options: &LoadOptions,
 ) -> Result<SimulationRunner, Error> {
     let load_start_time = SystemTime::now();
+    let profile = if options.profile {
+        Some(Rc::new(RefCell::new(Profile::new())))
+    } else {
+        None
+    };
+
+    check_spec_version(spec.version, options.strict)?;
+    override_spec(&mut spec, options);
 
     let surfel_specs_by_material_name = surfel_specs_by_material_name(&spec, &resolver)?;
 
-    let entities = load_entities(&spec.scenes, &surfel_specs_by_material_name)?;
+    let (mut entities, occluders) = {
+        let _timer = PhaseTimer::start(&profile, "mesh loading");
+        load_entities(&spec.scenes, &surfel_specs_by_material_name, options)?
+    };
 
-    let source_specs = load_source_specs(&spec.sources, &resolver)?;
+    if let Some(limit) = options.triangle_limit {
+        let total: usize = entities.iter().map(|e| e.mesh.triangles().count()).sum();
+        if total > limit {
+            info!(
+                "Previewing with the first {} of {} triangles, as overridden, results will be inaccurate.",
+                limit, total
+            );
+            entities = limit_triangles(entities, limit);
+        }
+    }
+
+    let mut source_specs = load_source_specs(&spec.sources, &resolver)?;
+
+    if options.first_hit_only {
+        for source in source_specs.iter_mut() {
+            if source.p_straight != 0.0 || source.p_parabolic != 0.0 || source.p_flow != 0.0 {
+                info!(
+                    "Source emitting from {:?} bounces with p_straight={}, p_parabolic={}, p_flow={}, overriding with first hits only.",
+                    source.mesh, source.p_straight, source.p_parabolic, source.p_flow
+                );
+            }
+            source.p_straight = 0.0;
+            source.p_parabolic = 0.0;
+            source.p_flow = 0.0;
+        }
+    }
+
+    if let Some(direction) = options.flow_direction {
+        override_flow_direction(&mut source_specs, direction);
+    }
+
+    if let Some(emission_count) = options.emission_count {
+        for source in source_specs.iter_mut() {
+            info!(
+                "Source emitting from {:?} emits {} gammatons per iteration instead of {}, as overridden.",
+                source.mesh, emission_count, source.emission_count
+            );
+            source.emission_count = emission_count;
+        }
+    }
 
     let unique_substance_names =
         unique_substance_names(&surfel_specs_by_material_name, &source_specs);
@@ -46,6 +102,36 @@ pub fn instantiate(
         return Err(Error::SubstancesMissing);
     }
 
+    let unknown: Vec<String> = options
+        .substances
+        .iter()
+        .filter(|s| !unique_substance_names.contains(s))
+        .cloned()
+        .collect();
+    if !unknown.is_empty() {
+        return Err(Error::UnknownSubstances {
+            unknown,
+            available: unique_substance_names,
+        });
+    }
+
+    if options.warn_unused_substances || options.strict {
+        let unused = unused_substance_names(
+            &unique_substance_names,
+            &surfel_specs_by_material_name,
+            &source_specs,
+            &spec.rules,
+        );
+        if !unused.is_empty() {
+            let unused = Error::UnusedSubstances(unused);
+            if options.strict {
+                return Err(unused);
+            } else {
+                warn!("{}", unused);
+            }
+        }
+    }
+
     if spec.effects.is_empty() {
         return Err(Error::EffectsMissing);
     }
@@ -57,14 +143,41 @@ pub fn instantiate(
     if surfel_distance.is_none() || surfel_distance.unwrap() <= 0.0 {
         return Err(Error::InvalidSurfelDistance(surfel_distance));
     }
-    let surface = build_surface(
-        &entities,
-        &surfel_specs_by_material_name,
-        &unique_substance_names,
-        surfel_distance.unwrap(),
-    );
+    match spec.output_bit_depth {
+        Some(8) | Some(16) | None => (),
+        Some(bits) => return Err(Error::InvalidBitDepth(bits)),
+    }
+    if spec
+        .effects
+        .iter()
+        .any(|e| matches!(e, &EffectSpec::Density { .. }))
+    {
+        info!(
+            "Writing density textures in PNG format with {} bits per channel.",
+            spec.output_bit_depth.unwrap_or(8)
+        );
+    }
+    let region_mask = match spec.region_mask {
+        Some(ref mask_path) => {
+            info!("Restricting weathering to region mask {:?}", mask_path);
+            Some(RegionMask::open(mask_path)?)
+        }
+        None => None,
+    };
+
+    let surface = {
+        let _timer = PhaseTimer::start(&profile, "surfel generation");
+        build_surface(
+            &entities,
+            &surfel_specs_by_material_name,
+            &unique_substance_names,
+            surfel_distance.unwrap(),
+            region_mask.as_ref(),
+        )
+    };
 
     let simulation = {
+        let _timer = PhaseTimer::start(&profile, "simulation setup");
         let has_fallback_surfel_spec = surfel_specs_by_material_name.contains_key("_");
 
         // Ignoring geometry where the corresponding material has no surfel specification
@@ -75,7 +188,8 @@ pub fn instantiate(
                 has_fallback_surfel_spec
                     || surfel_specs_by_material_name.contains_key(e.material.name())
             })
-            .flat_map(|e| e.mesh.triangles());
+            .flat_map(|e| e.mesh.triangles())
+            .chain(occluders.iter().flat_map(|e| e.mesh.triangles()));
 
         let transport = match spec.transport {
             Some(Classic) => Transport::classic(),
@@ -97,13 +211,24 @@ pub fn instantiate(
     };
 
     let datetime = fs_timestamp(creation_time);
-    let runner = SimulationRunner::new(
+    let mut runner = SimulationRunner::new(
         spec,
         unique_substance_names,
         simulation,
         entities,
         &datetime,
+        spec_name,
+        profile,
     );
+    runner.set_skip_effects(options.skip_outputs);
+    runner.set_source_specs(source_specs);
+    if !options.substances.is_empty() {
+        info!("Baking density textures only for substances {:?}.", options.substances);
+        runner.set_baked_substances(options.substances.clone());
+    }
+    if let Some(filter) = options.bake_filter {
+        runner.set_bake_filter(filter, options.bake_radius);
+    }
 
     if let Some(BenchSpec {
         setup: Some(ref setup_csv),
@@ -115,7 +240,11 @@ pub fn instantiate(
         let nanos = elapsed.subsec_nanos();
 
         let mut setup_csv = create_file_recursively(
-            setup_csv.to_str().unwrap().replace("{datetime}", &datetime),
+            setup_csv
+                .to_str()
+                .unwrap()
+                .replace("{datetime}", &datetime)
+                .replace("{spec_name}", spec_name),
         ).expect("Could not write to benchmark sink.");
 
         writeln!(setup_csv, "{}.{:09}", secs, nanos).expect("Could not write to benchmark sink.");
@@ -124,14 +253,52 @@ pub fn instantiate(
     Ok(runner)
 }
 
-fn load_entities(
+/// Applies the options that replace values of the simulation spec, i.e.
+/// iterations, surfel density, texture resolution and skipping outputs.
+pub fn override_spec(spec: &mut SimulationSpec, options: &LoadOptions) {
+    if let Some(iterations) = options.iterations {
+        if spec.iterations != Some(iterations) {
+            info!(
+                "Running {} iterations instead of {} from the spec, as overridden.",
+                iterations,
+                spec.iterations.unwrap_or(1)
+            );
+        }
+        spec.iterations = Some(iterations);
+    }
+
+    if options.skip_outputs {
+        // Benchmark files are created when building the runner
+        spec.benchmark = None;
+    }
+
+    if let Some(density) = options.surfel_density {
+        let distance = surfel_distance_for_density(density);
+        info!(
+            "Generating surfels with a density of {} per square unit, i.e. a surfel distance of {}, instead of a surfel distance of {:?} from the spec, as overridden.",
+            density, distance, spec.surfel_distance
+        );
+        spec.surfel_distance = Some(distance);
+    }
+
+    if let Some(resolution) = options.texture_resolution {
+        override_texture_resolution(&mut spec.effects, resolution);
+    }
+}
+
+/// Loads the entities to simulate and, if requested in the options,
+/// entities that only act as occluders.
+pub fn load_entities(
     paths: &Vec<PathBuf>,
     surfel_specs_by_material_name: &HashMap<String, SurfelSpec>,
-) -> Result<Vec<Entity>, Error> {
+    options: &LoadOptions,
+) -> Result<(Vec<Entity>, Vec<Entity>), Error> {
     let mut all_entities = Vec::new();
+    let mut all_names = Vec::new();
 
     for scene_path in paths.iter() {
         let mut entities = obj::load(&scene_path)?;
+        all_names.extend(entities.iter().map(|e| e.name.clone()));
 
         // Throw out all entitites which have no mapped surfel spec,
         // unless there is a fallback material named "_".
@@ -148,13 +315,116 @@ fn load_entities(
         all_entities.extend(entities);
     }
 
-    Ok(all_entities)
+    if options.objects.is_empty() {
+        return Ok((all_entities, Vec::new()));
+    }
+
+    let unknown: Vec<String> = options
+        .objects
+        .iter()
+        .filter(|o| !all_names.contains(o))
+        .cloned()
+        .collect();
+    if !unknown.is_empty() {
+        all_names.sort();
+        all_names.dedup();
+        return Err(Error::UnknownObjects {
+            unknown,
+            available: all_names,
+        });
+    }
+
+    let (selected, unselected): (Vec<Entity>, Vec<Entity>) = all_entities
+        .into_iter()
+        .partition(|e| options.objects.contains(&e.name));
+
+    // Selected entities may all have been thrown out for lack of a surfel spec
+    if selected.is_empty() {
+        return Err(Error::NothingSelected(options.objects.clone()));
+    }
+
+    info!(
+        "Simulating {} selected entities, {} unselected entities {}.",
+        selected.len(),
+        unselected.len(),
+        if options.unselected_as_occluders {
+            "remain as occluders"
+        } else {
+            "are ignored"
+        }
+    );
+
+    if options.unselected_as_occluders {
+        Ok((selected, unselected))
+    } else {
+        Ok((selected, Vec::new()))
+    }
+}
+
+/// Keeps only the first `limit` triangles of the given entities, in order,
+/// dropping entities without any triangles left.
+pub fn limit_triangles(entities: Vec<Entity>, limit: usize) -> Vec<Entity> {
+    let mut remaining = limit;
+    entities
+        .into_iter()
+        .filter_map(|mut entity| {
+            let count = entity.mesh.triangles().count();
+            if remaining == 0 {
+                return None;
+            } else if count > remaining {
+                entity.mesh = Rc::new(
+                    entity
+                        .mesh
+                        .triangles()
+                        .take(remaining)
+                        .flat_map(|TupleTriangle(v0, v1, v2)| vec![v0, v1, v2].into_iter())
+                        .collect::<DeinterleavedIndexedMeshBuf>(),
+                );
+                remaining = 0;
+            } else {
+                remaining -= count;
+            }
+            Some(entity)
+        })
+        .collect()
+}
+
+/// Warns if the declared spec version differs from the supported one, or
+/// fails in strict mode. Specs without a version are assumed to be written
+/// for version 1, the oldest supported version, which is only logged as info.
+fn check_spec_version(declared: Option<u32>, strict: bool) -> Result<(), Error> {
+    let declared = match declared {
+        Some(declared) => declared,
+        None => {
+            info!(
+                "Simulation spec does not declare a version, assuming version 1. Add \"version: {}\" to the spec to silence this message.",
+                SPEC_VERSION
+            );
+            1
+        }
+    };
+
+    if declared == SPEC_VERSION {
+        Ok(())
+    } else {
+        let mismatch = Error::SpecVersionMismatch {
+            declared,
+            supported: SPEC_VERSION,
+        };
+
+        if strict {
+            Err(mismatch)
+        } else {
+            warn!("{}", mismatch);
+            Ok(())
+        }
+    }
 }
 
 /// For faster substance access, each substance name gets an ID which is an
 /// index into the returned vector. Names can occur in sources, and surfels
 /// as initial values and as absorption/deposition rates
-fn unique_substance_names(
+pub fn unique_substance_names(
     surfel_specs: &HashMap<String, SurfelSpec>,
     source_specs: &Vec<TonSourceSpec>,
 ) -> Vec<String> {
@@ -171,7 +441,54 @@ fn unique_substance_names(
     unique_substance_names.into_iter().cloned().collect()
 }
 
-fn load_source_specs(
+/// Finds the substances that will never have a non-zero concentration,
+/// because they are neither emitted by sources, nor initially present on
+/// surfels, nor the target of a rule.
+fn unused_substance_names(
+    unique_substance_names: &Vec<String>,
+    surfel_specs: &HashMap<String, SurfelSpec>,
+    source_specs: &Vec<TonSourceSpec>,
+    global_rules: &Vec<SurfelRuleSpec>,
+) -> Vec<String> {
+    fn non_zero<'a>(concentrations: &'a HashMap<String, f32>) -> Vec<&'a String> {
+        concentrations
+            .iter()
+            .filter(|&(_, &c)| c != 0.0)
+            .map(|(s, _)| s)
+            .collect()
+    }
+
+    fn produced<'a>(rule: &'a SurfelRuleSpec) -> Option<&'a String> {
+        match rule {
+            &SurfelRuleSpec::Transfer { ref to, .. } => Some(to),
+            &SurfelRuleSpec::Deposit { ref to, .. } => Some(to),
+            &SurfelRuleSpec::Deteriorate { .. } => None,
+        }
+    }
+
+    let used: HashSet<&String> = source_specs
+        .iter()
+        .flat_map(|s| non_zero(&s.initial))
+        .chain(surfel_specs.values().flat_map(|s| non_zero(&s.initial)))
+        .chain(
+            surfel_specs
+                .values()
+                .flat_map(|s| s.rules.iter())
+                .chain(global_rules.iter())
+                .filter_map(produced),
+        )
+        .collect();
+
+    let mut unused: Vec<String> = unique_substance_names
+        .iter()
+        .filter(|s| !used.contains(s))
+        .cloned()
+        .collect();
+    unused.sort();
+    unused
+}
+
+pub fn load_source_specs(
     sources: &Vec<PathBuf>,
     resolver: &Resolver,
 ) -> Result<Vec<TonSourceSpec>, Error> {
@@ -190,9 +507,14 @@ fn load_source_spec(path: &PathBuf, resolver: &Resolver) -> Result<TonSourceSpec
         .resolve(path)
         .map_err(|e| Error::resolve(e, ResolveErrorKind::TonSourceSpec))?;
 
-    let spec_file = &mut File::open(path)?;
+    let spec_file = &mut File::open(&path)?;
+
+    let mut spec: TonSourceSpec = serde_yaml::from_reader(spec_file)?;
 
-    let spec: TonSourceSpec = serde_yaml::from_reader(spec_file)?;
+    // Relative to the source spec, like paths in simulation specs
+    spec.mesh = resolver
+        .resolve_next_to(&path, &spec.mesh)
+        .map_err(|e| Error::resolve(e, ResolveErrorKind::TonSourceMesh))?;
 
     Ok(spec)
 }
@@ -259,7 +581,62 @@ fn build_sources(
         .collect()
 }
 
-fn surfel_specs_by_material_name(
+/// Makes every source flow into the given direction, projected onto
+/// the triangles, instead of the direction from its spec.
+fn override_flow_direction(source_specs: &mut Vec<TonSourceSpec>, direction: [f32; 3]) {
+    for source in source_specs.iter_mut() {
+        info!(
+            "Source emitting from {:?} flows in direction {:?} instead of {:?}, as overridden.",
+            source.mesh, direction, source.flow_direction
+        );
+        source.flow_direction = Some(direction);
+    }
+}
+
+/// Makes density and layer effects bake textures with the given width and
+/// height instead of the dimensions from their specs or from the materials.
+fn override_texture_resolution(effects: &mut Vec<EffectSpec>, (width, height): (usize, usize)) {
+    for effect in effects.iter_mut() {
+        match *effect {
+            EffectSpec::Density {
+                width: ref mut spec_width,
+                height: ref mut spec_height,
+                ref tex_pattern,
+                ..
+            } => {
+                info!(
+                    "Density textures {} are baked at {}x{} instead of {}x{}, as overridden.",
+                    tex_pattern, width, height, spec_width, spec_height
+                );
+                *spec_width = width;
+                *spec_height = height;
+            }
+            EffectSpec::Layer {
+                ref substance,
+                ref mut normal,
+                ref mut displacement,
+                ref mut albedo,
+                ref mut metallicity,
+                ref mut roughness,
+                ..
+            } => {
+                info!(
+                    "Layer textures for {} are baked at {}x{}, as overridden.",
+                    substance, width, height
+                );
+                for blend in &mut [normal, displacement, albedo, metallicity, roughness] {
+                    if let Some(ref mut blend) = **blend {
+                        blend.width = Some(width);
+                        blend.height = Some(height);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+pub fn surfel_specs_by_material_name(
     spec: &SimulationSpec,
     resolver: &Resolver,
 ) -> Result<HashMap<String, SurfelSpec>, Error> {
@@ -284,11 +661,19 @@ fn surfel_specs_by_material_name(
     }
 }
 
+/// Minimum distance between surfels that yields roughly the given number
+/// of surfels per square unit, considering each surfel to cover a square
+/// with the surfel distance as side length.
+pub fn surfel_distance_for_density(density: f32) -> f32 {
+    density.sqrt().recip()
+}
+
 fn build_surface(
     entities: &Vec<Entity>,
     surfel_specs_by_material_name: &HashMap<String, SurfelSpec>,
     unique_substance_names: &Vec<String>,
     surfel_distance: f32,
+    region_mask: Option<&RegionMask>,
 ) -> Surface<Surfel<Vertex, SurfelData>> {
     let catchall_surfel_spec = surfel_specs_by_material_name.get("_");
     let default_substance_concentration = 0.0;
@@ -339,7 +724,28 @@ fn build_surface(
                         ent.name, surfel_distance
                     );
 
-                    b.sample_triangles(ent.mesh.triangles(), &proto_surfel)
+                    match region_mask {
+                        Some(mask) => {
+                            // Surfels outside of the mask neither take up substances
+                            // from settling tons nor age with surfel rules, so they
+                            // keep their initial concentrations
+                            let (inside, outside): (Vec<_>, Vec<_>) =
+                                ent.mesh.triangles().partition(|t| mask.contains(t));
+
+                            let masked_out_surfel = SurfelData {
+                                deposition_rates: vec![
+                                    default_deposition_rate;
+                                    unique_substance_names.len()
+                                ],
+                                rules: Vec::new(),
+                                ..proto_surfel.clone()
+                            };
+
+                            b.sample_triangles(inside.into_iter(), &proto_surfel)
+                                .sample_triangles(outside.into_iter(), &masked_out_surfel)
+                        }
+                        None => b.sample_triangles(ent.mesh.triangles(), &proto_surfel),
+                    }
                 } else {
                     // If no surfel spec is defined in the YAML, ignore the entity for the simulation
                     b
@@ -401,3 +807,108 @@ fn extract_keys<K: Eq + Hash, V: Clone>(map: &HashMap<K, V>, keys: &Vec<K>, defa
         .map(|k| map.get(k).unwrap_or(&default).clone())
         .collect()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spec_version_mismatch() {
+        assert!(check_spec_version(Some(SPEC_VERSION), true).is_ok());
+        assert!(check_spec_version(None, true).is_ok());
+        assert!(check_spec_version(Some(SPEC_VERSION + 1), false).is_ok());
+
+        match check_spec_version(Some(SPEC_VERSION + 1), true) {
+            Err(Error::SpecVersionMismatch {
+                declared,
+                supported,
+            }) => {
+                assert_eq!(SPEC_VERSION + 1, declared);
+                assert_eq!(SPEC_VERSION, supported);
+            }
+            other => panic!("Expected version mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unused_substances() {
+        let source: TonSourceSpec = serde_yaml::from_str(
+            "name: Rain\ndescription: Test\nmesh: sky.obj\nemission_count: 1\np_straight: 0.0\np_parabolic: 0.0\np_flow: 0.0\ninitial:\n  water: 1.0\n  dust: 0.0\nabsorb:\n  dust: 0.5\ninteraction_radius: 0.1\nparabola_height: 0.1\nflow_distance: 0.1",
+        ).unwrap();
+        let surfel: SurfelSpec = serde_yaml::from_str(
+            "name: Iron\ndescription: Test\nreflectance:\n  delta_straight: 0.0\n  delta_parabolic: 0.0\n  delta_flow: 0.0\ninitial:\n  moss: 0.0\ndeposit:\n  water: 1.0\n  moss: 1.0\nrules:\n  - from: water\n    to: rust\n    factor: 0.5",
+        ).unwrap();
+
+        let mut surfel_specs = HashMap::new();
+        surfel_specs.insert(String::from("_"), surfel);
+        let source_specs = vec![source];
+
+        let names = unique_substance_names(&surfel_specs, &source_specs);
+        let unused = unused_substance_names(&names, &surfel_specs, &source_specs, &Vec::new());
+
+        // Water is emitted, but nothing ever makes moss or dust non-zero
+        assert_eq!(vec![String::from("dust"), String::from("moss")], unused);
+    }
+
+    #[test]
+    fn flow_direction_is_overridden() {
+        let mut sources: Vec<TonSourceSpec> = vec![
+            serde_yaml::from_str(
+                "name: Rain\ndescription: Test\nmesh: sky.obj\nemission_count: 1\np_straight: 0.0\np_parabolic: 0.0\np_flow: 1.0\ninitial:\n  water: 1.0\nabsorb:\n  water: 0.5\ninteraction_radius: 0.1\nparabola_height: 0.1\nflow_distance: 0.1\nflow_direction: [0.0, -1.0, 0.0]",
+            ).unwrap(),
+            serde_yaml::from_str(
+                "name: Spray\ndescription: Test\nmesh: sky.obj\nemission_count: 1\np_straight: 0.0\np_parabolic: 0.0\np_flow: 1.0\ninitial:\n  water: 1.0\nabsorb:\n  water: 0.5\ninteraction_radius: 0.1\nparabola_height: 0.1\nflow_distance: 0.1",
+            ).unwrap(),
+        ];
+
+        override_flow_direction(&mut sources, [1.0, -1.0, 0.0]);
+
+        for source in sources.iter() {
+            assert_eq!(Some([1.0, -1.0, 0.0]), source.flow_direction);
+        }
+    }
+
+    #[test]
+    fn texture_resolution_is_overridden() {
+        let mut effects: Vec<EffectSpec> = serde_yaml::from_str(
+            "- density:\n    width: 2048\n    height: 1024\n    tex_pattern: a.png\n- layer:\n    materials: [bronze]\n    substance: rust\n    albedo:\n      tex_pattern: b.png\n      stops: []\n- dump_surfels:\n    obj_pattern: c.obj",
+        ).unwrap();
+
+        override_texture_resolution(&mut effects, (512, 256));
+
+        match effects[0] {
+            EffectSpec::Density { width, height, .. } => assert_eq!((512, 256), (width, height)),
+            _ => panic!("Expected density effect"),
+        }
+        match effects[1] {
+            EffectSpec::Layer {
+                albedo: Some(ref albedo),
+                ..
+            } => assert_eq!((Some(512), Some(256)), (albedo.width, albedo.height)),
+            _ => panic!("Expected layer effect with albedo"),
+        }
+    }
+
+    #[test]
+    fn triangles_are_limited_in_order() {
+        let two_skies = || {
+            let mut entities = obj::load("tests/assets/sky.obj").unwrap();
+            entities.extend(obj::load("tests/assets/sky.obj").unwrap());
+            entities
+        };
+        let triangle_counts = |entities: Vec<Entity>| -> Vec<usize> {
+            entities.iter().map(|e| e.mesh.triangles().count()).collect()
+        };
+        let sky = triangle_counts(obj::load("tests/assets/sky.obj").unwrap())[0];
+
+        assert_eq!(
+            vec![sky, 1],
+            triangle_counts(limit_triangles(two_skies(), sky + 1))
+        );
+        assert_eq!(vec![1], triangle_counts(limit_triangles(two_skies(), 1)));
+        assert_eq!(
+            vec![sky, sky],
+            triangle_counts(limit_triangles(two_skies(), 3 * sky))
+        );
+    }
+}
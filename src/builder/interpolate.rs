@@ -0,0 +1,127 @@
+use serde_yaml::Value;
+use std::env;
+
+/// Expands references to environment variables in all string scalars of the
+/// given YAML value, e.g. `${OUT_DIR}/weathered.png` or `$HOME/scenes`, with
+/// `$$` producing a single literal `$`. Keys of mappings are left as they are.
+///
+/// Fails with the name of the first referenced variable that is not set.
+pub fn interpolate_env(value: &mut Value) -> Result<(), String> {
+    interpolate_with(value, &|name| env::var(name).ok())
+}
+
+fn interpolate_with<F>(value: &mut Value, lookup: &F) -> Result<(), String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    match *value {
+        Value::String(ref mut string) => {
+            if string.contains('$') {
+                *string = interpolate_str(string, lookup)?;
+            }
+        }
+        Value::Sequence(ref mut sequence) => {
+            for element in sequence.iter_mut() {
+                interpolate_with(element, lookup)?;
+            }
+        }
+        Value::Mapping(ref mut mapping) => {
+            let keys: Vec<Value> = mapping.iter().map(|(key, _)| key.clone()).collect();
+            for key in keys {
+                interpolate_with(mapping.get_mut(&key).unwrap(), lookup)?;
+            }
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// Expands variables in a single string. A `$` that is followed by neither a
+/// variable name nor a brace is kept as is.
+fn interpolate_str<F>(string: &str, lookup: &F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut expanded = String::with_capacity(string.len());
+    let mut rest = string;
+
+    while let Some(dollar) = rest.find('$') {
+        expanded.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+
+        let (name, remaining) = if after.starts_with('$') {
+            expanded.push('$');
+            rest = &after[1..];
+            continue;
+        } else if after.starts_with('{') {
+            match after.find('}') {
+                Some(close) => (&after[1..close], &after[close + 1..]),
+                None => ("", after),
+            }
+        } else {
+            let len = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..len], &after[len..])
+        };
+
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            expanded.push('$');
+            rest = after;
+        } else {
+            expanded.push_str(&lookup(name).ok_or_else(|| String::from(name))?);
+            rest = remaining;
+        }
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_yaml;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "OUT_DIR" => Some(String::from("/tmp/out")),
+            "NAME" => Some(String::from("buddha")),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn set_variables_are_expanded() {
+        assert_eq!(
+            Ok(String::from("/tmp/out/buddha.png")),
+            interpolate_str("${OUT_DIR}/$NAME.png", &lookup)
+        );
+    }
+
+    #[test]
+    fn unset_variable_is_an_error() {
+        assert_eq!(
+            Err(String::from("MISSING")),
+            interpolate_str("${OUT_DIR}/${MISSING}/a.png", &lookup)
+        );
+        assert_eq!(Err(String::from("MISSING")), interpolate_str("$MISSING", &lookup));
+    }
+
+    #[test]
+    fn double_dollar_is_literal() {
+        assert_eq!(Ok(String::from("cost$NAME $")), interpolate_str("cost$$NAME $", &lookup));
+    }
+
+    #[test]
+    fn only_string_scalars_are_expanded() {
+        let mut value: Value =
+            serde_yaml::from_str("$NAME: $NAME\nlist:\n  - ${OUT_DIR}\n  - 5").unwrap();
+        interpolate_with(&mut value, &lookup).unwrap();
+
+        let expected: Value =
+            serde_yaml::from_str("$NAME: buddha\nlist:\n  - /tmp/out\n  - 5").unwrap();
+        assert_eq!(expected, value);
+    }
+}
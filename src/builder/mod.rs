@@ -1,11 +1,35 @@
 mod append;
 mod builder;
 mod canonicalize;
+mod check;
 mod err;
+mod estimate;
+mod format;
+mod include;
 mod instantiate;
+mod interpolate;
+mod options;
+mod overrides;
+mod region_mask;
+mod relocate;
+mod substances;
+mod suggest;
 
 pub use self::append::append;
 pub use self::builder::SimulationBuilder;
 pub use self::canonicalize::canonicalize;
+pub use self::check::{
+    check_assets, check_output_dirs, existing_outputs, input_paths, missing_inputs,
+    probe_output_dirs, AssetProblem, AssetReport,
+};
 pub use self::err::{Error, ResolveErrorKind};
-pub use self::instantiate::instantiate;
+pub use self::estimate::{estimate_bounds, estimate_peak_memory};
+pub use self::format::SpecFormat;
+pub use self::include::parse_spec;
+pub use self::instantiate::{instantiate, override_spec};
+pub use self::interpolate::interpolate_env;
+pub use self::options::LoadOptions;
+pub use self::overrides::set_field;
+pub use self::region_mask::RegionMask;
+pub use self::relocate::{map_outputs, relocated};
+pub use self::substances::{SubstanceRow, SubstanceTable};
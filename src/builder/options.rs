@@ -0,0 +1,48 @@
+use runner::BakeFilter;
+
+/// Options for loading a simulation that are not part of the simulation
+/// spec, but are chosen when running it, e.g. from the command line.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// If not empty, only entities with these names are simulated.
+    pub objects: Vec<String>,
+    /// If not empty, density effects only bake textures for the substances
+    /// with these names.
+    pub substances: Vec<String>,
+    /// Keeps entities that were not selected with `objects` as obstacles
+    /// for gammatons, without generating surfels or exporting them.
+    pub unselected_as_occluders: bool,
+    /// Warns about substances that never have a non-zero concentration.
+    pub warn_unused_substances: bool,
+    /// Settles every gammaton on the first surface it hits, disabling
+    /// straight, parabolic and flow bounces of all sources.
+    pub first_hit_only: bool,
+    /// Turns problems that are otherwise only warned about into errors,
+    /// e.g. a mismatching spec version.
+    pub strict: bool,
+    /// Overrides the number of iterations of the spec.
+    pub iterations: Option<u32>,
+    /// Overrides the surfel distance of the spec with one that yields
+    /// roughly this many surfels per square unit of surface area.
+    pub surfel_density: Option<f32>,
+    /// Overrides the number of gammatons every source emits per iteration.
+    pub emission_count: Option<usize>,
+    /// Overrides the flow direction of every source.
+    pub flow_direction: Option<[f32; 3]>,
+    /// Only keeps this many triangles of the simulated entities, in scene
+    /// order, for quick but inaccurate previews of dense meshes.
+    pub triangle_limit: Option<usize>,
+    /// Overrides width and height of textures baked by density and layer
+    /// effects.
+    pub texture_resolution: Option<(usize, usize)>,
+    /// Only traces, without performing effects or writing benchmarks, e.g.
+    /// to measure tracing throughput.
+    pub skip_outputs: bool,
+    /// Records the time spent in each phase of loading and running.
+    pub profile: bool,
+    /// Overrides the filter for baking density textures.
+    pub bake_filter: Option<BakeFilter>,
+    /// Radius of linear and gaussian bake filters, the surfel distance if
+    /// not given.
+    pub bake_radius: Option<f32>,
+}
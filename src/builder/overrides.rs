@@ -0,0 +1,132 @@
+use serde_yaml::{Mapping, Value};
+
+/// Replaces the field at the given dotted path in a YAML value, e.g.
+/// `benchmark.iterations`, with numbers indexing into lists, e.g.
+/// `effects.0.width`.
+///
+/// Only existing fields can be replaced, so misspelled paths are reported
+/// rather than silently ignored. Unset optional fields are null and can be
+/// set, including fields below them.
+pub fn set_field(root: &mut Value, path: &str, field_value: Value) -> Result<(), String> {
+    let segments: Vec<&str> = path.split('.').collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(format!("{:?} is not a valid field path", path));
+    }
+
+    set_segments(root, &segments, 0, field_value, false)
+}
+
+fn set_segments(
+    value: &mut Value,
+    segments: &[&str],
+    idx: usize,
+    field_value: Value,
+    created: bool,
+) -> Result<(), String> {
+    if idx == segments.len() {
+        *value = field_value;
+        return Ok(());
+    }
+
+    let segment = segments[idx];
+    let parent = if idx == 0 {
+        String::from("the spec")
+    } else {
+        format!("{:?}", segments[..idx].join("."))
+    };
+
+    // Unset optional fields can be set field by field
+    let created = if value.is_null() {
+        *value = Value::Mapping(Mapping::new());
+        true
+    } else {
+        created
+    };
+
+    match *value {
+        Value::Mapping(ref mut mapping) => {
+            let key = Value::String(String::from(segment));
+            if !mapping.contains_key(&key) {
+                if !created {
+                    return Err(format!("{} has no field {:?}", parent, segment));
+                }
+                mapping.insert(key.clone(), Value::Null);
+            }
+            set_segments(
+                mapping.get_mut(&key).unwrap(),
+                segments,
+                idx + 1,
+                field_value,
+                created,
+            )
+        }
+        Value::Sequence(ref mut sequence) => {
+            let len = sequence.len();
+            match segment.parse::<usize>() {
+                Ok(element) if element < len => set_segments(
+                    &mut sequence[element],
+                    segments,
+                    idx + 1,
+                    field_value,
+                    created,
+                ),
+                _ => Err(format!(
+                    "{} is a list of {} elements, {:?} is not a valid index",
+                    parent, len, segment
+                )),
+            }
+        }
+        _ => Err(format!("{} has no fields, cannot set {:?}", parent, segment)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_yaml;
+
+    fn yaml(text: &str) -> Value {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn nested_number() {
+        let mut spec = yaml("iterations: 10\neffects:\n  - density:\n      width: 64\n      height: 64");
+
+        set_field(&mut spec, "effects.0.density.width", yaml("1e3")).unwrap();
+        set_field(&mut spec, "iterations", yaml("20")).unwrap();
+
+        assert_eq!(
+            yaml("iterations: 20\neffects:\n  - density:\n      width: 1e3\n      height: 64"),
+            spec
+        );
+    }
+
+    #[test]
+    fn unset_optional_field() {
+        let mut spec = yaml("benchmark: ~");
+        set_field(&mut spec, "benchmark.iterations", yaml("bench.csv")).unwrap();
+        assert_eq!(yaml("benchmark:\n  iterations: bench.csv"), spec);
+    }
+
+    #[test]
+    fn invalid_paths() {
+        let mut spec = yaml("iterations: 10\neffects: []");
+
+        assert_eq!(
+            Err(String::from("the spec has no field \"iteration\"")),
+            set_field(&mut spec, "iteration", yaml("20"))
+        );
+        assert_eq!(
+            Err(String::from(
+                "\"effects\" is a list of 0 elements, \"0\" is not a valid index"
+            )),
+            set_field(&mut spec, "effects.0.width", yaml("20"))
+        );
+        assert_eq!(
+            Err(String::from("\"iterations\" has no fields, cannot set \"max\"")),
+            set_field(&mut spec, "iterations.max", yaml("20"))
+        );
+        assert!(set_field(&mut spec, "iterations..max", yaml("20")).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use builder::Error;
+use geom::{TupleTriangle, Vertex};
+use std::path::Path;
+use tex::{self, DynamicImage, GenericImage, Pixel};
+
+/// Restricts weathering to the parts of the scene where a mask image
+/// is bright.
+///
+/// The mask is looked up in UV space, using the average texture
+/// coordinates of each triangle, so it can be painted like any other
+/// texture map of the scene. Texels with a luminance of more than 50%
+/// admit the triangle, darker texels exclude it.
+///
+/// Texture coordinates outside of the unit square wrap around, as if
+/// the mask was repeated infinitely. Like in OBJ files, `v = 0` is the
+/// bottom row of the image.
+pub struct RegionMask {
+    mask: DynamicImage,
+}
+
+impl RegionMask {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mask = tex::open(path).map_err(|e| Error::RegionMask {
+            path: path.to_path_buf(),
+            reason: format!("{}", e),
+        })?;
+
+        Ok(Self { mask })
+    }
+
+    /// Checks whether surfels generated on the given triangle should
+    /// take part in the simulation.
+    pub fn contains(&self, triangle: &TupleTriangle<Vertex>) -> bool {
+        let &TupleTriangle(ref v0, ref v1, ref v2) = triangle;
+        let u = (v0.texcoords.x + v1.texcoords.x + v2.texcoords.x) / 3.0;
+        let v = (v0.texcoords.y + v1.texcoords.y + v2.texcoords.y) / 3.0;
+        self.contains_uv(u, v)
+    }
+
+    /// Checks whether the mask admits the given texture coordinates.
+    pub fn contains_uv(&self, u: f32, v: f32) -> bool {
+        let (width, height) = self.mask.dimensions();
+
+        // Wrap around like a repeating texture and flip v, since
+        // image rows start at the top
+        let u = u - u.floor();
+        let v = 1.0 - (v - v.floor());
+
+        let x = ((u * width as f32) as u32).min(width - 1);
+        let y = ((v * height as f32) as u32).min(height - 1);
+
+        self.mask.get_pixel(x, y).to_luma().data[0] > 127
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn white_mask_admits_everything() {
+        let mask = RegionMask::open("tests/examples/white_pixel.png").unwrap();
+        assert!(mask.contains_uv(0.0, 0.0));
+        assert!(mask.contains_uv(0.5, 0.5));
+        assert!(mask.contains_uv(1.0, 1.0));
+        assert!(mask.contains_uv(-2.3, 4.7), "Expected mask to wrap around");
+    }
+
+    #[test]
+    fn black_mask_admits_nothing() {
+        let mask = RegionMask::open("tests/examples/black_pixel.png").unwrap();
+        assert!(!mask.contains_uv(0.0, 0.0));
+        assert!(!mask.contains_uv(0.5, 0.5));
+        assert!(!mask.contains_uv(1.0, 1.0));
+    }
+
+    #[test]
+    fn missing_mask_is_error() {
+        assert!(RegionMask::open("tests/examples/nonexistent_mask.png").is_err());
+    }
+}
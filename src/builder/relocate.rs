@@ -0,0 +1,120 @@
+use spec::{EffectSpec, SimulationSpec};
+use std::path::{Path, PathBuf};
+
+/// Replaces all output paths and patterns of the spec, i.e. the outputs of
+/// effects, benchmarks and the log, with the result of the given function.
+pub fn map_outputs<F>(spec: &mut SimulationSpec, mut map: F)
+where
+    F: FnMut(&str) -> String,
+{
+    {
+        let mut map_pattern = |pattern: &mut String| *pattern = map(pattern);
+        map_effect_outputs(spec, &mut map_pattern);
+    }
+
+    let mut map_path = |path: &mut PathBuf| *path = PathBuf::from(map(&path.to_string_lossy()));
+    if let Some(ref mut benchmark) = spec.benchmark {
+        for path in [
+            &mut benchmark.setup,
+            &mut benchmark.iterations,
+            &mut benchmark.tracing,
+            &mut benchmark.synthesis,
+        ].iter_mut()
+        .filter_map(|p| p.as_mut())
+        {
+            map_path(path);
+        }
+    }
+
+    spec.log.iter_mut().for_each(map_path);
+}
+
+/// Prepends the given directory to the given output path or pattern if it
+/// is relative, absolute paths are left unchanged.
+pub fn relocated(dir: &Path, output: &str) -> String {
+    if Path::new(output).is_relative() {
+        dir.join(output).to_string_lossy().into_owned()
+    } else {
+        String::from(output)
+    }
+}
+
+fn map_effect_outputs<F: FnMut(&mut String)>(spec: &mut SimulationSpec, map: &mut F) {
+    for effect in spec.effects.iter_mut() {
+        match effect {
+            &mut EffectSpec::Density {
+                ref mut tex_pattern,
+                ref mut obj_pattern,
+                ref mut mtl_pattern,
+                ..
+            } => {
+                map(tex_pattern);
+                obj_pattern.iter_mut().for_each(|p| map(p));
+                mtl_pattern.iter_mut().for_each(|p| map(p));
+            }
+            &mut EffectSpec::Layer {
+                ref mut normal,
+                ref mut displacement,
+                ref mut albedo,
+                ref mut metallicity,
+                ref mut roughness,
+                ..
+            } => {
+                for blend in [normal, displacement, albedo, metallicity, roughness]
+                    .iter_mut()
+                    .filter_map(|b| b.as_mut())
+                {
+                    map(&mut blend.tex_pattern);
+                }
+            }
+            &mut EffectSpec::Export {
+                ref mut obj_pattern,
+                ref mut mtl_pattern,
+            } => {
+                obj_pattern.iter_mut().for_each(|p| map(p));
+                mtl_pattern.iter_mut().for_each(|p| map(p));
+            }
+            &mut EffectSpec::DumpSurfels {
+                ref mut obj_pattern,
+            } => map(obj_pattern),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_yaml;
+
+    #[test]
+    fn relative_outputs_below_dir() {
+        let mut spec: SimulationSpec = serde_yaml::from_str(
+            "log: logs/sim.log\nbenchmark:\n  iterations: bench/iterations.csv\neffects:\n  - density:\n      width: 64\n      height: 64\n      tex_pattern: \"{entity}-{substance}.png\"\n      obj_pattern: \"/var/aitios/{iteration}.obj\"\n  - dump_surfels:\n      obj_pattern: \"surfels/{iteration}.obj\"",
+        ).unwrap();
+
+        map_outputs(&mut spec, |output| relocated(Path::new("/tmp/run1"), output));
+
+        assert_eq!(Some(PathBuf::from("/tmp/run1/logs/sim.log")), spec.log);
+        assert_eq!(
+            Some(PathBuf::from("/tmp/run1/bench/iterations.csv")),
+            spec.benchmark.unwrap().iterations
+        );
+        match spec.effects[0] {
+            EffectSpec::Density {
+                ref tex_pattern,
+                ref obj_pattern,
+                ..
+            } => {
+                assert_eq!("/tmp/run1/{entity}-{substance}.png", tex_pattern);
+                assert_eq!(&Some(String::from("/var/aitios/{iteration}.obj")), obj_pattern);
+            }
+            _ => unreachable!(),
+        }
+        match spec.effects[1] {
+            EffectSpec::DumpSurfels { ref obj_pattern } => {
+                assert_eq!("/tmp/run1/surfels/{iteration}.obj", obj_pattern)
+            }
+            _ => unreachable!(),
+        }
+    }
+}
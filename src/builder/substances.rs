@@ -0,0 +1,199 @@
+use builder::instantiate::{load_source_specs, surfel_specs_by_material_name};
+use builder::Error;
+use files::Resolver;
+use spec::{SimulationSpec, SurfelRuleSpec};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Parameters of all substances of a simulation spec, as printed by
+/// `--list-substances`.
+///
+/// Every substance gets a row for each gammaton source and surfel spec
+/// that mentions it, sorted by substance name first.
+#[derive(Debug)]
+pub struct SubstanceTable {
+    pub rows: Vec<SubstanceRow>,
+}
+
+/// Parameters of a substance in a single gammaton source or surfel spec.
+#[derive(Debug)]
+pub struct SubstanceRow {
+    pub substance: String,
+    /// Kind and name of the spec, e.g. `source Rain`, or the material a
+    /// surfel spec is used for, e.g. `material bronze` or `material _` for
+    /// the fallback.
+    pub spec: String,
+    /// Initial concentration of gammatons or surfels.
+    pub initial: f32,
+    /// For sources the rate at which bouncing gammatons absorb the
+    /// substance, for surfels the rate at which settling gammatons deposit
+    /// it.
+    pub rate: f32,
+    /// Surfel rules that change the substance, e.g. `deteriorate 0.1`.
+    pub rules: Vec<String>,
+}
+
+impl SubstanceTable {
+    /// Loads the sources and surfel specs of the given spec and collects
+    /// the parameters of all substances they mention.
+    pub fn load(spec: &SimulationSpec, resolver: &Resolver) -> Result<Self, Error> {
+        let mut rows = Vec::new();
+
+        for source in load_source_specs(&spec.sources, resolver)? {
+            let spec = format!("source {}", source.name());
+            let substances = names(&source.initial, &source.absorb);
+            rows.extend(substances.into_iter().map(|substance| SubstanceRow {
+                initial: lookup(&source.initial, &substance),
+                rate: lookup(&source.absorb, &substance),
+                rules: Vec::new(),
+                spec: spec.clone(),
+                substance,
+            }));
+        }
+
+        for (material, surfel) in surfel_specs_by_material_name(spec, resolver)? {
+            let spec = format!("material {}", material);
+            let mut substances = names(&surfel.initial, &surfel.deposit);
+            for rule in surfel.rules.iter() {
+                for substance in rule_substances(rule) {
+                    if !substances.contains(substance) {
+                        substances.push(substance.clone());
+                    }
+                }
+            }
+            substances.sort();
+
+            rows.extend(substances.into_iter().map(|substance| SubstanceRow {
+                initial: lookup(&surfel.initial, &substance),
+                rate: lookup(&surfel.deposit, &substance),
+                rules: surfel
+                    .rules
+                    .iter()
+                    .filter_map(|rule| describe_rule(rule, &substance))
+                    .collect(),
+                spec: spec.clone(),
+                substance,
+            }));
+        }
+
+        // Also sorted by spec, since materials come from a hash map
+        rows.sort_by(|a, b| (&a.substance, &a.spec).cmp(&(&b.substance, &b.spec)));
+        Ok(SubstanceTable { rows })
+    }
+}
+
+/// Sorted names of the substances in either of the given maps.
+fn names(a: &HashMap<String, f32>, b: &HashMap<String, f32>) -> Vec<String> {
+    let mut names: Vec<String> = a.keys().chain(b.keys()).cloned().collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn lookup(concentrations: &HashMap<String, f32>, substance: &str) -> f32 {
+    concentrations.get(substance).cloned().unwrap_or(0.0)
+}
+
+fn rule_substances(rule: &SurfelRuleSpec) -> Vec<&String> {
+    match *rule {
+        SurfelRuleSpec::Transfer {
+            ref from, ref to, ..
+        } => vec![from, to],
+        SurfelRuleSpec::Deteriorate { ref from, .. } => vec![from],
+        SurfelRuleSpec::Deposit { ref to, .. } => vec![to],
+    }
+}
+
+/// Short description of the rule from the perspective of the substance, if
+/// the rule changes it.
+fn describe_rule(rule: &SurfelRuleSpec, substance: &str) -> Option<String> {
+    match *rule {
+        SurfelRuleSpec::Transfer {
+            ref from,
+            ref to,
+            factor,
+        } if from == substance => Some(format!("transfer {} to {}", factor, to)),
+        SurfelRuleSpec::Transfer {
+            ref from,
+            ref to,
+            factor,
+        } if to == substance => Some(format!("transfer {} from {}", factor, from)),
+        SurfelRuleSpec::Deteriorate { ref from, factor } if from == substance => {
+            Some(format!("deteriorate {}", factor))
+        }
+        SurfelRuleSpec::Deposit { ref to, amount } if to == substance => {
+            Some(format!("deposit {}", amount))
+        }
+        _ => None,
+    }
+}
+
+impl fmt::Display for SubstanceTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let substance_width = self
+            .rows
+            .iter()
+            .map(|r| r.substance.len())
+            .chain(Some("SUBSTANCE".len()))
+            .max()
+            .unwrap();
+        let spec_width = self
+            .rows
+            .iter()
+            .map(|r| r.spec.len())
+            .chain(Some("SPEC".len()))
+            .max()
+            .unwrap();
+
+        write!(
+            f,
+            "{:<sw$}  {:<pw$}  {:>10}  {:>10}  RULES",
+            "SUBSTANCE",
+            "SPEC",
+            "INITIAL",
+            "RATE",
+            sw = substance_width,
+            pw = spec_width
+        )?;
+        for row in self.rows.iter() {
+            write!(
+                f,
+                "\n{:<sw$}  {:<pw$}  {:>10}  {:>10}",
+                row.substance,
+                row.spec,
+                row.initial,
+                row.rate,
+                sw = substance_width,
+                pw = spec_width
+            )?;
+            if !row.rules.is_empty() {
+                write!(f, "  {}", row.rules.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rules_are_described_per_substance() {
+        let transfer = SurfelRuleSpec::Transfer {
+            from: String::from("humidity"),
+            to: String::from("rust"),
+            factor: 0.5,
+        };
+
+        assert_eq!(
+            Some(String::from("transfer 0.5 to rust")),
+            describe_rule(&transfer, "humidity")
+        );
+        assert_eq!(
+            Some(String::from("transfer 0.5 from humidity")),
+            describe_rule(&transfer, "rust")
+        );
+        assert_eq!(None, describe_rule(&transfer, "dust"));
+    }
+}
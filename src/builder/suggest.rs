@@ -0,0 +1,80 @@
+/// Suggests the closest expected name for an unknown field or variant in a
+/// serde error message, e.g. `emitter` for "unknown field `emiter`, expected
+/// one of `emitter`, `name`".
+///
+/// Returns `None` if the message is not about an unknown name or if no
+/// expected name is similar enough to be a plausible typo.
+pub fn did_you_mean(message: &str) -> Option<String> {
+    let unknown_start = message
+        .find("unknown field `")
+        .map(|idx| idx + "unknown field `".len())
+        .or_else(|| {
+            message
+                .find("unknown variant `")
+                .map(|idx| idx + "unknown variant `".len())
+        })?;
+    let unknown_len = message[unknown_start..].find('`')?;
+    let unknown = &message[unknown_start..unknown_start + unknown_len];
+
+    let rest = &message[unknown_start + unknown_len..];
+    let expected = &rest[rest.find("expected")?..];
+    let expected = match expected.find(" at line ") {
+        Some(end) => &expected[..end],
+        None => expected,
+    };
+
+    expected
+        .split('`')
+        // Names are every second piece between backticks
+        .skip(1)
+        .step_by(2)
+        .map(|candidate| (edit_distance(unknown, candidate), candidate))
+        .filter(|&(distance, candidate)| distance <= (candidate.len() / 3).max(1))
+        .min()
+        .map(|(_, candidate)| String::from(candidate))
+}
+
+/// Levenshtein distance between the given strings, in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..b.len() + 1).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, &b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == b_char { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn suggests_closest_field() {
+        assert_eq!(
+            Some(String::from("emitter")),
+            did_you_mean(
+                "unknown field `emiter`, expected one of `name`, `emitter`, `mesh` at line 12 column 3"
+            )
+        );
+        assert_eq!(
+            Some(String::from("density")),
+            did_you_mean("unknown variant `densty`, expected one of `density`, `export`")
+        );
+    }
+
+    #[test]
+    fn no_suggestion_for_unrelated_names() {
+        assert_eq!(
+            None,
+            did_you_mean("unknown field `color`, expected one of `name`, `mesh` at line 1 column 1")
+        );
+        assert_eq!(None, did_you_mean("invalid type: string \"a\", expected u32"));
+    }
+}
@@ -0,0 +1,166 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use tar;
+use zip::write::{FileOptions, ZipWriter};
+
+/// Container format of an output archive, inferred from its file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Infers the format from the extension of the given path, that is
+    /// `.zip`, `.tar.gz` or `.tgz`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let name = path.as_ref().file_name()?.to_str()?.to_lowercase();
+
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Packages the given files into a new archive at the given path, with the
+/// format inferred from its extension.
+///
+/// Paths inside the archive are relative to the given root, so the archive
+/// extracts into the same layout the files have below the root. Files outside
+/// the root cannot be archived and result in an error of kind
+/// `io::ErrorKind::InvalidInput`.
+pub fn write_archive<P>(archive_path: P, root: &Path, files: &[PathBuf]) -> io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let archive_path = archive_path.as_ref();
+    let format = ArchiveFormat::from_path(archive_path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Cannot infer archive format of {:?}, expected .zip, .tar.gz or .tgz",
+                archive_path
+            ),
+        )
+    })?;
+
+    let entries = files
+        .iter()
+        .map(|file| entry_name(root, file).map(|name| (file, name)))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let archive = File::create(archive_path)?;
+
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = ZipWriter::new(archive);
+            for (file, name) in entries {
+                zip.start_file(name, FileOptions::default())
+                    .map_err(zip_error)?;
+                io::copy(&mut File::open(file)?, &mut zip)?;
+            }
+            zip.finish().map_err(zip_error)?;
+        }
+        ArchiveFormat::TarGz => {
+            let mut tar = tar::Builder::new(GzEncoder::new(archive, Compression::default()));
+            for (file, name) in entries {
+                tar.append_path_with_name(file, name)?;
+            }
+            tar.into_inner()?.finish()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Name of the file inside the archive, relative to the root and with forward
+/// slashes as separators regardless of platform.
+fn entry_name(root: &Path, file: &Path) -> io::Result<String> {
+    let relative = if file.is_absolute() {
+        file.strip_prefix(root).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Cannot archive {:?} because it is not located below {:?}",
+                    file, root
+                ),
+            )
+        })?
+    } else {
+        file
+    };
+
+    let mut name = String::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => {
+                if !name.is_empty() {
+                    name.push('/');
+                }
+                name.push_str(&part.to_string_lossy());
+            }
+            Component::CurDir => (),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Cannot archive {:?} because it is not located below {:?}",
+                        file, root
+                    ),
+                ))
+            }
+        }
+    }
+
+    Ok(name)
+}
+
+fn zip_error<E>(err: E) -> io::Error
+where
+    E: Into<Box<::std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn infers_format_from_extension() {
+        assert_eq!(
+            Some(ArchiveFormat::Zip),
+            ArchiveFormat::from_path("results/out.ZIP")
+        );
+        assert_eq!(
+            Some(ArchiveFormat::TarGz),
+            ArchiveFormat::from_path("out.tar.gz")
+        );
+        assert_eq!(Some(ArchiveFormat::TarGz), ArchiveFormat::from_path("out.tgz"));
+        assert_eq!(None, ArchiveFormat::from_path("out.tar"));
+        assert_eq!(None, ArchiveFormat::from_path("zip"));
+    }
+
+    #[test]
+    fn entry_names_relative_to_root() {
+        let root = Path::new("/work");
+
+        assert_eq!(
+            "2018/iteration-1/rust.png",
+            entry_name(root, Path::new("/work/2018/iteration-1/rust.png")).unwrap()
+        );
+        assert_eq!(
+            "2018/rust.obj",
+            entry_name(root, Path::new("./2018/rust.obj")).unwrap()
+        );
+        assert!(entry_name(root, Path::new("/elsewhere/rust.png")).is_err());
+        assert!(entry_name(root, Path::new("../rust.png")).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// The first two bytes of every gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Reads a text file, decompressing it first if it is gzipped.
+///
+/// Files with a `.gz` extension, e.g. `sim.yml.gz`, are always decompressed,
+/// other files only if they start with the gzip magic number.
+pub fn read_to_string_gunzipped<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let path = path.as_ref();
+    let gzipped = path.extension().map(|ext| ext == "gz").unwrap_or(false);
+    read_text(File::open(path)?, gzipped)
+}
+
+/// Reads all text from the given source, e.g. stdin, decompressing it first
+/// if it starts with the gzip magic number.
+pub fn read_to_string_sniffed<R: Read>(reader: R) -> io::Result<String> {
+    read_text(reader, false)
+}
+
+fn read_text<R: Read>(mut reader: R, gzipped: bool) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    if gzipped || bytes.starts_with(&GZIP_MAGIC) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&bytes[..]).read_to_end(&mut decompressed)?;
+        bytes = decompressed;
+    }
+
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn plain_and_gzipped_text_read_the_same() {
+        let text = "name: Compressed\niterations: 2\n";
+        assert_eq!(text, read_to_string_sniffed(text.as_bytes()).unwrap());
+        assert_eq!(text, read_to_string_sniffed(&gzip(text)[..]).unwrap());
+    }
+
+    #[test]
+    fn gz_extension_must_be_gzipped() {
+        assert!(read_text("name: Plain".as_bytes(), true).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use std::io::{self, Write};
+
+/// Writer that holds back written data until a line is complete and then
+/// passes all complete lines to the inner writer in a single `write_all`.
+///
+/// Loggers usually write a record in several pieces, e.g. timestamp, level
+/// and message. Wrapping a log file in this ensures that readers following
+/// the file never see a partially written record.
+///
+/// An incomplete last line is written when the writer is dropped.
+pub struct WholeLines<W: Write> {
+    inner: W,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> WholeLines<W> {
+    pub fn new(inner: W) -> Self {
+        WholeLines {
+            inner,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for WholeLines<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+
+        if let Some(last_newline) = self.pending.iter().rposition(|&b| b == b'\n') {
+            let incomplete = self.pending.split_off(last_newline + 1);
+            let result = self.inner.write_all(&self.pending);
+            self.pending = incomplete;
+            result?;
+        }
+
+        Ok(buf.len())
+    }
+
+    /// Flushes the inner writer, but keeps back an incomplete line.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for WholeLines<W> {
+    fn drop(&mut self) {
+        if !self.pending.is_empty() {
+            let _ = self.inner.write_all(&self.pending);
+            let _ = self.inner.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use log::{Level, LevelFilter, Log, Record};
+    use simplelog::{Config, WriteLogger};
+    use std::env::temp_dir;
+    use std::fs::{read_to_string, remove_file, File};
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Records every call to `write` separately.
+    struct Writes(Vec<Vec<u8>>);
+
+    impl Write for Writes {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(buf.to_vec());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_only_whole_lines() {
+        let mut writes = Writes(Vec::new());
+        {
+            let mut lines = WholeLines::new(&mut writes);
+            write!(lines, "12:00:00 ").unwrap();
+            write!(lines, "[INFO] ").unwrap();
+            write!(lines, "first\nsec").unwrap();
+            write!(lines, "ond\n").unwrap();
+            write!(lines, "unterminated").unwrap();
+        }
+
+        assert_eq!(
+            vec![
+                b"12:00:00 [INFO] first\n".to_vec(),
+                b"second\n".to_vec(),
+                b"unterminated".to_vec(),
+            ],
+            writes.0
+        );
+    }
+
+    #[test]
+    fn concurrent_records_are_well_formed() {
+        const THREADS: usize = 8;
+        const RECORDS: usize = 500;
+
+        let path = temp_dir().join("aitios-whole-lines-stress-test.log");
+        {
+            let file = File::create(&path).unwrap();
+            let logger: Arc<Box<WriteLogger<WholeLines<File>>>> = Arc::new(WriteLogger::new(
+                LevelFilter::Info,
+                Config::default(),
+                WholeLines::new(file),
+            ));
+
+            let workers: Vec<_> = (0..THREADS)
+                .map(|thread_idx| {
+                    let logger = logger.clone();
+                    thread::spawn(move || {
+                        for record_idx in 0..RECORDS {
+                            logger.log(
+                                &Record::builder()
+                                    .args(format_args!(
+                                        "thread {} record {} end",
+                                        thread_idx, record_idx
+                                    ))
+                                    .level(Level::Info)
+                                    .target("stress")
+                                    .build(),
+                            );
+                        }
+                    })
+                })
+                .collect();
+
+            for worker in workers {
+                worker.join().unwrap();
+            }
+        }
+
+        let log = read_to_string(&path).unwrap();
+        remove_file(&path).unwrap();
+
+        assert_eq!(THREADS * RECORDS, log.lines().count());
+        for line in log.lines() {
+            assert!(
+                line.contains("thread ") && line.ends_with(" end"),
+                "Malformed log line: {:?}",
+                line
+            );
+        }
+    }
+}
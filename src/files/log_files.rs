@@ -0,0 +1,233 @@
+use files::{create_file_recursively, WholeLines};
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Writer that writes to every file of a set of log files, where the set
+/// can be replaced while a logger holds a clone of the writer.
+///
+/// The global logger can only be installed once per process, so logging
+/// into different files for different simulations, e.g. in batch mode,
+/// requires switching the files below the logger.
+#[derive(Clone)]
+pub struct LogFiles {
+    files: Arc<Mutex<Vec<WholeLines<File>>>>,
+}
+
+impl LogFiles {
+    /// Creates a writer without files, discarding everything written.
+    pub fn new() -> Self {
+        LogFiles {
+            files: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Creates or overwrites the files at the given paths and replaces the
+    /// current files with them.
+    ///
+    /// If any of the files cannot be created, the current files are kept.
+    pub fn open<I>(&self, paths: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        let opened = create_distinct_files(paths)?
+            .into_iter()
+            .map(WholeLines::new)
+            .collect();
+
+        // Previous files are dropped and thereby flushed
+        *self.lock()? = opened;
+        Ok(())
+    }
+
+    /// Closes all files, discarding everything written until the next call
+    /// to `open`.
+    pub fn close(&self) -> io::Result<()> {
+        self.lock()?.clear();
+        Ok(())
+    }
+
+    fn lock(&self) -> io::Result<MutexGuard<Vec<WholeLines<File>>>> {
+        self.files
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Log files poisoned"))
+    }
+}
+
+thread_local! {
+    /// Batch job of the current thread, if any, see `JobLogFiles`.
+    static CURRENT_JOB: Cell<Option<usize>> = Cell::new(None);
+}
+
+/// Writer that writes into one of several sets of log files, depending on
+/// the batch job the logging thread belongs to, e.g. for `--jobs`.
+///
+/// Threads join a job with `enter`, usually in the start handler of the
+/// thread pool of the job. Records logged on threads outside of any job,
+/// e.g. top-level progress of a batch, are discarded.
+#[derive(Clone)]
+pub struct JobLogFiles {
+    jobs: Arc<Vec<LogFiles>>,
+}
+
+impl JobLogFiles {
+    /// Creates a writer for the given number of jobs, with each job
+    /// starting out without files.
+    pub fn new(jobs: usize) -> Self {
+        JobLogFiles {
+            jobs: Arc::new((0..jobs).map(|_| LogFiles::new()).collect()),
+        }
+    }
+
+    /// Log files of the job with the given index.
+    pub fn job(&self, job: usize) -> &LogFiles {
+        &self.jobs[job]
+    }
+
+    /// Makes the calling thread log into the files of the job with the
+    /// given index.
+    pub fn enter(job: usize) {
+        CURRENT_JOB.with(|current| current.set(Some(job)));
+    }
+
+    fn current(&self) -> Option<LogFiles> {
+        CURRENT_JOB.with(|current| current.get().and_then(|job| self.jobs.get(job).cloned()))
+    }
+}
+
+impl Write for JobLogFiles {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.current() {
+            Some(mut files) => files.write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.current() {
+            Some(mut files) => files.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Write for LogFiles {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for file in self.lock()?.iter_mut() {
+            file.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for file in self.lock()?.iter_mut() {
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Creates or overwrites the files at the given paths, skipping paths that
+/// turn out to refer to a file created before, e.g. through a symlink.
+///
+/// Paths of files that do not exist yet cannot be compared before creating
+/// them, so paths are canonicalized again after creating each file.
+pub fn create_distinct_files<I>(paths: I) -> io::Result<Vec<File>>
+where
+    I: IntoIterator<Item = PathBuf>,
+{
+    let mut created = HashSet::new();
+    let mut files = Vec::new();
+
+    for path in paths {
+        let file = create_file_recursively(&path)?;
+        if created.insert(path.canonicalize()?) {
+            files.push(file);
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, read_to_string, remove_dir_all};
+    use std::thread;
+
+    #[test]
+    fn switches_files() {
+        let dir = temp_dir().join("aitios-log-files-test");
+        let first = dir.join("first.log");
+        let second = dir.join("second.log");
+
+        let mut logs = LogFiles::new();
+        writeln!(logs, "discarded").unwrap();
+        logs.open(vec![first.clone()]).unwrap();
+        writeln!(logs, "first").unwrap();
+        logs.open(vec![second.clone()]).unwrap();
+        writeln!(logs, "second").unwrap();
+        logs.close().unwrap();
+
+        assert_eq!("first\n", read_to_string(&first).unwrap());
+        assert_eq!("second\n", read_to_string(&second).unwrap());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_paths_are_created_once() {
+        use std::os::unix::fs::symlink;
+
+        let dir = temp_dir().join("aitios-log-files-symlink-test");
+        if dir.exists() {
+            remove_dir_all(&dir).unwrap();
+        }
+        create_dir_all(&dir).unwrap();
+        let target = dir.join("target.log");
+        let link = dir.join("link.log");
+        // Dangling until the target is created, so canonicalizing before
+        // creation cannot tell the paths are the same
+        symlink(&target, &link).unwrap();
+
+        let files = create_distinct_files(vec![link, target]).unwrap();
+        assert_eq!(1, files.len());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn jobs_log_into_own_files() {
+        let dir = temp_dir().join("aitios-job-log-files-test");
+        let first = dir.join("first.log");
+        let second = dir.join("second.log");
+
+        let logs = JobLogFiles::new(2);
+        logs.job(0).open(vec![first.clone()]).unwrap();
+        logs.job(1).open(vec![second.clone()]).unwrap();
+
+        let mut outside = logs.clone();
+        writeln!(outside, "discarded").unwrap();
+        let threads: Vec<_> = (0..2)
+            .map(|job| {
+                let mut logs = logs.clone();
+                thread::spawn(move || {
+                    JobLogFiles::enter(job);
+                    writeln!(logs, "job {}", job).unwrap();
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        logs.job(0).close().unwrap();
+        logs.job(1).close().unwrap();
+
+        assert_eq!("job 0\n", read_to_string(&first).unwrap());
+        assert_eq!("job 1\n", read_to_string(&second).unwrap());
+        remove_dir_all(&dir).unwrap();
+    }
+}
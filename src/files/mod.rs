@@ -1,7 +1,21 @@
+mod archive;
+mod gzip;
+#[cfg(feature = "cli")]
+mod lines;
+#[cfg(feature = "cli")]
+mod log_files;
 mod recursive;
 mod resolv;
+mod retry;
 mod timestamp;
 
+pub use self::archive::{write_archive, ArchiveFormat};
+pub use self::gzip::{read_to_string_gunzipped, read_to_string_sniffed};
+#[cfg(feature = "cli")]
+pub use self::lines::WholeLines;
+#[cfg(feature = "cli")]
+pub use self::log_files::{create_distinct_files, JobLogFiles, LogFiles};
 pub use self::recursive::create_file_recursively;
 pub use self::resolv::{ResolveError, Resolver};
+pub use self::retry::{write_file_atomically, write_file_retrying, Retry};
 pub use self::timestamp::fs_timestamp;
@@ -61,21 +61,7 @@ impl Resolver {
     /// resolver.add_base(".");
     /// ```
     pub fn add_base<P: AsRef<Path>>(&mut self, base: P) -> Result<(), ResolveError> {
-        let base = base.as_ref();
-
-        if base.as_os_str().is_empty() {
-            return Err(ResolveError::EmptyBasePath);
-        }
-
-        let base = match base.canonicalize() {
-            Ok(base) => base,
-            Err(io) => {
-                return Err(ResolveError::InaccessibleBasePath {
-                    base_path: base.to_path_buf(),
-                    cause: io,
-                })
-            }
-        };
+        let base = canonical_base(base.as_ref())?;
 
         if !self.bases.contains(&base) {
             self.bases.push(base);
@@ -84,6 +70,37 @@ impl Resolver {
         Ok(())
     }
 
+    /// Like `add_base`, but adds the base directory at the front, i.e.
+    /// with the highest priority.
+    ///
+    /// If the canonicalized version is already present, it is moved to the
+    /// front instead of being duplicated.
+    pub fn add_base_first<P: AsRef<Path>>(&mut self, base: P) -> Result<(), ResolveError> {
+        let base = canonical_base(base.as_ref())?;
+
+        self.bases.retain(|existing| existing != &base);
+        self.bases.insert(0, base);
+
+        Ok(())
+    }
+
+    /// Like `resolve`, but looks up relative search paths in the directory
+    /// of the given file first, e.g. for paths referenced in that file.
+    pub fn resolve_next_to<F, P>(&self, file: F, search_path: P) -> Result<PathBuf, ResolveError>
+    where
+        F: AsRef<Path>,
+        P: AsRef<Path>,
+    {
+        match file.as_ref().parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => {
+                let mut resolver = self.clone();
+                resolver.add_base_first(dir)?;
+                resolver.resolve(search_path)
+            }
+            _ => self.resolve(search_path),
+        }
+    }
+
     /// Looks up the given search path in a list of base paths and
     /// returns an absolute, canonicalized path to the referenced
     /// file or directory.
@@ -196,6 +213,19 @@ impl Resolver {
     }
 }
 
+/// Canonicalizes a path for use as a base directory.
+fn canonical_base(base: &Path) -> Result<PathBuf, ResolveError> {
+    if base.as_os_str().is_empty() {
+        return Err(ResolveError::EmptyBasePath);
+    }
+
+    base.canonicalize()
+        .map_err(|io| ResolveError::InaccessibleBasePath {
+            base_path: base.to_path_buf(),
+            cause: io,
+        })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -470,4 +500,32 @@ mod test {
         resolver.add_base(current_dir().unwrap()).unwrap();
         assert_eq!(1, resolver.bases.len());
     }
+
+    #[test]
+    fn first_base_takes_precedence() {
+        let cwd = current_dir().unwrap().canonicalize().unwrap();
+        let mut resolver = Resolver::new();
+        resolver.add_base(".").unwrap();
+        resolver.add_base("src").unwrap();
+        resolver.add_base_first("src").unwrap();
+
+        // Moved to the front instead of duplicated
+        assert_eq!(2, resolver.bases.len());
+        assert_eq!(cwd.join("src"), resolver.bases[0]);
+        assert_eq!(cwd, resolver.bases[1]);
+    }
+
+    #[test]
+    fn resolve_next_to_referencing_file() {
+        let mut resolver = Resolver::new();
+        resolver.add_base(".").unwrap();
+
+        assert!(resolver.resolve("../assets/sky.obj").is_err());
+        assert_eq!(
+            current_dir().unwrap().canonicalize().unwrap().join("tests/assets/sky.obj"),
+            resolver
+                .resolve_next_to("tests/examples/rain.yml", "../assets/sky.obj")
+                .unwrap()
+        );
+    }
 }
@@ -0,0 +1,216 @@
+use files::create_file_recursively;
+use std::fs::{remove_file, rename, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// Policy for retrying I/O operations that failed with errors that are
+/// likely to go away on their own, e.g. on flaky network file systems.
+#[derive(Debug, Clone, Copy)]
+pub struct Retry {
+    /// Maximum number of additional attempts after the first one failed.
+    pub retries: u32,
+    /// Time to wait before each retry.
+    pub delay: Duration,
+}
+
+impl Retry {
+    /// Fails immediately on the first error.
+    pub fn none() -> Self {
+        Self {
+            retries: 0,
+            delay: Duration::from_secs(0),
+        }
+    }
+
+    /// Runs the given operation and runs it again if it failed with a
+    /// transient error, until it succeeds or retries are exhausted.
+    ///
+    /// Errors that are not transient, e.g. a lack of permissions or of
+    /// storage space, are returned immediately.
+    pub fn run<T, F>(&self, what: &str, mut operation: F) -> io::Result<T>
+    where
+        F: FnMut() -> io::Result<T>,
+    {
+        let mut attempt = 0;
+        loop {
+            match operation() {
+                Err(ref err) if attempt < self.retries && is_transient(err) => {
+                    attempt += 1;
+                    warn!(
+                        "{} failed with transient error: {}, retry {} of {}...",
+                        what, err, attempt, self.retries
+                    );
+                    sleep(self.delay);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Checks if an I/O error is worth retrying.
+pub fn is_transient(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::Interrupted
+        | io::ErrorKind::TimedOut
+        | io::ErrorKind::WouldBlock
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::NotConnected
+        | io::ErrorKind::BrokenPipe
+        | io::ErrorKind::UnexpectedEof => true,
+        _ => false,
+    }
+}
+
+/// Creates or overwrites the file at the given path with the given contents,
+/// retrying with the given policy.
+pub fn write_file_retrying<P>(path: P, contents: &[u8], retry: &Retry) -> io::Result<()>
+where
+    P: Into<PathBuf>,
+{
+    let path = path.into();
+    retry.run(&format!("Writing {:?}", path), || {
+        let mut file = create_file_recursively(&path)?;
+        file.write_all(contents)?;
+        file.flush()
+    })
+}
+
+/// Like `write_file_retrying`, but writes to a temporary file next to the
+/// target first and then renames it, so readers never see a partially
+/// written file, even if the process is killed while writing.
+///
+/// The temporary file is synced to disk before renaming. If writing fails,
+/// e.g. because the disk is full, the temporary file is removed and a file
+/// previously written to the path is left untouched.
+pub fn write_file_atomically<P>(path: P, contents: &[u8], retry: &Retry) -> io::Result<()>
+where
+    P: Into<PathBuf>,
+{
+    write_atomically_with(path, retry, |file| file.write_all(contents))
+}
+
+fn write_atomically_with<P, F>(path: P, retry: &Retry, mut write: F) -> io::Result<()>
+where
+    P: Into<PathBuf>,
+    F: FnMut(&mut File) -> io::Result<()>,
+{
+    let path = path.into();
+    let mut tmp_name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let written = retry.run(&format!("Writing {:?}", tmp_path), || {
+        let mut file = create_file_recursively(&tmp_path)?;
+        write(&mut file)?;
+        file.sync_all()
+    });
+    if let Err(err) = written {
+        remove_file(&tmp_path).ok();
+        return Err(err);
+    }
+
+    retry.run(&format!("Renaming {:?}", tmp_path), || rename(&tmp_path, &path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    use std::env::temp_dir;
+    use std::fs::{read, remove_dir_all};
+
+    #[test]
+    fn retries_transient_errors() {
+        let retry = Retry {
+            retries: 2,
+            delay: Duration::from_millis(1),
+        };
+        let attempts = Cell::new(0);
+
+        let result = retry.run("Test", || {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() < 3 {
+                Err(io::Error::new(io::ErrorKind::TimedOut, "flaky"))
+            } else {
+                Ok(attempts.get())
+            }
+        });
+
+        assert_eq!(3, result.unwrap());
+    }
+
+    #[test]
+    fn gives_up_after_retries() {
+        let retry = Retry {
+            retries: 2,
+            delay: Duration::from_millis(1),
+        };
+        let attempts = Cell::new(0);
+
+        let result: io::Result<()> = retry.run("Test", || {
+            attempts.set(attempts.get() + 1);
+            Err(io::Error::new(io::ErrorKind::Interrupted, "flaky"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(3, attempts.get());
+    }
+
+    #[test]
+    fn permanent_errors_fail_immediately() {
+        let retry = Retry {
+            retries: 5,
+            delay: Duration::from_millis(1),
+        };
+        let attempts = Cell::new(0);
+
+        let result: io::Result<()> = retry.run("Test", || {
+            attempts.set(attempts.get() + 1);
+            Err(io::Error::new(io::ErrorKind::PermissionDenied, "nope"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(1, attempts.get());
+    }
+
+    #[test]
+    fn atomic_write_leaves_no_temporary() {
+        let dir = temp_dir().join("aitios-atomic-write-test");
+        let path = dir.join("checkpoint.json");
+
+        write_file_atomically(&path, b"first", &Retry::none()).unwrap();
+        write_file_atomically(&path, b"second", &Retry::none()).unwrap();
+
+        assert_eq!(b"second".to_vec(), read(&path).unwrap());
+        assert!(!dir.join("checkpoint.json.tmp").exists());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_atomic_write_leaves_no_partial_file() {
+        let dir = temp_dir().join("aitios-atomic-write-failure-test");
+        let path = dir.join("density.png");
+        let tmp_path = dir.join("density.png.tmp");
+
+        let failed = write_atomically_with(&path, &Retry::none(), |file| {
+            file.write_all(b"partial")?;
+            Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+        });
+        assert!(failed.is_err());
+        assert!(!path.exists(), "Expected no file with the final name");
+        assert!(!tmp_path.exists(), "Expected temporary file to be removed");
+
+        write_file_atomically(&path, b"complete", &Retry::none()).unwrap();
+        let failed = write_atomically_with(&path, &Retry::none(), |file| {
+            file.write_all(b"partial")?;
+            Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(b"complete".to_vec(), read(&path).unwrap());
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,9 +1,20 @@
+//! Runs aitios weathering simulations described by simulation specs.
+//!
+//! The simplest way to embed a simulation is `run_spec`, which loads a spec
+//! file and runs it to completion. `SimulationBuilder` combines multiple spec
+//! fragments and options into a `SimulationRunner` for more control.
+//!
+//! The command line interface lives in the `app` module, which is only
+//! available with the default `cli` feature. Disable default features to use
+//! the library without the dependencies of the command line tool.
+
 extern crate aitios_asset as asset;
 extern crate aitios_geom as geom;
 extern crate aitios_scene as scene;
 extern crate aitios_sim as sim;
 extern crate aitios_surf as surf;
 extern crate aitios_tex as tex;
+#[cfg(feature = "cli")]
 #[macro_use]
 extern crate clap;
 #[macro_use]
@@ -14,15 +25,49 @@ extern crate chrono;
 #[macro_use]
 extern crate serde_derive;
 extern crate rayon;
+#[macro_use]
 extern crate serde;
+#[macro_use]
+extern crate serde_json;
+#[macro_use]
+extern crate schemars;
 extern crate serde_yaml;
 #[macro_use]
 extern crate log;
+#[cfg(any(feature = "cli", test))]
 extern crate simplelog;
+#[cfg(feature = "cli")]
+extern crate atty;
+extern crate base64;
+extern crate exr;
+#[cfg(feature = "cli")]
+extern crate ctrlc;
+extern crate flate2;
+#[cfg(test)]
+extern crate gltf;
+#[cfg(feature = "cli")]
+extern crate indicatif;
+#[cfg(feature = "cli")]
+extern crate notify;
+extern crate png;
+#[cfg(all(feature = "cli", unix))]
+extern crate libc;
+#[cfg(all(feature = "cli", unix))]
+extern crate syslog;
+extern crate tar;
+extern crate toml;
+extern crate zip;
 
+#[cfg(feature = "cli")]
 pub mod app;
 mod bencher;
 pub mod builder;
 mod files;
+mod run_spec;
 pub mod runner;
 pub mod spec;
+
+pub use builder::{LoadOptions, SimulationBuilder};
+pub use run_spec::run_spec;
+pub use runner::{RunStatus, RunSummary, SimulationRunner};
+pub use spec::*;
@@ -0,0 +1,44 @@
+use builder::{Error, LoadOptions, SimulationBuilder};
+use runner::RunSummary;
+use std::path::Path;
+
+/// Loads the simulation spec at the given path and runs all of its
+/// iterations, writing the outputs specified in the spec.
+///
+/// Relative paths in the spec are resolved relative to the directory of the
+/// spec first and then relative to the working directory, like the command
+/// line tool does. For multiple spec fragments, overrides or interrupting
+/// runs, use `SimulationBuilder` and `SimulationRunner` directly.
+///
+/// ```
+/// use aitios_cli::{run_spec, LoadOptions, RunStatus};
+/// use std::env::{current_dir, temp_dir};
+/// use std::fs::{create_dir_all, write};
+///
+/// // Write all outputs into a temporary directory
+/// let dir = temp_dir().join("aitios-run-spec-example");
+/// create_dir_all(&dir).unwrap();
+/// let tests = current_dir().unwrap().join("tests");
+/// let examples = tests.join("examples");
+/// let spec = dir.join("sky.yml");
+/// write(&spec, format!(
+///     "scenes: [{:?}]\nsurfels_by_material:\n  _: {:?}\nsources: [{:?}]\n\
+///      surfel_distance: 2.0\niterations: 1\n\
+///      effects:\n  - dump_surfels:\n      obj_pattern: {:?}",
+///     tests.join("assets/sky.obj"),
+///     examples.join("concrete.yml"),
+///     examples.join("rain.yml"),
+///     dir.join("surfels-{iteration}.obj"),
+/// )).unwrap();
+///
+/// let summary = run_spec(&spec, LoadOptions::default()).unwrap();
+/// assert_eq!(RunStatus::Completed, summary.status);
+/// assert_eq!(1, summary.iterations);
+/// ```
+pub fn run_spec<P: AsRef<Path>>(path: P, options: LoadOptions) -> Result<RunSummary, Error> {
+    let mut runner = SimulationBuilder::new()
+        .append_spec_fragment_file(path.as_ref())?
+        .options(options)
+        .build()?;
+    Ok(runner.run())
+}
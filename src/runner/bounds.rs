@@ -0,0 +1,168 @@
+use std::f32::{INFINITY, NEG_INFINITY};
+use std::fmt;
+
+/// Axis-aligned bounding box and surface area of the simulated geometry,
+/// useful to check whether the units of the scene fit the spec.
+pub struct Bounds {
+    min: [f32; 3],
+    max: [f32; 3],
+    area: f32,
+    triangle_count: usize,
+    surfel_distance: Option<f32>,
+    surfel_count: usize,
+    surfels_estimated: bool,
+}
+
+impl Bounds {
+    pub fn new() -> Self {
+        Self {
+            min: [INFINITY; 3],
+            max: [NEG_INFINITY; 3],
+            area: 0.0,
+            triangle_count: 0,
+            surfel_distance: None,
+            surfel_count: 0,
+            surfels_estimated: false,
+        }
+    }
+
+    /// Grows the bounds to include the triangle with the given vertex
+    /// positions and adds its area to the total surface area.
+    pub fn add_triangle(&mut self, a: [f32; 3], b: [f32; 3], c: [f32; 3]) {
+        for vertex in [a, b, c].iter() {
+            for axis in 0..3 {
+                self.min[axis] = self.min[axis].min(vertex[axis]);
+                self.max[axis] = self.max[axis].max(vertex[axis]);
+            }
+        }
+
+        let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let ac = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+        let cross = [
+            ab[1] * ac[2] - ab[2] * ac[1],
+            ab[2] * ac[0] - ab[0] * ac[2],
+            ab[0] * ac[1] - ab[1] * ac[0],
+        ];
+        self.area +=
+            0.5 * (cross[0] * cross[0] + cross[1] * cross[1] + cross[2] * cross[2]).sqrt();
+
+        self.triangle_count += 1;
+    }
+
+    /// Sets the surfel parameters to relate to the geometry.
+    pub fn surfels(mut self, surfel_distance: Option<f32>, surfel_count: usize) -> Self {
+        self.surfel_distance = surfel_distance;
+        self.surfel_count = surfel_count;
+        self
+    }
+
+    /// Like `surfels`, but the surfel count is an estimate since the surfels
+    /// have not been generated yet, and is labeled as such.
+    pub fn estimated_surfels(self, surfel_distance: Option<f32>, surfel_count: usize) -> Self {
+        let mut bounds = self.surfels(surfel_distance, surfel_count);
+        bounds.surfels_estimated = true;
+        bounds
+    }
+
+    pub fn min(&self) -> [f32; 3] {
+        self.min
+    }
+
+    pub fn max(&self) -> [f32; 3] {
+        self.max
+    }
+
+    /// Edge lengths of the bounding box, zero if no triangles were added.
+    pub fn extent(&self) -> [f32; 3] {
+        if self.triangle_count == 0 {
+            [0.0; 3]
+        } else {
+            [
+                self.max[0] - self.min[0],
+                self.max[1] - self.min[1],
+                self.max[2] - self.min[2],
+            ]
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        self.area
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangle_count
+    }
+}
+
+impl fmt::Display for Bounds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let extent = self.extent();
+        let diagonal =
+            (extent[0] * extent[0] + extent[1] * extent[1] + extent[2] * extent[2]).sqrt();
+
+        write!(f, "Triangles:          {}\n", self.triangle_count)?;
+        if self.triangle_count > 0 {
+            write!(f, "Bounds min:         {:?}\n", self.min)?;
+            write!(f, "Bounds max:         {:?}\n", self.max)?;
+        }
+        write!(f, "Extent:             {:?}\n", extent)?;
+        write!(f, "Diagonal:           {}\n", diagonal)?;
+        write!(f, "Surface area:       {}\n", self.area)?;
+        match self.surfel_distance {
+            Some(surfel_distance) => {
+                write!(f, "Surfel distance:    {}\n", surfel_distance)?;
+                if diagonal > 0.0 {
+                    write!(
+                        f,
+                        "Relative distance:  {} of diagonal\n",
+                        surfel_distance / diagonal
+                    )?;
+                }
+            }
+            None => write!(f, "Surfel distance:    undefined\n")?,
+        }
+        if self.surfels_estimated {
+            write!(f, "Surfels (estimate): {}", self.surfel_count)
+        } else {
+            write!(f, "Surfels:            {}", self.surfel_count)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unit_triangle() {
+        let mut bounds = Bounds::new();
+        bounds.add_triangle([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 2.0, 0.0]);
+
+        assert_eq!([0.0, 0.0, 0.0], bounds.min());
+        assert_eq!([1.0, 2.0, 0.0], bounds.max());
+        assert_eq!([1.0, 2.0, 0.0], bounds.extent());
+        assert_eq!(1.0, bounds.area());
+        assert_eq!(1, bounds.triangle_count());
+    }
+
+    #[test]
+    fn empty() {
+        let bounds = Bounds::new();
+        assert_eq!([0.0, 0.0, 0.0], bounds.extent());
+        assert_eq!(0.0, bounds.area());
+    }
+
+    #[test]
+    fn display_is_locale_independent() {
+        // Scripts parse this, so numbers must always use a period for
+        // decimals and no digit grouping, whatever LC_NUMERIC says.
+        let mut bounds = Bounds::new().surfels(Some(0.5), 12345);
+        bounds.add_triangle([0.0, 0.0, 0.0], [1500.0, 0.0, 0.0], [0.0, 3.0, 0.0]);
+        let display = format!("{}", bounds);
+
+        assert!(display.contains("Surface area:       2250\n"), "{}", display);
+        assert!(display.contains("Surfel distance:    0.5\n"), "{}", display);
+        assert!(display.contains("Surfels:            12345"), "{}", display);
+        assert!(display.contains("[1500.0, 3.0, 0.0]"), "{}", display);
+    }
+}
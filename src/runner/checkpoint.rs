@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+
+/// Simulation state after a completed iteration, as written every few
+/// iterations with `--checkpoint-every`.
+///
+/// Concentrations are stored per surfel in the order of `substance_names`,
+/// which is the order used by the simulation that wrote the checkpoint.
+///
+/// The random number generators of the tracer are internal to the
+/// simulation and not part of the checkpoint, so a simulation continued
+/// from a checkpoint is statistically equivalent, but not bit-identical,
+/// to an uninterrupted one.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Checkpoint {
+    /// Index of the last completed iteration.
+    pub iteration: u32,
+    /// Date and time the simulation was started, in file system format.
+    pub datetime: String,
+    pub substance_names: Vec<String>,
+    /// Substance concentrations of every surfel.
+    pub surfels: Vec<Vec<f32>>,
+}
+
+/// Path of the checkpoint for the given iteration in the given directory.
+pub fn checkpoint_path<P: AsRef<Path>>(dir: P, datetime: &str, iteration: u32) -> PathBuf {
+    dir.as_ref()
+        .join(format!("checkpoint-{}-iteration-{}.json", datetime, iteration))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn roundtrip() {
+        let checkpoint = Checkpoint {
+            iteration: 4,
+            datetime: String::from("2018-06-01T12_00_00+02_00"),
+            substance_names: vec![String::from("water")],
+            surfels: vec![vec![0.5], vec![0.25]],
+        };
+
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        assert_eq!(checkpoint, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn path_contains_datetime_and_iteration() {
+        assert_eq!(
+            PathBuf::from("out/checkpoint-2018-06-01T12_00_00+02_00-iteration-4.json"),
+            checkpoint_path("out", "2018-06-01T12_00_00+02_00", 4)
+        );
+    }
+}
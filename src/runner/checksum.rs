@@ -0,0 +1,69 @@
+/// Computes a stable 64 bit FNV-1a hash of the substance concentrations of
+/// all surfels, in the given surfel order.
+///
+/// The order of substances within a surfel differs between runs, since it
+/// stems from a hash set, so concentrations are hashed in the order of their
+/// substance names instead. The hash only depends on the exact bits of the
+/// concentrations, not on the platform or compiler version, so checksums of
+/// two runs can be compared directly.
+pub fn concentration_checksum<'a, I>(substance_names: &[String], surfels: I) -> u64
+where
+    I: IntoIterator<Item = &'a [f32]>,
+{
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut by_name: Vec<usize> = (0..substance_names.len()).collect();
+    by_name.sort_by_key(|&idx| &substance_names[idx]);
+
+    let mut hash = OFFSET_BASIS;
+    for concentrations in surfels {
+        for &idx in by_name.iter() {
+            let bits = concentrations[idx].to_bits();
+            for shift in 0..4 {
+                hash ^= u64::from((bits >> (shift * 8)) as u8);
+                hash = hash.wrapping_mul(PRIME);
+            }
+        }
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn independent_of_substance_order() {
+        let water_rust = vec![String::from("water"), String::from("rust")];
+        let rust_water = vec![String::from("rust"), String::from("water")];
+
+        let a: Vec<&[f32]> = vec![&[0.5, 0.25][..], &[1.0, 0.0][..]];
+        let b: Vec<&[f32]> = vec![&[0.25, 0.5][..], &[0.0, 1.0][..]];
+
+        assert_eq!(
+            concentration_checksum(&water_rust, a),
+            concentration_checksum(&rust_water, b)
+        );
+    }
+
+    #[test]
+    fn detects_changes() {
+        let names = vec![String::from("water")];
+        let before: Vec<&[f32]> = vec![&[0.5][..], &[1.0][..]];
+        let changed: Vec<&[f32]> = vec![&[0.5][..], &[0.999][..]];
+        let swapped: Vec<&[f32]> = vec![&[1.0][..], &[0.5][..]];
+
+        let checksum = concentration_checksum(&names, before);
+        assert_ne!(checksum, concentration_checksum(&names, changed));
+        assert_ne!(checksum, concentration_checksum(&names, swapped));
+    }
+
+    #[test]
+    fn stable_across_builds() {
+        let names = vec![String::from("water")];
+        let surfels: Vec<&[f32]> = vec![&[1.0][..], &[0.5][..]];
+        assert_eq!(0x097a_58ee_2da2_e4f5, concentration_checksum(&names, surfels));
+    }
+}
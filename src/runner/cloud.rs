@@ -0,0 +1,123 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+/// File format of a surfel point cloud, inferred from the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloudFormat {
+    /// ASCII PLY with one float property per substance.
+    Ply,
+    /// Whitespace separated columns, one line per surfel.
+    Xyz,
+}
+
+impl CloudFormat {
+    /// PLY for `.ply` files, XYZ for everything else.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("ply") => CloudFormat::Ply,
+            _ => CloudFormat::Xyz,
+        }
+    }
+}
+
+/// A single surfel as written into a point cloud.
+pub struct CloudPoint<'a> {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub substances: &'a [f32],
+}
+
+/// Writes one point per surfel with position, normal and the concentration
+/// of every substance, in the order of the given substance names.
+pub fn write_cloud<'a, W, I>(
+    sink: &mut W,
+    format: CloudFormat,
+    substance_names: &[String],
+    points: I,
+) -> io::Result<()>
+where
+    W: Write,
+    I: ExactSizeIterator<Item = CloudPoint<'a>>,
+{
+    match format {
+        CloudFormat::Ply => {
+            writeln!(sink, "ply")?;
+            writeln!(sink, "format ascii 1.0")?;
+            writeln!(sink, "element vertex {}", points.len())?;
+            for axis in ["x", "y", "z", "nx", "ny", "nz"].iter() {
+                writeln!(sink, "property float {}", axis)?;
+            }
+            for name in substance_names {
+                writeln!(sink, "property float {}", property_name(name))?;
+            }
+            writeln!(sink, "end_header")?;
+        }
+        CloudFormat::Xyz => {
+            write!(sink, "# x y z nx ny nz")?;
+            for name in substance_names {
+                write!(sink, " {}", property_name(name))?;
+            }
+            writeln!(sink)?;
+        }
+    }
+
+    for point in points {
+        let p = point.position;
+        let n = point.normal;
+        write!(sink, "{} {} {} {} {} {}", p[0], p[1], p[2], n[0], n[1], n[2])?;
+        for concentration in point.substances {
+            write!(sink, " {}", concentration)?;
+        }
+        writeln!(sink)?;
+    }
+
+    Ok(())
+}
+
+/// Makes a substance name usable as a PLY property or column name by
+/// replacing whitespace.
+fn property_name(substance: &str) -> String {
+    substance
+        .chars()
+        .map(|c| if c.is_whitespace() { '_' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ply_with_substances() {
+        let names = vec![String::from("water"), String::from("dry rust")];
+        let wet = [1.0, 0.0];
+        let rusty = [0.25, 0.5];
+        let points = vec![
+            CloudPoint {
+                position: [0.0, 1.0, 2.0],
+                normal: [0.0, 0.0, 1.0],
+                substances: &wet,
+            },
+            CloudPoint {
+                position: [-1.5, 0.0, 0.0],
+                normal: [1.0, 0.0, 0.0],
+                substances: &rusty,
+            },
+        ];
+
+        let mut ply = Vec::new();
+        write_cloud(&mut ply, CloudFormat::Ply, &names, points.into_iter()).unwrap();
+        let ply = String::from_utf8(ply).unwrap();
+
+        assert!(ply.starts_with("ply\nformat ascii 1.0\nelement vertex 2\n"));
+        assert!(ply.contains("property float water\nproperty float dry_rust\nend_header\n"));
+        assert!(ply.ends_with("0 1 2 0 0 1 1 0\n-1.5 0 0 1 0 0 0.25 0.5\n"));
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(CloudFormat::Ply, CloudFormat::from_path("surfels.PLY"));
+        assert_eq!(CloudFormat::Xyz, CloudFormat::from_path("surfels.xyz"));
+        assert_eq!(CloudFormat::Xyz, CloudFormat::from_path("surfels"));
+    }
+}
@@ -0,0 +1,218 @@
+use exr::error::Result as ExrResult;
+use exr::prelude::{Image, SpecificChannels, Vec2, WritableImage};
+use std::io::Cursor;
+use std::str::FromStr;
+
+/// Offset of surfel distances when weighting them for smooth filtering, so
+/// surfels right at the texel do not get infinite weight.
+const DISTANCE_EPSILON: f32 = 1e-6;
+
+/// Names of the filters that can be chosen with `--bake-filter`.
+pub const BAKE_FILTER_NAMES: [&str; 3] = ["nearest", "linear", "gaussian"];
+
+/// Kernel that reconstructs the concentration of a texel from the surfels
+/// associated with it in a surfel table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BakeFilter {
+    /// Concentration of the nearest surfel, as with `flat_filtering`.
+    Nearest,
+    /// Average weighted by inverse distance, the default without
+    /// `flat_filtering`.
+    InverseDistance,
+    /// Average weighted by one minus the distance relative to the radius,
+    /// ignoring surfels outside of the radius.
+    Linear,
+    /// Average weighted by a gaussian with a third of the radius as
+    /// standard deviation, ignoring surfels outside of the radius.
+    Gaussian,
+}
+
+impl BakeFilter {
+    /// Weight of a surfel at the given distance from the texel, for all but
+    /// nearest filtering.
+    fn weight(&self, distance: f32, radius: f32) -> f32 {
+        match *self {
+            BakeFilter::Nearest | BakeFilter::InverseDistance => {
+                (distance + DISTANCE_EPSILON).recip()
+            }
+            BakeFilter::Linear => (1.0 - distance / radius).max(0.0),
+            BakeFilter::Gaussian if distance < radius => {
+                let sigma = radius / 3.0;
+                (-0.5 * (distance / sigma) * (distance / sigma)).exp()
+            }
+            BakeFilter::Gaussian => 0.0,
+        }
+    }
+}
+
+impl FromStr for BakeFilter {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "nearest" => Ok(BakeFilter::Nearest),
+            "linear" => Ok(BakeFilter::Linear),
+            "gaussian" => Ok(BakeFilter::Gaussian),
+            _ => Err(format!(
+                "Unknown bake filter {:?}, expected one of: {}",
+                name,
+                BAKE_FILTER_NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// Checks if the given texture path should be written as OpenEXR with float
+/// concentrations rather than as a quantized PNG.
+pub fn is_exr_path(tex_filename: &str) -> bool {
+    tex_filename.to_lowercase().ends_with(".exr")
+}
+
+/// Concentrations for every texel of a surfel table, given the distances
+/// and indexes of the surfels associated with each texel, and a function
+/// that looks up the concentration of a surfel by its index.
+///
+/// Density textures are filtered with this in every format, so that OpenEXR
+/// and PNG textures of the same run agree.
+///
+/// With nearest filtering, each texel gets the concentration of its nearest
+/// surfel, as aitios-tex did for flat PNG textures, otherwise the
+/// concentrations of its surfels are averaged with the weights of the
+/// filter. The radius only affects linear and gaussian filters, texels
+/// without surfels inside of the radius fall back to their nearest surfel.
+/// Texels without surfels, e.g. those outside of UV islands, get a
+/// concentration of zero.
+pub fn texel_concentrations<F>(
+    table: &[Vec<(f32, usize)>],
+    filter: BakeFilter,
+    radius: f32,
+    concentration: F,
+) -> Vec<f32>
+where
+    F: Fn(usize) -> f32,
+{
+    let nearest = |surfels: &Vec<(f32, usize)>| {
+        surfels
+            .iter()
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+            .map(|&(_, idx)| concentration(idx))
+            .unwrap_or(0.0)
+    };
+
+    table
+        .iter()
+        .map(|surfels| {
+            if filter == BakeFilter::Nearest {
+                return nearest(surfels);
+            }
+
+            let (weighted, weights) = surfels.iter().fold(
+                (0.0, 0.0),
+                |(weighted, weights), &(distance, idx)| {
+                    let weight = filter.weight(distance, radius);
+                    (weighted + weight * concentration(idx), weights + weight)
+                },
+            );
+            if weights > 0.0 {
+                weighted / weights
+            } else {
+                nearest(surfels)
+            }
+        })
+        .collect()
+}
+
+/// Encodes the given concentrations, one per texel in rows from the top, as
+/// OpenEXR with 32 bit float RGB channels that all hold the concentration,
+/// so it shows as a grayscale image without any quantization.
+pub fn encode_exr(width: usize, height: usize, concentrations: &[f32]) -> ExrResult<Vec<u8>> {
+    let channels = SpecificChannels::rgb(|Vec2(x, y): Vec2<usize>| {
+        let concentration = concentrations[y * width + x];
+        (concentration, concentration, concentration)
+    });
+
+    let mut exr = Cursor::new(Vec::new());
+    Image::from_channels((width, height), channels)
+        .write()
+        .to_buffered(&mut exr)?;
+    Ok(exr.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use exr::prelude::read_first_rgba_layer_from_file;
+    use std::env::temp_dir;
+    use std::fs::{remove_file, write};
+
+    #[test]
+    fn concentrations_round_trip() {
+        let concentrations = vec![0.0, 0.123_456_7, 0.5, 1.0, 0.999_999, 1e-5];
+        let exr = encode_exr(3, 2, &concentrations).unwrap();
+
+        let path = temp_dir().join("aitios-exr-round-trip-test.exr");
+        write(&path, &exr).unwrap();
+        let image = read_first_rgba_layer_from_file(
+            &path,
+            |resolution, _| vec![0.0; resolution.width() * resolution.height()],
+            |texels: &mut Vec<f32>, Vec2(x, y), (r, _, _, _): (f32, f32, f32, f32)| {
+                texels[y * 3 + x] = r
+            },
+        ).unwrap();
+        remove_file(&path).unwrap();
+
+        let decoded = image.layer_data.channel_data.pixels;
+        for (expected, decoded) in concentrations.iter().zip(decoded.iter()) {
+            assert!((expected - decoded).abs() < 1e-7, "{} != {}", expected, decoded);
+        }
+    }
+
+    #[test]
+    fn flat_and_smooth_filtering() {
+        let table = vec![vec![(0.5, 1), (0.1, 0)], vec![(1.0, 0), (1.0, 1)], Vec::new()];
+        let concentration = |idx: usize| [0.2, 0.8][idx];
+
+        assert_eq!(
+            vec![0.2, 0.2, 0.0],
+            texel_concentrations(&table, BakeFilter::Nearest, 1.0, concentration)
+        );
+
+        let smooth =
+            texel_concentrations(&table, BakeFilter::InverseDistance, 1.0, concentration);
+        assert!(smooth[0] > 0.2 && smooth[0] < 0.5, "Expected nearer surfel to weigh more");
+        assert!((smooth[1] - 0.5).abs() < 1e-6);
+        assert_eq!(0.0, smooth[2]);
+    }
+
+    #[test]
+    fn linear_and_gaussian_filtering_within_radius() {
+        let table = vec![vec![(0.5, 1), (0.1, 0)], vec![(2.0, 1), (1.5, 0)], Vec::new()];
+        let concentration = |idx: usize| [0.2, 0.8][idx];
+
+        for &filter in &[BakeFilter::Linear, BakeFilter::Gaussian] {
+            let baked = texel_concentrations(&table, filter, 1.0, concentration);
+            assert!(baked[0] > 0.2 && baked[0] < 0.5, "Expected nearer surfel to weigh more");
+            assert_eq!(0.2, baked[1], "Expected nearest surfel outside of radius");
+            assert_eq!(0.0, baked[2]);
+
+            let wide = texel_concentrations(&table, filter, 100.0, concentration);
+            assert!(wide[1] > 0.2 && wide[1] < 0.8, "Expected both surfels within radius");
+        }
+    }
+
+    #[test]
+    fn bake_filter_names() {
+        for name in BAKE_FILTER_NAMES.iter() {
+            assert!(name.parse::<BakeFilter>().is_ok());
+        }
+        let unknown = "smooth".parse::<BakeFilter>().unwrap_err();
+        assert!(unknown.contains("nearest, linear, gaussian"), "{}", unknown);
+    }
+
+    #[test]
+    fn exr_extension() {
+        assert!(is_exr_path("textures/rust.exr"));
+        assert!(is_exr_path("textures/rust.EXR"));
+        assert!(!is_exr_path("textures/rust.png"));
+    }
+}
@@ -0,0 +1,408 @@
+use base64;
+use geom::TupleTriangle;
+use scene::{Entity, Mesh};
+use serde_json::{self, Map, Value};
+use std::collections::HashMap;
+use std::fs::read;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tex::{self, DynamicImage, FilterType, GenericImage, Rgba};
+
+const ARRAY_BUFFER: u32 = 34962;
+const FLOAT: u32 = 5126;
+const TRIANGLES: u32 = 4;
+
+const GLB_MAGIC: u32 = 0x4654_6c67;
+const GLB_CHUNK_JSON: u32 = 0x4e4f_534a;
+const GLB_CHUNK_BIN: u32 = 0x004e_4942;
+
+/// File format of an exported glTF 2.0 scene, inferred from the file
+/// extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GltfFormat {
+    /// JSON with geometry and textures embedded as a base64 data URI.
+    Gltf,
+    /// Binary container with geometry and textures in the binary chunk.
+    Glb,
+}
+
+impl GltfFormat {
+    /// glTF for `.gltf` and binary glTF for `.glb` files, `None` for all
+    /// other extensions.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("gltf") => Some(GltfFormat::Gltf),
+            Some(ext) if ext.eq_ignore_ascii_case("glb") => Some(GltfFormat::Glb),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the given entities as a self-contained glTF 2.0 scene with one
+/// node per entity, embedding all textures of their materials.
+///
+/// Diffuse color and normal maps become base color and normal textures.
+/// Metallic and roughness maps are combined into a single texture, since
+/// glTF expects metallicity in the blue and roughness in the green channel
+/// of the same image. Displacement maps have no equivalent in glTF and are
+/// not exported.
+pub fn write_gltf<'a, W, E>(sink: &mut W, format: GltfFormat, entities: E) -> io::Result<()>
+where
+    W: Write,
+    E: IntoIterator<Item = &'a Entity>,
+{
+    let mut document = Document::default();
+    for entity in entities {
+        document.add_entity(entity)?;
+    }
+
+    match format {
+        GltfFormat::Gltf => {
+            let uri = format!(
+                "data:application/octet-stream;base64,{}",
+                base64::encode(&document.buffer)
+            );
+            let json = document.json(Some(uri));
+            serde_json::to_writer_pretty(sink, &json).map_err(io::Error::from)
+        }
+        GltfFormat::Glb => {
+            let mut json = serde_json::to_vec(&document.json(None)).map_err(io::Error::from)?;
+            pad(&mut json, b' ');
+            let mut bin = document.buffer;
+            pad(&mut bin, 0);
+
+            let mut length = 12 + 8 + json.len();
+            if !bin.is_empty() {
+                length += 8 + bin.len();
+            }
+
+            write_u32(sink, GLB_MAGIC)?;
+            write_u32(sink, 2)?;
+            write_u32(sink, length as u32)?;
+            write_u32(sink, json.len() as u32)?;
+            write_u32(sink, GLB_CHUNK_JSON)?;
+            sink.write_all(&json)?;
+            if !bin.is_empty() {
+                write_u32(sink, bin.len() as u32)?;
+                write_u32(sink, GLB_CHUNK_BIN)?;
+                sink.write_all(&bin)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Top-level arrays of the glTF document under construction, along with
+/// the single binary buffer that all buffer views refer to.
+#[derive(Default)]
+struct Document {
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+    images: Vec<Value>,
+    textures: Vec<Value>,
+    materials: Vec<Value>,
+    meshes: Vec<Value>,
+    nodes: Vec<Value>,
+    /// Texture indexes of already embedded images, by path.
+    textures_by_path: HashMap<PathBuf, usize>,
+}
+
+impl Document {
+    fn add_entity(&mut self, entity: &Entity) -> io::Result<()> {
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut texcoords = Vec::new();
+        for TupleTriangle(v0, v1, v2) in entity.mesh.triangles() {
+            for v in [v0, v1, v2].iter() {
+                positions.extend_from_slice(&[v.position.x, v.position.y, v.position.z]);
+                normals.extend_from_slice(&[v.normal.x, v.normal.y, v.normal.z]);
+                // OBJ has the origin of texture space at the bottom, glTF at the top
+                texcoords.extend_from_slice(&[v.texcoords.x, 1.0 - v.texcoords.y]);
+            }
+        }
+
+        let position = self.add_accessor(&positions, "VEC3", true);
+        let normal = self.add_accessor(&normals, "VEC3", false);
+        let texcoord = self.add_accessor(&texcoords, "VEC2", false);
+
+        let material = &entity.material;
+        let mut pbr = Map::new();
+        if let Some(diffuse) = material.diffuse_color_map() {
+            pbr.insert(
+                String::from("baseColorTexture"),
+                json!({ "index": self.add_texture(diffuse)? }),
+            );
+        }
+        match self.add_metallic_roughness(material.metallic_map(), material.roughness_map())? {
+            Some(texture) => {
+                pbr.insert(
+                    String::from("metallicRoughnessTexture"),
+                    json!({ "index": texture }),
+                );
+            }
+            None => {
+                // Dielectric unless specified otherwise
+                pbr.insert(String::from("metallicFactor"), json!(0.0));
+            }
+        }
+
+        let mut gltf_material = Map::new();
+        gltf_material.insert(String::from("name"), json!(material.name()));
+        gltf_material.insert(String::from("pbrMetallicRoughness"), Value::Object(pbr));
+        if let Some(normal_map) = material.normal_map() {
+            gltf_material.insert(
+                String::from("normalTexture"),
+                json!({ "index": self.add_texture(normal_map)? }),
+            );
+        }
+        self.materials.push(Value::Object(gltf_material));
+
+        self.meshes.push(json!({
+            "name": entity.name,
+            "primitives": [{
+                "attributes": {
+                    "POSITION": position,
+                    "NORMAL": normal,
+                    "TEXCOORD_0": texcoord,
+                },
+                "material": self.materials.len() - 1,
+                "mode": TRIANGLES,
+            }],
+        }));
+        self.nodes.push(json!({
+            "name": entity.name,
+            "mesh": self.meshes.len() - 1,
+        }));
+
+        Ok(())
+    }
+
+    /// Appends float vertex attributes with the given number of components
+    /// per element and returns the index of the accessor.
+    fn add_accessor(&mut self, data: &[f32], kind: &str, bounds: bool) -> usize {
+        let components = if kind == "VEC3" { 3 } else { 2 };
+
+        let mut bytes = Vec::with_capacity(data.len() * 4);
+        for value in data {
+            let bits = value.to_bits();
+            for shift in 0..4 {
+                bytes.push((bits >> (shift * 8)) as u8);
+            }
+        }
+        let view = self.add_buffer_view(&bytes, Some(ARRAY_BUFFER));
+
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": data.len() / components,
+            "type": kind,
+        });
+        if bounds && !data.is_empty() {
+            let mut min = vec![::std::f32::INFINITY; components];
+            let mut max = vec![::std::f32::NEG_INFINITY; components];
+            for element in data.chunks(components) {
+                for (idx, &value) in element.iter().enumerate() {
+                    min[idx] = min[idx].min(value);
+                    max[idx] = max[idx].max(value);
+                }
+            }
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn add_buffer_view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        // Accessors of floats must be aligned to four bytes
+        pad(&mut self.buffer, 0);
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.buffer.len(),
+            "byteLength": bytes.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.buffer.extend_from_slice(bytes);
+
+        self.buffer_views.push(view);
+        self.buffer_views.len() - 1
+    }
+
+    /// Embeds the image at the given path, if not embedded before, and
+    /// returns the index of its texture. PNG and JPEG files are embedded as
+    /// they are, other formats are converted to PNG.
+    fn add_texture(&mut self, path: &Path) -> io::Result<usize> {
+        if let Some(&texture) = self.textures_by_path.get(path) {
+            return Ok(texture);
+        }
+
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let (bytes, mime_type) = match extension.as_str() {
+            "png" => (read(path)?, "image/png"),
+            "jpg" | "jpeg" => (read(path)?, "image/jpeg"),
+            _ => (encode_png(&open_image(path)?)?, "image/png"),
+        };
+
+        let texture = self.add_image(&bytes, mime_type);
+        self.textures_by_path.insert(path.to_path_buf(), texture);
+        Ok(texture)
+    }
+
+    fn add_image(&mut self, bytes: &[u8], mime_type: &str) -> usize {
+        let view = self.add_buffer_view(bytes, None);
+        self.images.push(json!({
+            "bufferView": view,
+            "mimeType": mime_type,
+        }));
+        self.textures.push(json!({ "source": self.images.len() - 1 }));
+        self.textures.len() - 1
+    }
+
+    /// Combines metallic and roughness maps into a single texture with
+    /// metallicity in blue and roughness in green, in the size of the larger
+    /// one. A missing metallic map is treated as non-metal, a missing
+    /// roughness map as fully rough.
+    fn add_metallic_roughness(
+        &mut self,
+        metallic: Option<&PathBuf>,
+        roughness: Option<&PathBuf>,
+    ) -> io::Result<Option<usize>> {
+        if metallic.is_none() && roughness.is_none() {
+            return Ok(None);
+        }
+
+        let metallic = match metallic {
+            Some(path) => Some(open_image(path)?),
+            None => None,
+        };
+        let roughness = match roughness {
+            Some(path) => Some(open_image(path)?),
+            None => None,
+        };
+        let (width, height) = metallic
+            .iter()
+            .chain(roughness.iter())
+            .map(|i| i.dimensions())
+            .max()
+            .unwrap();
+
+        let channel = |image: &Option<DynamicImage>| {
+            image
+                .as_ref()
+                .map(|i| i.resize_exact(width, height, FilterType::Triangle).to_luma())
+        };
+        let metallic_channel = channel(&metallic);
+        let roughness_channel = channel(&roughness);
+
+        // Every pixel is overwritten, the original map only provides the size
+        let mut combined = metallic
+            .or(roughness)
+            .unwrap()
+            .resize_exact(width, height, FilterType::Nearest)
+            .to_rgba();
+        for y in 0..height {
+            for x in 0..width {
+                let metal = metallic_channel
+                    .as_ref()
+                    .map(|m| m.get_pixel(x, y).data[0])
+                    .unwrap_or(0);
+                let rough = roughness_channel
+                    .as_ref()
+                    .map(|r| r.get_pixel(x, y).data[0])
+                    .unwrap_or(255);
+                combined.put_pixel(
+                    x,
+                    y,
+                    Rgba {
+                        data: [0, rough, metal, 255],
+                    },
+                );
+            }
+        }
+
+        let png = encode_png(&tex::ImageRgba8(combined))?;
+        Ok(Some(self.add_image(&png, "image/png")))
+    }
+
+    fn json(&self, buffer_uri: Option<String>) -> Value {
+        let mut root = Map::new();
+        root.insert(
+            String::from("asset"),
+            json!({ "version": "2.0", "generator": "aitios" }),
+        );
+        root.insert(String::from("scene"), json!(0));
+        root.insert(
+            String::from("scenes"),
+            json!([{ "nodes": (0..self.nodes.len()).collect::<Vec<_>>() }]),
+        );
+
+        // glTF forbids empty top-level arrays
+        for &(name, values) in [
+            ("nodes", &self.nodes),
+            ("meshes", &self.meshes),
+            ("materials", &self.materials),
+            ("textures", &self.textures),
+            ("images", &self.images),
+            ("accessors", &self.accessors),
+            ("bufferViews", &self.buffer_views),
+        ].iter()
+        {
+            if !values.is_empty() {
+                root.insert(String::from(name), Value::Array(values.clone()));
+            }
+        }
+
+        if !self.buffer.is_empty() {
+            let mut buffer = json!({ "byteLength": self.buffer.len() });
+            if let Some(uri) = buffer_uri {
+                buffer["uri"] = json!(uri);
+            }
+            root.insert(String::from("buffers"), json!([buffer]));
+        }
+
+        Value::Object(root)
+    }
+}
+
+fn open_image(path: &Path) -> io::Result<DynamicImage> {
+    tex::open(path).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Texture {:?} could not be loaded: {}", path, e),
+        )
+    })
+}
+
+fn encode_png(image: &DynamicImage) -> io::Result<Vec<u8>> {
+    let mut png = Vec::new();
+    image.write_to(&mut png, tex::PNG).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Texture could not be encoded: {}", e),
+        )
+    })?;
+    Ok(png)
+}
+
+/// Pads the given bytes with the given value to a multiple of four bytes.
+fn pad(bytes: &mut Vec<u8>, with: u8) {
+    while bytes.len() % 4 != 0 {
+        bytes.push(with);
+    }
+}
+
+fn write_u32<W: Write>(sink: &mut W, value: u32) -> io::Result<()> {
+    sink.write_all(&[
+        value as u8,
+        (value >> 8) as u8,
+        (value >> 16) as u8,
+        (value >> 24) as u8,
+    ])
+}
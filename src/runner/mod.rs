@@ -1,4 +1,24 @@
+mod bounds;
+mod checkpoint;
+mod checksum;
+mod cloud;
+mod exr_export;
+mod gltf_export;
+mod plan;
+mod png_export;
+mod profile;
 mod runner;
+mod snapshot;
+mod stats;
+mod summary;
 mod surfel_table_cache;
 
-pub use self::runner::SimulationRunner;
+pub use self::bounds::Bounds;
+pub use self::checkpoint::{checkpoint_path, Checkpoint};
+pub use self::exr_export::{BakeFilter, BAKE_FILTER_NAMES};
+pub use self::plan::{Plan, PlannedOutput, PlannedStage, SURFEL_BYTES};
+pub use self::profile::{PhaseTime, PhaseTimer, Profile, SharedProfile};
+pub use self::runner::{RunStatus, SimulationRunner};
+pub use self::snapshot::snapshot_effects;
+pub use self::stats::{substance_stats, SubstanceStats};
+pub use self::summary::RunSummary;
@@ -0,0 +1,116 @@
+use std::fmt;
+
+/// Rough time to trace a single gammaton and transport its substances.
+pub const SECONDS_PER_GAMMATON: f64 = 2e-6;
+/// Rough time to filter the surfels of a single texel for one substance.
+pub const SECONDS_PER_TEXEL: f64 = 1e-7;
+/// Rough size of a surfel without its substance concentrations.
+pub const SURFEL_BYTES: u64 = 96;
+/// Rough size of a vertex in an exported OBJ file.
+pub const OBJ_BYTES_PER_VERTEX: u64 = 120;
+/// Rough size of a material in an exported MTL file.
+pub const MTL_BYTES_PER_ENTITY: u64 = 200;
+
+/// Report of what a simulation will do when run, with estimates for time,
+/// memory and output sizes derived from counts and simple models, as
+/// printed by `--plan`.
+///
+/// The estimates are meant to tell apart a run of minutes from one of days,
+/// they can be off by a constant factor depending on the machine.
+#[derive(Debug, Serialize)]
+pub struct Plan {
+    pub stages: Vec<PlannedStage>,
+    pub estimated_seconds: f64,
+    pub estimated_peak_memory_bytes: u64,
+    pub outputs: Vec<PlannedOutput>,
+    pub estimated_output_bytes: u64,
+}
+
+/// Tracing or texture synthesis in a specific iteration.
+#[derive(Debug, Serialize)]
+pub struct PlannedStage {
+    pub iteration: u32,
+    pub stage: String,
+    pub estimated_seconds: f64,
+}
+
+/// A file that will be written, with uncompressed size for textures.
+#[derive(Debug, Serialize)]
+pub struct PlannedOutput {
+    pub path: String,
+    pub estimated_bytes: u64,
+}
+
+impl Plan {
+    pub fn new() -> Self {
+        Plan {
+            stages: Vec::new(),
+            estimated_seconds: 0.0,
+            estimated_peak_memory_bytes: 0,
+            outputs: Vec::new(),
+            estimated_output_bytes: 0,
+        }
+    }
+
+    pub fn add_stage(&mut self, iteration: u32, stage: &str, estimated_seconds: f64) {
+        self.estimated_seconds += estimated_seconds;
+        self.stages.push(PlannedStage {
+            iteration,
+            stage: String::from(stage),
+            estimated_seconds,
+        });
+    }
+
+    pub fn add_output(&mut self, path: String, estimated_bytes: u64) {
+        self.estimated_output_bytes += estimated_bytes;
+        self.outputs.push(PlannedOutput {
+            path,
+            estimated_bytes,
+        });
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Stages:\n")?;
+        for stage in self.stages.iter() {
+            write!(
+                f,
+                "  Iteration {:>4} {:<10} {:>12.3}s\n",
+                stage.iteration, stage.stage, stage.estimated_seconds
+            )?;
+        }
+        write!(f, "Outputs:\n")?;
+        for output in self.outputs.iter() {
+            write!(f, "  {:>12} bytes  {}\n", output.estimated_bytes, output.path)?;
+        }
+        write!(f, "Estimated time:     {:.3}s\n", self.estimated_seconds)?;
+        write!(
+            f,
+            "Peak memory:        {} bytes\n",
+            self.estimated_peak_memory_bytes
+        )?;
+        write!(f, "Output size:        {} bytes", self.estimated_output_bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sums_up_stages_and_outputs() {
+        let mut plan = Plan::new();
+        plan.add_stage(0, "synthesis", 1.5);
+        plan.add_stage(1, "tracing", 2.0);
+        plan.add_output(String::from("a.png"), 400);
+        plan.add_output(String::from("a.obj"), 100);
+
+        assert_eq!(3.5, plan.estimated_seconds);
+        assert_eq!(500, plan.estimated_output_bytes);
+
+        let display = format!("{}", plan);
+        assert!(display.contains("Iteration    1 tracing           2.000s\n"), "{}", display);
+        assert!(display.contains("           400 bytes  a.png\n"), "{}", display);
+    }
+}
@@ -0,0 +1,102 @@
+use png::{BitDepth, ColorType, Encoder, EncodingError};
+
+/// Gray value of each texel for its concentration, white for none and black
+/// for a concentration of one or more, like density colors in aitios-tex.
+fn density_gray(concentration: f32, max: f32) -> f32 {
+    ((1.0 - concentration.max(0.0).min(1.0)) * max).round()
+}
+
+/// Encodes the given concentrations, one per texel in rows from the top, as
+/// an RGBA PNG with grayscale colors from white for no concentration to
+/// black for a concentration of one.
+///
+/// With 16 bits per channel, concentrations are quantized to the full range
+/// of 65536 gray values instead of 256, which avoids visible banding.
+///
+/// # Panics
+/// Panics if the bit depth is not 8 or 16, which the builder checks.
+pub fn encode_density_png(
+    width: usize,
+    height: usize,
+    concentrations: &[f32],
+    bit_depth: u8,
+) -> Result<Vec<u8>, EncodingError> {
+    let mut data = Vec::with_capacity(width * height * 4 * usize::from(bit_depth / 8));
+    for &concentration in concentrations.iter() {
+        match bit_depth {
+            8 => {
+                let gray = density_gray(concentration, 255.0) as u8;
+                data.extend_from_slice(&[gray, gray, gray, 255]);
+            }
+            16 => {
+                // PNG stores 16 bit samples in big endian
+                let gray = density_gray(concentration, 65535.0) as u16;
+                let (high, low) = ((gray >> 8) as u8, gray as u8);
+                data.extend_from_slice(&[high, low, high, low, high, low, 255, 255]);
+            }
+            _ => panic!("Unsupported PNG bit depth {}", bit_depth),
+        }
+    }
+
+    let mut png = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut png, width as u32, height as u32);
+        encoder.set_color(ColorType::RGBA);
+        encoder.set_depth(if bit_depth == 16 {
+            BitDepth::Sixteen
+        } else {
+            BitDepth::Eight
+        });
+        // Writes the end of the image when dropped
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+    }
+    Ok(png)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use png::Decoder;
+
+    #[test]
+    fn concentrations_map_to_gray() {
+        assert_eq!(255.0, density_gray(0.0, 255.0));
+        assert_eq!(0.0, density_gray(1.0, 255.0));
+        assert_eq!(128.0, density_gray(0.498, 255.0));
+        assert_eq!(255.0, density_gray(-0.5, 255.0));
+        assert_eq!(0.0, density_gray(3.0, 255.0));
+    }
+
+    #[test]
+    fn sixteen_bits_use_full_range() {
+        let concentrations = vec![0.0, 0.2, 0.2001, 1.0];
+        let png = encode_density_png(2, 2, &concentrations, 16).unwrap();
+
+        let (info, mut reader) = Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut texels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut texels).unwrap();
+
+        assert_eq!(BitDepth::Sixteen, info.bit_depth);
+        let grays: Vec<u16> = texels
+            .chunks(8)
+            .map(|texel| (u16::from(texel[0]) << 8) | u16::from(texel[1]))
+            .collect();
+        assert_eq!(65535, grays[0]);
+        // Too close to tell apart with 8 bits
+        assert!(grays[1] > grays[2], "{:?}", grays);
+        assert_eq!(0, grays[3]);
+    }
+
+    #[test]
+    fn eight_bits_by_default() {
+        let png = encode_density_png(1, 1, &[0.25], 8).unwrap();
+
+        let (info, mut reader) = Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut texels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut texels).unwrap();
+
+        assert_eq!(BitDepth::Eight, info.bit_depth);
+        assert_eq!(vec![191, 191, 191, 255], texels);
+    }
+}
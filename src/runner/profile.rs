@@ -0,0 +1,123 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Profile shared between the builder, the runner and running timers.
+pub type SharedProfile = Option<Rc<RefCell<Profile>>>;
+
+/// Accumulated wall-clock time of the phases of a simulation, e.g. mesh
+/// loading or tracing, in the order the phases were first entered, as
+/// logged by `--profile`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Profile {
+    pub phases: Vec<PhaseTime>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseTime {
+    pub phase: String,
+    /// How often the phase was entered, e.g. once per iteration for tracing.
+    pub count: u32,
+    pub seconds: f64,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Profile { phases: Vec::new() }
+    }
+
+    /// Adds the given time to the phase with the given name.
+    pub fn record(&mut self, phase: &str, elapsed: Duration) {
+        let seconds = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9;
+        match self.phases.iter_mut().find(|p| p.phase == phase) {
+            Some(time) => {
+                time.count += 1;
+                time.seconds += seconds;
+            }
+            None => self.phases.push(PhaseTime {
+                phase: String::from(phase),
+                count: 1,
+                seconds,
+            }),
+        }
+    }
+
+    pub fn total_seconds(&self) -> f64 {
+        self.phases.iter().map(|p| p.seconds).sum()
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.total_seconds();
+        write!(f, "{:<20} {:>6} {:>12} {:>7}", "Phase", "Count", "Seconds", "Share")?;
+        for time in self.phases.iter() {
+            let share = if total > 0.0 {
+                100.0 * time.seconds / total
+            } else {
+                0.0
+            };
+            write!(
+                f,
+                "\n{:<20} {:>6} {:>12.3} {:>6.1}%",
+                time.phase, time.count, time.seconds, share
+            )?;
+        }
+        write!(f, "\n{:<20} {:>6} {:>12.3}", "Total", "", total)
+    }
+}
+
+/// Records the time from its creation until it is dropped into a phase of
+/// a profile.
+pub struct PhaseTimer {
+    profile: Rc<RefCell<Profile>>,
+    phase: &'static str,
+    start: Instant,
+}
+
+impl PhaseTimer {
+    /// Starts timing the given phase, or returns `None` if profiling is
+    /// disabled, so that disabled profiling costs no more than a check.
+    pub fn start(profile: &SharedProfile, phase: &'static str) -> Option<PhaseTimer> {
+        profile.as_ref().map(|profile| PhaseTimer {
+            profile: profile.clone(),
+            phase,
+            start: Instant::now(),
+        })
+    }
+}
+
+impl Drop for PhaseTimer {
+    fn drop(&mut self) {
+        self.profile
+            .borrow_mut()
+            .record(self.phase, self.start.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timers_accumulate_per_phase() {
+        let profile = Some(Rc::new(RefCell::new(Profile::new())));
+        for _ in 0..3 {
+            let _timer = PhaseTimer::start(&profile, "tracing");
+        }
+        {
+            let _timer = PhaseTimer::start(&profile, "synthesis");
+        }
+        assert!(PhaseTimer::start(&None, "disabled").is_none());
+
+        let profile = profile.unwrap();
+        let profile = profile.borrow();
+        let phases: Vec<_> = profile.phases.iter().map(|p| (&p.phase[..], p.count)).collect();
+        assert_eq!(vec![("tracing", 3), ("synthesis", 1)], phases);
+
+        let table = format!("{}", profile);
+        assert!(table.starts_with("Phase"), "{}", table);
+        assert!(table.contains("\ntracing                   3"), "{}", table);
+    }
+}
@@ -1,15 +1,37 @@
 use asset::obj;
 use bencher::Bencher;
-use files::create_file_recursively;
-use geom::Vertex;
+use chrono::Local;
+use files::{create_file_recursively, write_file_atomically, write_file_retrying, Retry};
+use geom::{TupleTriangle, Vertex};
+use rayon::ThreadPool;
+use runner::checkpoint::{checkpoint_path, Checkpoint};
+use runner::checksum::concentration_checksum;
+use runner::cloud::{write_cloud, CloudFormat, CloudPoint};
+use runner::exr_export::{encode_exr, is_exr_path, texel_concentrations, BakeFilter};
+use runner::gltf_export::{write_gltf, GltfFormat};
+use runner::plan::{
+    Plan, MTL_BYTES_PER_ENTITY, OBJ_BYTES_PER_VERTEX, SECONDS_PER_GAMMATON, SECONDS_PER_TEXEL,
+    SURFEL_BYTES,
+};
+use runner::png_export::encode_density_png;
+use runner::snapshot::{snapshot_effects, snapshot_pattern};
+use runner::stats::{substance_stats, write_stats_header, write_stats_row};
 use runner::surfel_table_cache::SurfelTableCache;
-use scene::{Entity, MaterialBuilder};
+use runner::{Bounds, PhaseTimer, Profile, RunSummary, SharedProfile};
+use scene::{Entity, MaterialBuilder, Mesh};
+use serde_json;
 use sim::Simulation;
 use sim::SurfelData;
-use spec::{BenchSpec, Blend, EffectSpec, SimulationSpec, SurfelLookup};
+use spec::{BenchSpec, Blend, EffectSpec, SimulationSpec, SurfelLookup, TonSourceSpec};
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::fmt;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use surf;
 use tex::{
     self, combine_normals, open, BlendType, Density, DynamicImage, FilterType, GenericImage,
@@ -18,6 +40,20 @@ use tex::{
 
 type Surface = surf::Surface<surf::Surfel<Vertex, SurfelData>>;
 
+/// How a call to `SimulationRunner::run` ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    /// All iterations were simulated.
+    Completed,
+    /// The interrupt flag was set and the run stopped early, after the
+    /// outputs of the given iteration were written.
+    Interrupted { iteration: u32 },
+    /// The time budget was used up and the run stopped early, after the
+    /// outputs of the given iteration were written.
+    TimeBudgetReached { iteration: u32 },
+}
+
 pub struct SimulationRunner {
     spec: SimulationSpec,
     sim: Simulation,
@@ -29,6 +65,34 @@ pub struct SimulationRunner {
     tracing_benchmark: Option<Bencher>,
     synthesis_benchmark: Option<Bencher>,
     datetime: String,
+    spec_name: String,
+    retry: Retry,
+    /// Paths of all files written so far, in the order they were written.
+    outputs: RefCell<Vec<PathBuf>>,
+    checksum_log: Option<Box<Write>>,
+    stats_log: Option<Box<Write>>,
+    preheat: u32,
+    interrupt: Option<Arc<AtomicBool>>,
+    /// Interval in iterations and directory for checkpoints.
+    checkpoints: Option<(u32, PathBuf)>,
+    /// Interval in iterations and directory for texture snapshots.
+    snapshots: Option<(u32, PathBuf)>,
+    /// Path of the surfel cloud, which is also written with snapshots under
+    /// its file name.
+    snapshot_cloud: Option<PathBuf>,
+    on_iteration: Option<Box<FnMut(u32, u32)>>,
+    thread_pool: Option<Arc<ThreadPool>>,
+    profile: SharedProfile,
+    skip_effects: bool,
+    /// Substances density effects bake textures for, all if empty.
+    baked_substances: Vec<String>,
+    /// Filter and radius for baking density textures, overriding
+    /// `flat_filtering` of the spec.
+    bake_filter: Option<BakeFilter>,
+    bake_radius: Option<f32>,
+    time_budget: Option<Duration>,
+    /// Specs of the gammaton sources, for explaining the simulation.
+    source_specs: Vec<TonSourceSpec>,
 }
 
 impl SimulationRunner {
@@ -39,11 +103,17 @@ impl SimulationRunner {
         entities: Vec<Entity>,
         // Datetime to replace in file patterns
         datetime: &str,
+        // Stem of the spec file to replace in file patterns
+        spec_name: &str,
+        profile: SharedProfile,
     ) -> Self {
-        let surfel_tables = build_surfel_tables(&spec.effects, &entities, sim.surface());
+        let surfel_tables = {
+            let _timer = PhaseTimer::start(&profile, "surfel tables");
+            build_surfel_tables(&spec.effects, &entities, sim.surface())
+        };
 
         let (iteration_benchmark, tracing_benchmark, synthesis_benchmark) =
-            build_benchmarks(&spec.benchmark, datetime);
+            build_benchmarks(&spec.benchmark, datetime, spec_name);
 
         Self {
             spec,
@@ -56,6 +126,180 @@ impl SimulationRunner {
             tracing_benchmark,
             synthesis_benchmark,
             datetime: String::from(datetime),
+            spec_name: String::from(spec_name),
+            retry: Retry::none(),
+            outputs: RefCell::new(Vec::new()),
+            checksum_log: None,
+            stats_log: None,
+            preheat: 0,
+            interrupt: None,
+            checkpoints: None,
+            snapshots: None,
+            snapshot_cloud: None,
+            on_iteration: None,
+            thread_pool: None,
+            profile,
+            skip_effects: false,
+            baked_substances: Vec::new(),
+            bake_filter: None,
+            bake_radius: None,
+            time_budget: None,
+            source_specs: Vec::new(),
+        }
+    }
+
+    /// Sets how writing output files is retried after transient errors.
+    pub fn set_retry(&mut self, retry: Retry) {
+        self.retry = retry;
+    }
+
+    /// Paths of the output files written by the simulation so far, including
+    /// benchmarks, in the order they were first written.
+    pub fn outputs(&self) -> Vec<PathBuf> {
+        let (datetime, spec_name) = (&self.datetime, &self.spec_name);
+        let benchmarks = self.spec.benchmark.iter().flat_map(|b| {
+            vec![&b.setup, &b.iterations, &b.tracing, &b.synthesis]
+                .into_iter()
+                .filter_map(|p| p.as_ref())
+                .map(move |p| {
+                    let path = p.to_string_lossy().replace("{datetime}", datetime);
+                    PathBuf::from(path.replace("{spec_name}", spec_name))
+                })
+        });
+
+        let mut outputs: Vec<PathBuf> = Vec::new();
+        for output in self.outputs.borrow().iter().cloned().chain(benchmarks) {
+            if !outputs.contains(&output) {
+                outputs.push(output);
+            }
+        }
+        outputs
+    }
+
+    /// Writes a checksum of all surfel concentrations to the given sink
+    /// after each iteration, one line per iteration.
+    pub fn set_checksum_log<W: Write + 'static>(&mut self, checksum_log: W) {
+        self.checksum_log = Some(Box::new(checksum_log));
+    }
+
+    /// Writes a CSV header to the given sink and then a row with the total,
+    /// mean and maximum concentration of every substance after each
+    /// iteration.
+    pub fn set_stats_log<W: Write + 'static>(&mut self, mut stats_log: W) -> io::Result<()> {
+        write_stats_header(&mut stats_log, &self.unique_substance_names)?;
+        self.stats_log = Some(Box::new(stats_log));
+        Ok(())
+    }
+
+    /// Sets a number of iterations to trace before the first iteration,
+    /// without effects, benchmarks or checksums, so that the simulation can
+    /// settle before iterations are counted.
+    pub fn set_preheat(&mut self, preheat: u32) {
+        self.preheat = preheat;
+    }
+
+    /// Number of iterations traced before the first counted iteration.
+    pub fn preheat(&self) -> u32 {
+        self.preheat
+    }
+
+    /// Skips all effects, including those of iteration 0 and of snapshots,
+    /// so that only tracing and substance transport are performed.
+    pub fn set_skip_effects(&mut self, skip_effects: bool) {
+        self.skip_effects = skip_effects;
+    }
+
+    /// Restricts the textures baked by density effects to the substances
+    /// with the given names, or bakes all substances if empty.
+    pub fn set_baked_substances(&mut self, substances: Vec<String>) {
+        self.baked_substances = substances;
+    }
+
+    /// Overrides the filter for baking density textures from `flat_filtering`
+    /// of the spec and the radius of linear and gaussian filters, which is
+    /// the surfel distance unless given.
+    pub fn set_bake_filter(&mut self, filter: BakeFilter, radius: Option<f32>) {
+        self.bake_filter = Some(filter);
+        self.bake_radius = radius;
+    }
+
+    /// Sets the specs the gammaton sources were built from, which are listed
+    /// by `explain`.
+    pub fn set_source_specs(&mut self, source_specs: Vec<TonSourceSpec>) {
+        self.source_specs = source_specs;
+    }
+
+    fn bakes_substance(&self, substance: &str) -> bool {
+        self.baked_substances.is_empty() || self.baked_substances.iter().any(|s| s == substance)
+    }
+
+    /// Sets a wall-clock limit for `run`, checked between iterations. When it
+    /// is exceeded, the run stops after the current iteration, performing its
+    /// effects even if they are not scheduled, so partial results are written.
+    pub fn set_time_budget(&mut self, time_budget: Duration) {
+        self.time_budget = Some(time_budget);
+    }
+
+    /// Sets a flag that is checked between iterations. When it is set, the
+    /// run stops after the current iteration, performing its effects even
+    /// if they were not scheduled, so partial results are available.
+    pub fn set_interrupt(&mut self, interrupt: Arc<AtomicBool>) {
+        self.interrupt = Some(interrupt);
+    }
+
+    /// Writes a checkpoint into the given directory after every iteration
+    /// with an index divisible by `every`.
+    pub fn set_checkpoints<P: Into<PathBuf>>(&mut self, every: u32, dir: P) {
+        self.checkpoints = Some((every, dir.into()));
+    }
+
+    /// Writes the textures of density and layer effects into the given
+    /// directory after every iteration with an index divisible by `every`,
+    /// with the iteration appended to the file names.
+    pub fn set_snapshots<P: Into<PathBuf>>(&mut self, every: u32, dir: P) {
+        self.snapshots = Some((every, dir.into()));
+    }
+
+    /// Also writes a surfel cloud with every snapshot, named like the given
+    /// path with the iteration appended, as with `export_surfel_cloud`.
+    pub fn set_snapshot_cloud<P: Into<PathBuf>>(&mut self, cloud: P) {
+        self.snapshot_cloud = Some(cloud.into());
+    }
+
+    /// Traces gammatons on the threads of the given pool rather than on the
+    /// pool of the thread calling `run`, usually the global one.
+    ///
+    /// This allows embedding applications to limit the threads of each
+    /// runner without touching the global pool they may use themselves.
+    pub fn set_thread_pool(&mut self, thread_pool: Arc<ThreadPool>) {
+        self.thread_pool = Some(thread_pool);
+    }
+
+    /// Calls the given function after every completed iteration with the
+    /// index of the iteration and the total number of iterations.
+    pub fn set_on_iteration(&mut self, on_iteration: Box<FnMut(u32, u32)>) {
+        self.on_iteration = Some(on_iteration);
+    }
+
+    /// Time spent in each phase of loading and running the simulation so far,
+    /// if profiling was enabled when loading.
+    pub fn profile(&self) -> Option<Profile> {
+        self.profile.as_ref().map(|p| p.borrow().clone())
+    }
+
+    /// Captures the current state of the simulation.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            iteration: self.iteration,
+            datetime: self.datetime.clone(),
+            substance_names: self.unique_substance_names.clone(),
+            surfels: self
+                .sim
+                .surface()
+                .samples
+                .iter()
+                .map(|s| s.data().substances.clone())
+                .collect(),
         }
     }
 
@@ -63,9 +307,80 @@ impl SimulationRunner {
         &self.spec
     }
 
-    pub fn run(&mut self) {
+    /// Calculates the bounding box and surface area of the simulated
+    /// geometry and relates it to the surfel parameters.
+    pub fn bounds(&self) -> Bounds {
+        let mut bounds = Bounds::new();
+
+        for triangle in self.entities.iter().flat_map(|e| e.mesh.triangles()) {
+            let TupleTriangle(v0, v1, v2) = triangle;
+            bounds.add_triangle(
+                [v0.position.x, v0.position.y, v0.position.z],
+                [v1.position.x, v1.position.y, v1.position.z],
+                [v2.position.x, v2.position.y, v2.position.z],
+            );
+        }
+
+        bounds.surfels(self.spec.surfel_distance, self.sim.surfel_count())
+    }
+
+    /// Writes the current surfels as a point cloud with position, normal and
+    /// substance concentrations to the given path, as PLY if it ends with
+    /// `.ply` and as whitespace separated XYZ columns otherwise.
+    pub fn export_surfel_cloud<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let points = self.sim.surface().samples.iter().map(|surfel| {
+            let vertex = surfel.vertex();
+            CloudPoint {
+                position: [vertex.position.x, vertex.position.y, vertex.position.z],
+                normal: [vertex.normal.x, vertex.normal.y, vertex.normal.z],
+                substances: &surfel.data().substances,
+            }
+        });
+
+        let mut cloud = Vec::new();
+        write_cloud(
+            &mut cloud,
+            CloudFormat::from_path(path),
+            &self.unique_substance_names,
+            points,
+        )?;
+
+        write_file_retrying(path, &cloud, &self.retry)?;
+        self.record_output(path);
+        Ok(())
+    }
+
+    pub fn run(&mut self) -> RunSummary {
+        let start = Instant::now();
+        let status = self.run_iterations(start);
+        let elapsed = start.elapsed();
+
+        let traced_iterations = u64::from(self.preheat) + u64::from(self.iteration);
+        RunSummary {
+            status,
+            iterations: self.iteration,
+            duration_seconds: elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9,
+            gammatons_traced: traced_iterations * self.sim.emission_count() as u64,
+            outputs: self.outputs(),
+        }
+    }
+
+    fn run_iterations(&mut self, start: Instant) -> RunStatus {
+        if self.preheat > 0 {
+            info!("Preheating with {} iterations...", self.preheat);
+            for _ in 0..self.preheat {
+                self.trace();
+            }
+            info!("Preheated with {} iterations.", self.preheat);
+        }
+
         // Iteration 0 only performs effects, no tracing is performed.
-        // Useful as a reference for iteration 1.
+        // Useful as a reference for iteration 1, and the only output for
+        // zero iterations, which exports the starting state.
+        if self.iterations() == 0 {
+            info!("0 iterations requested, writing baseline only.");
+        }
         self.iteration = 0;
         self.perform_effects();
 
@@ -73,10 +388,83 @@ impl SimulationRunner {
             // Iteration 1 is the first iteration with actual gammaton simulation before effects.
             self.iteration += 1;
             self.perform_iteration();
+            self.write_checkpoint_if_due();
+            self.write_snapshot_if_due();
+
+            let (iteration, iterations) = (self.iteration, self.iterations());
+            if let Some(ref mut on_iteration) = self.on_iteration {
+                on_iteration(iteration, iterations);
+            }
+
+            let stop = if iteration == iterations {
+                None
+            } else if self.interrupted() {
+                Some(RunStatus::Interrupted { iteration })
+            } else if self.time_budget_exceeded(start) {
+                Some(RunStatus::TimeBudgetReached { iteration })
+            } else {
+                None
+            };
+
+            if let Some(stop) = stop {
+                if !self.effects_scheduled(iteration) {
+                    info!("Texture synthesis for partial results...");
+                    self.perform_effects();
+                }
+                return stop;
+            }
         }
+
+        RunStatus::Completed
+    }
+
+    fn write_checkpoint_if_due(&self) {
+        if let Some((every, ref dir)) = self.checkpoints {
+            if self.iteration % every == 0 {
+                let _timer = PhaseTimer::start(&self.profile, "checkpoints");
+                let path = checkpoint_path(dir, &self.datetime, self.iteration);
+                info!("Writing checkpoint {:?}...", path);
+                let checkpoint = serde_json::to_vec(&self.checkpoint())
+                    .expect("Checkpoint could not be serialized");
+                write_file_atomically(&path, &checkpoint, &self.retry)
+                    .expect("Checkpoint could not be persisted");
+                self.record_output(path);
+            }
+        }
+    }
+
+    fn write_snapshot_if_due(&self) {
+        if let Some((every, ref dir)) = self.snapshots {
+            if self.iteration % every == 0 {
+                let _timer = PhaseTimer::start(&self.profile, "snapshots");
+                info!("Writing texture snapshot into {:?}...", dir);
+                self.apply_effects(&snapshot_effects(&self.spec.effects, dir, self.iteration));
+
+                if let Some(ref cloud) = self.snapshot_cloud {
+                    let cloud = snapshot_pattern(&cloud.to_string_lossy(), dir, self.iteration);
+                    self.export_surfel_cloud(cloud)
+                        .expect("Surfel cloud snapshot could not be persisted");
+                }
+            }
+        }
+    }
+
+    fn time_budget_exceeded(&self, start: Instant) -> bool {
+        self.time_budget
+            .map(|budget| start.elapsed() >= budget)
+            .unwrap_or(false)
+    }
+
+    fn interrupted(&self) -> bool {
+        self.interrupt
+            .as_ref()
+            .map(|i| i.load(Ordering::SeqCst))
+            .unwrap_or(false)
     }
 
-    fn iterations(&self) -> u32 {
+    /// Number of iterations to simulate, not counting iteration 0 and
+    /// preheat iterations.
+    pub fn iterations(&self) -> u32 {
         // Default to 1 iteration
         self.spec.iterations.unwrap_or(1)
     }
@@ -97,35 +485,93 @@ impl SimulationRunner {
             let _tracing_and_transport_bench = self.tracing_benchmark.as_ref().map(|b| b.bench());
 
             info!("Tracing...");
-            self.sim.run();
+            self.trace();
         }
 
-        let effects_scheduled = match self.spec.effect_interval {
-            // Interval is defined, 1-based iteration index must be divisible.
-            Some(interval) if (self.iteration % interval) == 0 => true,
-            // Either no interval defined or defined and not divisible, skip effects,
-            // except for the last iteration.
-            _ => self.iteration == self.iterations(),
-        };
+        self.log_checksum();
+        self.log_stats();
 
-        if effects_scheduled {
+        if self.effects_scheduled(self.iteration) {
             // NOTE surfel table cache invalidation necessary if geometry was changed
             info!("Texture synthesis...");
             self.perform_effects();
         }
     }
 
+    fn trace(&mut self) {
+        let _timer = PhaseTimer::start(&self.profile, "tracing");
+        let sim = &mut self.sim;
+        install(&self.thread_pool, || sim.run())
+    }
+
+    fn log_checksum(&mut self) {
+        if let Some(ref mut checksum_log) = self.checksum_log {
+            let checksum = concentration_checksum(
+                &self.unique_substance_names,
+                self.sim
+                    .surface()
+                    .samples
+                    .iter()
+                    .map(|s| &s.data().substances[..]),
+            );
+
+            writeln!(checksum_log, "{} {:016x}", self.iteration, checksum)
+                .and_then(|_| checksum_log.flush())
+                .expect("Failed to write checksum log");
+        }
+    }
+
+    fn log_stats(&mut self) {
+        if let Some(ref mut stats_log) = self.stats_log {
+            let stats = substance_stats(
+                self.unique_substance_names.len(),
+                self.sim
+                    .surface()
+                    .samples
+                    .iter()
+                    .map(|s| &s.data().substances[..]),
+            );
+
+            write_stats_row(
+                stats_log,
+                self.iteration,
+                &Local::now().to_rfc3339(),
+                &self.unique_substance_names,
+                &stats,
+            ).expect("Failed to write statistics log");
+        }
+    }
+
+    fn effects_scheduled(&self, iteration: u32) -> bool {
+        match self.spec.effect_interval {
+            // Interval is defined, 1-based iteration index must be divisible.
+            Some(interval) if (iteration % interval) == 0 => true,
+            // Either no interval defined or defined and not divisible, skip effects,
+            // except for the last iteration.
+            _ => iteration == self.iterations(),
+        }
+    }
+
     fn perform_effects(&self) {
         // NOTE this will run for iteration 0, so there will be one benchmark more for
         //      synthesis when compared to tracing
         let _synthesis_bench = self.synthesis_benchmark.as_ref().map(|b| b.bench());
+        let _timer = PhaseTimer::start(&self.profile, "synthesis");
+
+        self.apply_effects(&self.spec.effects);
+    }
+
+    fn apply_effects(&self, effects: &[EffectSpec]) {
+        if self.skip_effects {
+            return;
+        }
 
         // Make a fresh copy of the scene to run the effects on for each effect run.
         // With this technique, effects can accumulate throughout one iteration,
         // but each iteration will apply its effects on top of the base material.
         let mut entities = self.entities.clone();
 
-        for effect in &self.spec.effects {
+        for effect in effects {
             self.perform_effect(effect, &mut entities);
         }
     }
@@ -181,6 +627,23 @@ impl SimulationRunner {
         }
     }
 
+    /// Bits per channel of density textures written as PNG.
+    fn bit_depth(&self) -> u8 {
+        self.spec.output_bit_depth.unwrap_or(8)
+    }
+
+    fn bake_filter(&self) -> BakeFilter {
+        match self.bake_filter {
+            Some(filter) => filter,
+            None if self.spec.flat_filtering == Some(true) => BakeFilter::Nearest,
+            None => BakeFilter::InverseDistance,
+        }
+    }
+
+    fn bake_radius(&self) -> f32 {
+        self.bake_radius.or(self.spec.surfel_distance).unwrap_or(1.0)
+    }
+
     fn filtering(&self) -> SubstanceFilter {
         match self.spec.flat_filtering {
             Some(true) => SubstanceFilter::Flat,
@@ -201,26 +664,12 @@ impl SimulationRunner {
         obj_pattern: &Option<String>,
         mtl_pattern: &Option<String>,
     ) {
-        for (substance_idx, substance_name) in self.unique_substance_names.iter().enumerate() {
-            let density = Density::new(
-                substance_idx,
-                width,  // tex_width
-                height, // tex_height
-                island_bleed,
-                0.0, // min_density
-                1.0, // max_density
-                Rgba {
-                    data: [255, 255, 255, 255],
-                }, // undefined_color
-                Rgba {
-                    data: [255, 255, 255, 255],
-                }, // min color
-                Rgba {
-                    data: [0, 0, 0, 255],
-                }, // max color
-                self.filtering(),
-            );
-
+        let baked_substances = self
+            .unique_substance_names
+            .iter()
+            .enumerate()
+            .filter(|&(_, name)| self.bakes_substance(name));
+        for (substance_idx, substance_name) in baked_substances {
             // Make lazy copy of original scene with each material replaced
             // by a new one with diffuse color set to substance density
             let density_scene = self
@@ -236,21 +685,37 @@ impl SimulationRunner {
                         island_bleed,
                     );
 
-                    let density_tex = density.collect_with_table(self.sim.surface(), surfel_table);
-
                     let tex_filename = tex_pattern
                         .replace("{iteration}", &format!("{}", self.iteration))
                         .replace("{id}", &format!("{}", ent_idx))
                         .replace("{entity}", &ent.name)
                         .replace("{substance}", substance_name)
-                        .replace("{datetime}", &self.datetime);
-
-                    let mut fout = create_file_recursively(&tex_filename)
-                        .expect("Could not create image file for density effect.");
+                        .replace("{datetime}", &self.datetime)
+                        .replace("{spec_name}", &self.spec_name);
+
+                    // Filtered the same way for every format, so textures
+                    // of the same run agree regardless of their format
+                    let samples = &self.sim.surface().samples;
+                    let concentrations = texel_concentrations(
+                        surfel_table,
+                        self.bake_filter(),
+                        self.bake_radius(),
+                        |idx| samples[idx].data().substances[substance_idx],
+                    );
 
-                    tex::ImageRgba8(density_tex)
-                        .write_to(&mut fout, tex::PNG)
+                    // OpenEXR stores concentrations as floats instead of
+                    // quantizing them to colors between min and max density
+                    let encoded = if is_exr_path(&tex_filename) {
+                        encode_exr(width, height, &concentrations)
+                            .expect("Density texture could not be encoded")
+                    } else {
+                        encode_density_png(width, height, &concentrations, self.bit_depth())
+                            .expect("Density texture could not be encoded")
+                    };
+
+                    write_file_atomically(&tex_filename, &encoded, &self.retry)
                         .expect("Density texture could not be persisted");
+                    self.record_output(&tex_filename);
 
                     // Reference old entity name and mesh, but replace
                     // material in a fresh entity
@@ -473,14 +938,17 @@ impl SimulationRunner {
             .replace("{id}", &format!("{}", entity_idx))
             .replace("{entity}", &entity.name)
             .replace("{substance}", &self.unique_substance_names[substance_idx])
-            .replace("{datetime}", &self.datetime);
-
-        let mut tex_file = create_file_recursively(&tex_filename)
-            .expect("Could not create texture file for blending effect");
+            .replace("{datetime}", &self.datetime)
+            .replace("{spec_name}", &self.spec_name);
 
+        let mut png = Vec::new();
         tex::ImageRgba8(blend_result_tex)
-            .write_to(&mut tex_file, tex::PNG)
-            .expect("Density texture could not be persisted");
+            .write_to(&mut png, tex::PNG)
+            .expect("Blended texture could not be encoded");
+
+        write_file_atomically(&tex_filename, &png, &self.retry)
+            .expect("Blended texture could not be persisted");
+        self.record_output(&tex_filename);
 
         PathBuf::from(tex_filename)
     }
@@ -533,45 +1001,105 @@ impl SimulationRunner {
         // e.g. group by name and then make every multiply used name unique if values differ
 
         match (obj_pattern, mtl_pattern) {
+            (&Some(ref gltf_pattern), _) if GltfFormat::from_path(gltf_pattern).is_some() => {
+                self.export_gltf(entities, gltf_pattern, substance)
+            },
             (&Some(ref obj_pattern), &Some(ref mtl_pattern)) => {
                 let obj_filename = obj_pattern.replace("{iteration}", &format!("{}", self.iteration))
                     .replace("{substance}", substance)
-                    .replace("{datetime}", datetime);
+                    .replace("{datetime}", datetime)
+                    .replace("{spec_name}", &self.spec_name);
 
                 let mtl_filename = mtl_pattern.replace("{iteration}", &format!("{}", self.iteration))
                     .replace("{substance}", substance)
-                    .replace("{datetime}", datetime);
+                    .replace("{datetime}", datetime)
+                    .replace("{spec_name}", &self.spec_name);
 
                 info!("Persisting scene: {}", obj_filename);
 
-                create_file_recursively(&obj_filename)
-                    .expect("Failed to create OBJ file when persisting effect results.");
-                create_file_recursively(&mtl_filename)
-                    .expect("Failed to create MTL file when persisting effect results.");
-
-                obj::save(entities, Some(obj_filename), Some(mtl_filename))
+                // Collected so that a retry can save the entities again
+                let entities: Vec<&Entity> = entities.into_iter().collect();
+                self.retry
+                    .run("Saving OBJ/MTL", || {
+                        create_file_recursively(&obj_filename)?;
+                        create_file_recursively(&mtl_filename)?;
+                        let entities = entities.iter().cloned();
+                        obj::save(entities, Some(&obj_filename), Some(&mtl_filename))
+                    })
                     .expect("Failed to save OBJ/MTL.");
+
+                self.record_output(&obj_filename);
+                self.record_output(&mtl_filename);
             },
             (&None, &None) => (),
             _ => unimplemented!("Individual OBJ/MTL output without its counterpart unsupported by now. Export counterpart too to make it work.")
         }
     }
 
+    /// Writes the scene as glTF 2.0 including all textures, as binary glTF if
+    /// the pattern ends with `.glb`.
+    fn export_gltf<'a, E>(&'a self, entities: E, gltf_pattern: &str, substance: &str)
+    where
+        E: IntoIterator<Item = &'a Entity>,
+    {
+        let gltf_filename = gltf_pattern
+            .replace("{iteration}", &format!("{}", self.iteration))
+            .replace("{substance}", substance)
+            .replace("{datetime}", &self.datetime)
+            .replace("{spec_name}", &self.spec_name);
+
+        info!("Persisting scene: {}", gltf_filename);
+
+        let mut gltf = Vec::new();
+        // Unwrap is safe since caller checked the extension
+        let format = GltfFormat::from_path(&gltf_filename).unwrap();
+        write_gltf(&mut gltf, format, entities).expect("Failed to encode glTF.");
+
+        write_file_retrying(&gltf_filename, &gltf, &self.retry).expect("Failed to save glTF.");
+        self.record_output(&gltf_filename);
+    }
+
     fn export_surfels(&self, surfel_obj_pattern: &str) {
         let datetime = &self.datetime;
 
         let surfel_obj_path = surfel_obj_pattern
             .replace("{iteration}", &format!("{}", self.iteration))
-            .replace("{datetime}", datetime);
+            .replace("{datetime}", datetime)
+            .replace("{spec_name}", &self.spec_name);
 
-        let mut obj_file = create_file_recursively(surfel_obj_path)
+        let mut obj_file = create_file_recursively(&surfel_obj_path)
             .expect("Failed to create OBJ file to save surfels into.");
+        self.record_output(&surfel_obj_path);
 
         self.sim
             .surface()
             .dump(&mut obj_file)
             .expect("Failed to save surfels to OBJ file");
     }
+
+    fn record_output<P: Into<PathBuf>>(&self, path: P) {
+        self.outputs.borrow_mut().push(path.into());
+    }
+}
+
+/// Runs the operation in the given thread pool, or in the global thread
+/// pool of rayon without one.
+fn install<OP, R>(thread_pool: &Option<Arc<ThreadPool>>, op: OP) -> R
+where
+    OP: FnOnce() -> R + Send,
+    R: Send,
+{
+    match *thread_pool {
+        Some(ref thread_pool) => thread_pool.install(op),
+        None => op(),
+    }
+}
+
+fn lookup_count(surfel_lookup: SurfelLookup) -> usize {
+    match surfel_lookup {
+        SurfelLookup::Nearest { count } => count,
+        SurfelLookup::Within { .. } => 0,
+    }
 }
 
 // Underscore material is catchall as always, empty array also means admit all materials
@@ -585,12 +1113,21 @@ fn is_entity_applicable_for_materials(entity: &Entity, materials: &Vec<String>)
 fn build_benchmarks(
     benchmark: &Option<BenchSpec>,
     creation_time: &str,
+    spec_name: &str,
 ) -> (Option<Bencher>, Option<Bencher>, Option<Bencher>) {
-    fn build_benchmark(target_file: &Option<PathBuf>, creation_time: &str) -> Option<Bencher> {
+    fn build_benchmark(
+        target_file: &Option<PathBuf>,
+        creation_time: &str,
+        spec_name: &str,
+    ) -> Option<Bencher> {
         target_file
             .as_ref()
             .and_then(|csv| {
-                let csv = csv.to_str().unwrap().replace("{datetime}", creation_time);
+                let csv = csv
+                    .to_str()
+                    .unwrap()
+                    .replace("{datetime}", creation_time)
+                    .replace("{spec_name}", spec_name);
 
                 Some(create_file_recursively(csv).expect("Failed to create benchmark file"))
             })
@@ -598,9 +1135,9 @@ fn build_benchmarks(
     }
 
     if let Some(ref benchmark) = benchmark {
-        let iteration_benchmark = build_benchmark(&benchmark.iterations, creation_time);
-        let tracing_benchmark = build_benchmark(&benchmark.tracing, creation_time);
-        let synthesis_benchmark = build_benchmark(&benchmark.synthesis, creation_time);
+        let iteration_benchmark = build_benchmark(&benchmark.iterations, creation_time, spec_name);
+        let tracing_benchmark = build_benchmark(&benchmark.tracing, creation_time, spec_name);
+        let synthesis_benchmark = build_benchmark(&benchmark.synthesis, creation_time, spec_name);
 
         (iteration_benchmark, tracing_benchmark, synthesis_benchmark)
     } else {
@@ -774,6 +1311,272 @@ fn blend_output_size(blend: &Blend, original_tex_path: Option<&PathBuf>) -> (u32
     }
 }
 
+impl SimulationRunner {
+    /// Estimates the stages, time, memory and outputs of running the
+    /// simulation without running it.
+    pub fn plan(&self) -> Plan {
+        let mut plan = Plan::new();
+        let substance_count = self.unique_substance_names.len() as u64;
+        let vertex_count: u64 = self
+            .entities
+            .iter()
+            .map(|e| 3 * e.mesh.triangles().count() as u64)
+            .sum();
+        let obj_bytes = vertex_count * OBJ_BYTES_PER_VERTEX;
+        let mtl_bytes = self.entities.len() as u64 * MTL_BYTES_PER_ENTITY;
+
+        // Surfel tables are cached for the whole run, one per distinct
+        // combination of entity, size and lookup
+        let mut surfel_tables = HashSet::new();
+        let mut largest_texture_bytes = 0;
+
+        for iteration in 0..(self.iterations() + 1) {
+            if iteration > 0 {
+                plan.add_stage(
+                    iteration,
+                    "tracing",
+                    self.sim.emission_count() as f64 * SECONDS_PER_GAMMATON,
+                );
+            }
+
+            if iteration != 0 && !self.effects_scheduled(iteration) {
+                continue;
+            }
+
+            let expand = |pattern: &str, entity_idx: usize, entity: &str, substance: &str| {
+                pattern
+                    .replace("{iteration}", &format!("{}", iteration))
+                    .replace("{id}", &format!("{}", entity_idx))
+                    .replace("{entity}", entity)
+                    .replace("{substance}", substance)
+                    .replace("{datetime}", &self.datetime)
+                    .replace("{spec_name}", &self.spec_name)
+            };
+
+            let mut texels = 0;
+            let mut outputs = Vec::new();
+            for effect in self.spec.effects.iter() {
+                match effect {
+                    &EffectSpec::Density {
+                        width,
+                        height,
+                        surfel_lookup,
+                        ref tex_pattern,
+                        ref obj_pattern,
+                        ref mtl_pattern,
+                        ..
+                    } => {
+                        let count = lookup_count(surfel_lookup);
+                        let channel_bytes = u64::from(self.bit_depth() / 8);
+                        let texture_bytes = 4 * channel_bytes * (width * height) as u64;
+                        largest_texture_bytes = largest_texture_bytes.max(texture_bytes);
+
+                        let baked_substances = self
+                            .unique_substance_names
+                            .iter()
+                            .filter(|s| self.bakes_substance(s));
+                        for substance in baked_substances {
+                            for (entity_idx, entity) in self.entities.iter().enumerate() {
+                                texels += (width * height) as u64;
+                                surfel_tables.insert((entity_idx, width, height, count));
+                                let tex = expand(tex_pattern, entity_idx, &entity.name, substance);
+                                outputs.push((tex, texture_bytes));
+                            }
+
+                            if let (&Some(ref obj), &Some(ref mtl)) = (obj_pattern, mtl_pattern) {
+                                outputs.push((expand(obj, 0, "", substance), obj_bytes));
+                                outputs.push((expand(mtl, 0, "", substance), mtl_bytes));
+                            }
+                        }
+                    }
+                    &EffectSpec::Layer {
+                        ref materials,
+                        ref substance,
+                        surfel_lookup,
+                        ref normal,
+                        ref displacement,
+                        ref albedo,
+                        ref metallicity,
+                        ref roughness,
+                        ..
+                    } => {
+                        let count = lookup_count(surfel_lookup);
+                        for (entity_idx, entity) in self
+                            .entities
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, e)| is_entity_applicable_for_materials(e, materials))
+                        {
+                            let material = &entity.material;
+                            let blends = vec![
+                                (normal, material.normal_map()),
+                                (displacement, material.displacement_map()),
+                                (albedo, material.diffuse_color_map()),
+                                (metallicity, material.metallic_map()),
+                                (roughness, material.roughness_map()),
+                            ];
+
+                            for (blend, original_map) in blends {
+                                if let &Some(ref blend) = blend {
+                                    let (width, height) = blend_output_size(blend, original_map);
+                                    let (width, height) = (width as usize, height as usize);
+                                    // Original, stops and result are in memory at once
+                                    let texture_bytes = 4 * (width * height) as u64;
+                                    largest_texture_bytes = largest_texture_bytes
+                                        .max(texture_bytes * (blend.stops.len() as u64 + 2));
+                                    texels += (width * height) as u64;
+                                    surfel_tables.insert((entity_idx, width, height, count));
+                                    let tex = expand(
+                                        &blend.tex_pattern,
+                                        entity_idx,
+                                        &entity.name,
+                                        substance,
+                                    );
+                                    outputs.push((tex, texture_bytes));
+                                }
+                            }
+                        }
+                    }
+                    &EffectSpec::Export {
+                        obj_pattern: Some(ref gltf),
+                        ..
+                    } if GltfFormat::from_path(gltf).is_some() => {
+                        outputs.push((expand(gltf, 0, "", "all"), obj_bytes));
+                    }
+                    &EffectSpec::Export {
+                        obj_pattern: Some(ref obj),
+                        mtl_pattern: Some(ref mtl),
+                    } => {
+                        outputs.push((expand(obj, 0, "", "all"), obj_bytes));
+                        outputs.push((expand(mtl, 0, "", "all"), mtl_bytes));
+                    }
+                    &EffectSpec::Export { .. } => (),
+                    &EffectSpec::DumpSurfels { ref obj_pattern } => {
+                        outputs.push((
+                            expand(obj_pattern, 0, "", ""),
+                            self.sim.surfel_count() as u64 * OBJ_BYTES_PER_VERTEX,
+                        ));
+                    }
+                }
+            }
+
+            plan.add_stage(iteration, "synthesis", texels as f64 * SECONDS_PER_TEXEL);
+            for (path, bytes) in outputs {
+                plan.add_output(path, bytes);
+            }
+        }
+
+        let surfel_bytes =
+            self.sim.surfel_count() as u64 * (SURFEL_BYTES + 8 * substance_count);
+        // Each texel stores distance and index for each of its nearest surfels
+        let table_bytes: u64 = surfel_tables
+            .iter()
+            .map(|&(_, width, height, count)| {
+                (width * height) as u64 * (24 + 16 * count as u64)
+            })
+            .sum();
+        plan.estimated_peak_memory_bytes = surfel_bytes + table_bytes + largest_texture_bytes;
+
+        plan
+    }
+
+    /// Concise description of the simulation with counts only, suitable
+    /// for logging large simulations. Use `Display` for the full version.
+    pub fn summary(&self) -> String {
+        format!(
+            "Name:               {}\n\
+             Entities:           {}\n\
+             Iterations:         {}\n\
+             Surfels:            {}\n\
+             Substances:         {}",
+            self.spec.name,
+            self.entities.len(),
+            self.iterations(),
+            self.sim.surfel_count(),
+            self.unique_substance_names.len()
+        )
+    }
+
+    /// Human readable narrative of what the simulation will do when run, as
+    /// printed by `--explain`, with the emitting sources, the substances
+    /// they carry, the weathered materials and the written outputs.
+    pub fn explain(&self) -> String {
+        let mut lines = vec![format!(
+            "Simulation \"{}\" runs {} iterations on {} entities with {} surfels.",
+            self.spec.name,
+            self.iterations(),
+            self.entities.len(),
+            self.sim.surfel_count()
+        )];
+
+        lines.push(format!(
+            "In every iteration, {} gammaton sources emit {} gammatons:",
+            self.source_specs.len(),
+            self.sim.emission_count()
+        ));
+        for source in self.source_specs.iter() {
+            let mut carried: Vec<(&String, &f32)> =
+                source.initial.iter().filter(|&(_, c)| *c != 0.0).collect();
+            carried.sort_by_key(|&(substance, _)| substance);
+            let carried = if carried.is_empty() {
+                String::from("no substances")
+            } else {
+                carried
+                    .iter()
+                    .map(|&(substance, concentration)| format!("{} ({})", substance, concentration))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            lines.push(format!(
+                "  {} emits {} gammatons from {}, depositing {}.",
+                source.name(),
+                source.emission_count,
+                source.mesh.display(),
+                carried
+            ));
+        }
+
+        lines.push(String::from("Surfels on these materials receive weathering:"));
+        let mut materials: Vec<&str> = self.entities.iter().map(|e| e.material.name()).collect();
+        materials.sort();
+        materials.dedup();
+        for material in materials {
+            let surfel_spec = self
+                .spec
+                .surfels_by_material
+                .get(material)
+                .or_else(|| self.spec.surfels_by_material.get("_"));
+            if let Some(surfel_spec) = surfel_spec {
+                lines.push(format!("  {}, as specified in {}", material, surfel_spec));
+            }
+        }
+
+        if self.skip_effects || self.spec.effects.is_empty() {
+            lines.push(String::from("No effects are performed and no outputs written."));
+        } else {
+            let effect_iterations: Vec<String> = (1..(self.iterations() + 1))
+                .filter(|&iteration| self.effects_scheduled(iteration))
+                .map(|iteration| format!("{}", iteration))
+                .collect();
+            if effect_iterations.is_empty() {
+                lines.push(String::from(
+                    "Effects are performed once for the unweathered baseline, writing:",
+                ));
+            } else {
+                lines.push(format!(
+                    "Effects are performed before the first iteration and after iterations {}, writing:",
+                    effect_iterations.join(", ")
+                ));
+            }
+            for output in self.plan().outputs {
+                lines.push(format!("  {}", output.path));
+            }
+        }
+
+        lines.join("\n")
+    }
+}
+
 impl fmt::Display for SimulationRunner {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Name:               {}\n", self.spec.name)?;
@@ -791,3 +1594,407 @@ impl fmt::Display for SimulationRunner {
         write!(f, "Substances:         {:?}", self.unique_substance_names)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use builder::{Error, SimulationBuilder};
+    use gltf;
+    use rayon::{self, ThreadPoolBuilder};
+    use std::env::temp_dir;
+    use std::fs::{read, read_dir, remove_dir_all, remove_file};
+    use std::thread;
+
+    /// Sets the interrupt flag as soon as the first checksum is written.
+    struct InterruptOnWrite(Arc<AtomicBool>);
+
+    impl Write for InterruptOnWrite {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.store(true, Ordering::SeqCst);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Loads the sky example with the given iterations, dumping surfels into
+    /// the temporary directory.
+    fn sky_runner(iterations: u32) -> SimulationRunner {
+        sky_builder(iterations).build().unwrap()
+    }
+
+    fn sky_builder(iterations: u32) -> SimulationBuilder {
+        let dumps = temp_dir().join("aitios-runner-test/iteration-{iteration}.obj");
+        SimulationBuilder::new()
+            .append_spec_fragment_str(&format!(
+                "scenes: [\"tests/assets/sky.obj\"]\nsurfels_by_material:\n  _: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 2.0\niterations: {}\neffects:\n  - dump_surfels:\n      obj_pattern: {:?}",
+                iterations, dumps
+            ))
+            .unwrap()
+    }
+
+    #[test]
+    fn interrupt_stops_after_current_iteration() {
+        let mut runner = sky_runner(3);
+
+        let interrupt = Arc::new(AtomicBool::new(false));
+        runner.set_interrupt(interrupt.clone());
+        runner.set_checksum_log(InterruptOnWrite(interrupt));
+
+        assert_eq!(RunStatus::Interrupted { iteration: 1 }, runner.run().status);
+    }
+
+    #[test]
+    fn time_budget_stops_after_current_iteration() {
+        let mut runner = sky_runner(3);
+        // Tracing a single iteration takes longer than this
+        runner.set_time_budget(Duration::from_millis(1));
+
+        let summary = runner.run();
+        assert_eq!(RunStatus::TimeBudgetReached { iteration: 1 }, summary.status);
+        assert_eq!(1, summary.iterations);
+
+        let mut runner = sky_runner(2);
+        runner.set_time_budget(Duration::from_secs(3600));
+        assert_eq!(RunStatus::Completed, runner.run().status);
+    }
+
+    #[test]
+    fn checkpoints_every_iteration() {
+        let dir = temp_dir().join("aitios-checkpoint-test");
+        let mut runner = sky_runner(2);
+        runner.set_checkpoints(1, &dir);
+
+        assert_eq!(RunStatus::Completed, runner.run().status);
+
+        for iteration in 1..3 {
+            let path = checkpoint_path(&dir, &runner.datetime, iteration);
+            let checkpoint: Checkpoint =
+                serde_json::from_slice(&read(&path).expect("Expected checkpoint to exist"))
+                    .unwrap();
+            assert_eq!(iteration, checkpoint.iteration);
+            assert_eq!(runner.sim.surfel_count(), checkpoint.surfels.len());
+        }
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn summary_lists_iterations_and_outputs() {
+        let summary = sky_runner(2).run();
+
+        assert_eq!(RunStatus::Completed, summary.status);
+        assert_eq!(2, summary.iterations);
+        // Surfels are dumped in iteration 0 and after the last iteration
+        let dumps: Vec<PathBuf> = [0, 2]
+            .iter()
+            .map(|i| temp_dir().join(format!("aitios-runner-test/iteration-{}.obj", i)))
+            .collect();
+        assert_eq!(dumps, summary.outputs);
+    }
+
+    #[test]
+    fn zero_iterations_write_baseline() {
+        let mut runner = sky_runner(0);
+        let summary = runner.run();
+
+        assert_eq!(RunStatus::Completed, summary.status);
+        assert_eq!(0, summary.iterations);
+        assert_eq!(0, summary.gammatons_traced);
+        let baseline = temp_dir().join("aitios-runner-test/iteration-0.obj");
+        assert_eq!(vec![baseline.clone()], summary.outputs);
+        assert!(baseline.is_file());
+        assert!(runner.explain().contains("once for the unweathered baseline"));
+    }
+
+    #[test]
+    fn snapshots_every_iteration() {
+        let dir = temp_dir().join("aitios-snapshot-test");
+        let mut runner = SimulationBuilder::new()
+            .append_spec_fragment_str(&format!(
+                "scenes: [\"tests/assets/buddha.obj\"]\nsurfels_by_material:\n  _: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 0.5\niterations: 2\neffects:\n  - density:\n      width: 8\n      height: 8\n      tex_pattern: {:?}",
+                temp_dir().join("aitios-snapshot-test-final/{id}-{substance}.png")
+            ))
+            .unwrap()
+            .build()
+            .unwrap();
+        runner.set_snapshots(1, &dir);
+        runner.run();
+
+        let snapshots: Vec<String> = read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        for iteration in &["0001", "0002"] {
+            assert!(
+                snapshots
+                    .iter()
+                    .any(|s| s.ends_with(&format!("_{}.png", iteration))),
+                "Expected snapshot for iteration {}, but got {:?}",
+                iteration,
+                snapshots
+            );
+        }
+        assert!(!snapshots.iter().any(|s| s.ends_with("_0000.png")));
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn surfel_cloud_has_a_point_per_surfel() {
+        let path = temp_dir().join("aitios-surfel-cloud-test.ply");
+        let mut runner = sky_runner(1);
+        runner.run();
+        runner.export_surfel_cloud(&path).unwrap();
+
+        let ply = String::from_utf8(read(&path).unwrap()).unwrap();
+        let header = ply.split("end_header\n").next().unwrap();
+        assert!(header.contains(&format!("element vertex {}\n", runner.sim.surfel_count())));
+        assert!(header.contains("property float x\nproperty float y\nproperty float z\n"));
+        assert!(header.contains("property float nx\nproperty float ny\nproperty float nz\n"));
+        for substance in runner.unique_substance_names.iter() {
+            assert!(
+                header.contains(&format!("property float {}\n", substance)),
+                "Expected property for {} in {}",
+                substance,
+                header
+            );
+        }
+        assert_eq!(
+            runner.sim.surfel_count(),
+            ply.lines().skip(header.lines().count() + 1).count()
+        );
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn exports_parseable_gltf() {
+        let dir = temp_dir().join("aitios-gltf-test");
+        let mut runner = SimulationBuilder::new()
+            .append_spec_fragment_str(&format!(
+                "scenes: [\"tests/assets/sky.obj\"]\nsurfels_by_material:\n  _: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 2.0\niterations: 1\neffects:\n  - export:\n      obj_pattern: {:?}\n  - export:\n      obj_pattern: {:?}",
+                dir.join("sky-{iteration}.gltf"),
+                dir.join("sky-{iteration}.glb")
+            ))
+            .unwrap()
+            .build()
+            .unwrap();
+        runner.run();
+
+        for file in &["sky-1.gltf", "sky-1.glb"] {
+            let exported = gltf::Gltf::from_slice(&read(dir.join(file)).unwrap())
+                .expect("Expected exported scene to be valid glTF");
+            assert_eq!(runner.entities.len(), exported.meshes().count());
+        }
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stats_row_per_iteration() {
+        let path = temp_dir().join("aitios-stats-test.csv");
+        let mut runner = sky_runner(3);
+        runner
+            .set_stats_log(create_file_recursively(&path).unwrap())
+            .unwrap();
+        runner.run();
+
+        let csv = String::from_utf8(read(&path).unwrap()).unwrap();
+        let columns = 2 + 3 * runner.unique_substance_names.len();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(4, lines.len(), "Expected header and three rows: {}", csv);
+        assert!(lines[0].starts_with("iteration,timestamp,"));
+        for line in lines {
+            assert_eq!(columns, line.split(',').count(), "Wrong columns: {}", line);
+        }
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn runners_with_own_thread_pools() {
+        for &threads in [1, 2].iter() {
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .thread_name(move |idx| format!("runner-pool-{}-{}", threads, idx))
+                .build()
+                .unwrap();
+            let mut runner = sky_runner(1);
+            runner.set_thread_pool(Arc::new(pool));
+            assert_eq!(RunStatus::Completed, runner.run().status);
+
+            let (num_threads, name) = install(&runner.thread_pool, || {
+                let name = thread::current().name().map(String::from);
+                (rayon::current_num_threads(), name)
+            });
+            assert_eq!(threads, num_threads);
+            let name = name.expect("Expected work to run on a named pool thread");
+            assert!(
+                name.starts_with(&format!("runner-pool-{}-", threads)),
+                "Unexpected thread {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn profile_has_known_phases() {
+        let mut runner = sky_builder(2).profile(true).build().unwrap();
+        runner.run();
+
+        let profile = runner.profile().expect("Expected profiling to be enabled");
+        let table = format!("{}", profile);
+        for phase in &["mesh loading", "surfel generation", "tracing", "synthesis"] {
+            assert!(table.contains(phase), "Expected {} in:\n{}", phase, table);
+        }
+
+        let tracing = profile.phases.iter().find(|p| p.phase == "tracing").unwrap();
+        assert_eq!(2, tracing.count);
+        assert!(sky_runner(1).profile().is_none());
+    }
+
+    #[test]
+    fn emission_count_is_overridden() {
+        // rain.yml emits 100000 gammatons per iteration
+        assert_eq!(100000, sky_runner(1).sim.emission_count());
+
+        let mut runner = sky_builder(1).emission_count(500).build().unwrap();
+        assert_eq!(500, runner.sim.emission_count());
+        assert_eq!(500, runner.run().gammatons_traced);
+    }
+
+    #[test]
+    fn only_selected_substances_are_baked() {
+        let dir = temp_dir().join("aitios-only-substance-test");
+        let tex_pattern = dir.join("{substance}.png");
+        let mut runner = SimulationBuilder::new()
+            .append_spec_fragment_str(&format!(
+                "scenes: [\"tests/assets/sky.obj\"]\nsurfels_by_material:\n  _: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 2.0\neffects:\n  - density:\n      width: 8\n      height: 8\n      tex_pattern: {:?}",
+                tex_pattern
+            ))
+            .unwrap()
+            .emission_count(100)
+            .only_substances(vec!["rust"])
+            .build()
+            .unwrap();
+
+        runner.run();
+
+        let mut written: Vec<String> = read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        written.sort();
+        remove_dir_all(&dir).unwrap();
+        assert_eq!(vec![String::from("rust.png")], written);
+
+        let result = sky_builder(1).only_substances(vec!["moss"]).build();
+        match result {
+            Err(Error::UnknownSubstances { unknown, .. }) => {
+                assert_eq!(vec![String::from("moss")], unknown)
+            }
+            _ => panic!("Expected moss to be an unknown substance"),
+        }
+    }
+
+    #[test]
+    fn explanation_names_sources_and_substances() {
+        let explanation = sky_builder(2)
+            .append_spec_fragment_str("name: Sky")
+            .unwrap()
+            .build()
+            .unwrap()
+            .explain();
+
+        assert!(explanation.contains("\"Sky\" runs 2 iterations"), "{}", explanation);
+        assert!(explanation.contains("  Rain emits 100000 gammatons"), "{}", explanation);
+        assert!(explanation.contains("depositing humidity (1)."), "{}", explanation);
+        assert!(explanation.contains("concrete.yml"), "{}", explanation);
+        assert!(explanation.contains("after iterations 2, writing:"), "{}", explanation);
+        assert!(explanation.contains("iteration-2.obj"), "{}", explanation);
+    }
+
+    #[test]
+    fn density_is_baked_to_exr_without_quantization() {
+        let dir = temp_dir().join("aitios-exr-density-test");
+        let mut runner = SimulationBuilder::new()
+            .append_spec_fragment_str(&format!(
+                "scenes: [\"tests/assets/sky.obj\"]\nsurfels_by_material:\n  _: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 2.0\neffects:\n  - density:\n      width: 8\n      height: 4\n      tex_pattern: {:?}",
+                dir.join("{substance}-{iteration}.exr")
+            ))
+            .unwrap()
+            .emission_count(100)
+            .only_substances(vec!["humidity"])
+            .build()
+            .unwrap();
+
+        runner.run();
+
+        let baked = read(dir.join("humidity-1.exr")).unwrap();
+        remove_dir_all(&dir).unwrap();
+        // OpenEXR magic number
+        assert_eq!(&[0x76, 0x2f, 0x31, 0x01], &baked[0..4]);
+    }
+
+    #[test]
+    fn exr_and_png_densities_match_surfels() {
+        use exr::prelude::{read_first_rgba_layer_from_file, Vec2};
+
+        let dir = temp_dir().join("aitios-exr-png-density-test");
+        let mut runner = SimulationBuilder::new()
+            .append_spec_fragment_str(&format!(
+                "scenes: [\"tests/assets/sky.obj\"]\nsurfels_by_material:\n  _: \"tests/examples/concrete.yml\"\nsources: [\"tests/examples/rain.yml\"]\nsurfel_distance: 2.0\neffects:\n  - density:\n      width: 8\n      height: 4\n      tex_pattern: {:?}\n  - density:\n      width: 8\n      height: 4\n      tex_pattern: {:?}",
+                dir.join("{substance}.exr"),
+                dir.join("{substance}.png")
+            ))
+            .unwrap()
+            .emission_count(1000)
+            .only_substances(vec!["humidity"])
+            .flat_filtering(true)
+            .build()
+            .unwrap();
+        runner.run();
+
+        let exr = read_first_rgba_layer_from_file(
+            dir.join("humidity.exr"),
+            |resolution, _| vec![0.0; resolution.width() * resolution.height()],
+            |texels: &mut Vec<f32>, Vec2(x, y), (r, _, _, _): (f32, f32, f32, f32)| {
+                texels[y * 8 + x] = r
+            },
+        ).unwrap();
+        let exr = exr.layer_data.channel_data.pixels;
+        let png = tex::open(dir.join("humidity.png")).unwrap().to_rgba();
+        remove_dir_all(&dir).unwrap();
+
+        let (surfel_lookup, island_bleed) = match runner.spec.effects[0] {
+            EffectSpec::Density {
+                surfel_lookup,
+                island_bleed,
+                ..
+            } => (surfel_lookup, island_bleed),
+            _ => unreachable!(),
+        };
+        let humidity = runner
+            .unique_substance_names
+            .iter()
+            .position(|s| s == "humidity")
+            .unwrap();
+        let samples = &runner.sim.surface().samples;
+        let table = runner
+            .surfel_tables
+            .lookup(0, 8, 4, surfel_lookup, island_bleed);
+
+        for (idx, surfels) in table.iter().enumerate() {
+            // Flat filtering takes the nearest surfel of each texel, as PNG
+            // textures baked with aitios-tex did
+            let expected = surfels
+                .iter()
+                .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+                .map(|&(_, surfel)| samples[surfel].data().substances[humidity])
+                .unwrap_or(0.0);
+            assert_eq!(expected, exr[idx], "Wrong concentration at texel {}", idx);
+
+            let gray = png.get_pixel((idx % 8) as u32, (idx / 8) as u32).data[0];
+            let quantized = ((1.0 - expected.max(0.0).min(1.0)) * 255.0).round() as u8;
+            assert_eq!(quantized, gray, "PNG disagrees with EXR at texel {}", idx);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use spec::EffectSpec;
+use std::path::Path;
+
+/// Texture effects of the given effects with outputs redirected into the
+/// given directory, as performed every few iterations with
+/// `--snapshot-every`.
+///
+/// Only density and layer effects are kept, since exporting scenes or
+/// surfels is not needed to see the weathering progress. Each texture is
+/// written into the directory with its file name from the pattern and the
+/// iteration appended, e.g. `weathering_0005.png`. Placeholders in
+/// directories of the patterns are not kept, so file names should contain
+/// `{id}` or `{entity}` if there are multiple entities.
+pub fn snapshot_effects(effects: &[EffectSpec], dir: &Path, iteration: u32) -> Vec<EffectSpec> {
+    effects
+        .iter()
+        .filter_map(|effect| match effect {
+            &EffectSpec::Density { .. } | &EffectSpec::Layer { .. } => Some(effect.clone()),
+            _ => None,
+        })
+        .map(|mut effect| {
+            match effect {
+                EffectSpec::Density {
+                    ref mut tex_pattern,
+                    ref mut obj_pattern,
+                    ref mut mtl_pattern,
+                    ..
+                } => {
+                    *tex_pattern = snapshot_pattern(tex_pattern, dir, iteration);
+                    *obj_pattern = None;
+                    *mtl_pattern = None;
+                }
+                EffectSpec::Layer {
+                    ref mut normal,
+                    ref mut displacement,
+                    ref mut albedo,
+                    ref mut metallicity,
+                    ref mut roughness,
+                    ..
+                } => {
+                    for blend in vec![normal, displacement, albedo, metallicity, roughness]
+                        .into_iter()
+                        .filter_map(|b| b.as_mut())
+                    {
+                        blend.tex_pattern = snapshot_pattern(&blend.tex_pattern, dir, iteration);
+                    }
+                }
+                _ => unreachable!(),
+            }
+            effect
+        })
+        .collect()
+}
+
+/// File name of the given pattern with the iteration appended, in the given
+/// directory.
+pub fn snapshot_pattern(pattern: &str, dir: &Path, iteration: u32) -> String {
+    let pattern = Path::new(pattern);
+    let stem = pattern
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file_name = match pattern.extension() {
+        Some(ext) => format!("{}_{:04}.{}", stem, iteration, ext.to_string_lossy()),
+        None => format!("{}_{:04}", stem, iteration),
+    };
+    dir.join(file_name).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_yaml;
+
+    #[test]
+    fn textures_are_numbered() {
+        let effects: Vec<EffectSpec> = serde_yaml::from_str(
+            "- density:\n    width: 64\n    height: 64\n    tex_pattern: \"out/{iteration}/weathering.png\"\n    obj_pattern: \"out/{iteration}/density.obj\"\n- dump_surfels:\n    obj_pattern: \"surfels/{iteration}.obj\"",
+        ).unwrap();
+
+        let snapshots = snapshot_effects(&effects, Path::new("/tmp/snapshots"), 5);
+
+        assert_eq!(1, snapshots.len());
+        match snapshots[0] {
+            EffectSpec::Density {
+                ref tex_pattern,
+                ref obj_pattern,
+                ..
+            } => {
+                assert_eq!("/tmp/snapshots/weathering_0005.png", tex_pattern);
+                assert_eq!(&None, obj_pattern);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
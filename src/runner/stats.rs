@@ -0,0 +1,115 @@
+use std::io::{self, Write};
+
+/// Total, mean and maximum concentration of a substance over all surfels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SubstanceStats {
+    pub total: f64,
+    pub mean: f64,
+    pub max: f32,
+}
+
+/// Calculates statistics for each of the given number of substances, in the
+/// order of the concentrations of the surfels.
+pub fn substance_stats<'a, I>(substance_count: usize, surfels: I) -> Vec<SubstanceStats>
+where
+    I: IntoIterator<Item = &'a [f32]>,
+{
+    let mut stats = vec![
+        SubstanceStats {
+            total: 0.0,
+            mean: 0.0,
+            max: 0.0,
+        };
+        substance_count
+    ];
+
+    let mut surfel_count = 0;
+    for concentrations in surfels {
+        surfel_count += 1;
+        for (stats, &concentration) in stats.iter_mut().zip(concentrations.iter()) {
+            stats.total += f64::from(concentration);
+            if surfel_count == 1 || concentration > stats.max {
+                stats.max = concentration;
+            }
+        }
+    }
+
+    if surfel_count > 0 {
+        for stats in stats.iter_mut() {
+            stats.mean = stats.total / f64::from(surfel_count);
+        }
+    }
+
+    stats
+}
+
+/// Writes the CSV header for `write_stats_row`, with total, mean and
+/// maximum columns for each substance.
+///
+/// Substances are ordered by name, since the order of substances in the
+/// simulation differs between runs.
+pub fn write_stats_header<W: Write>(sink: &mut W, substance_names: &[String]) -> io::Result<()> {
+    write!(sink, "iteration,timestamp")?;
+    for idx in by_name(substance_names) {
+        let name = &substance_names[idx];
+        for column in ["total", "mean", "max"].iter() {
+            write!(sink, ",{}", csv_field(&format!("{}_{}", name, column)))?;
+        }
+    }
+    writeln!(sink)?;
+    sink.flush()
+}
+
+/// Writes a CSV row with the statistics of an iteration, in the column
+/// order of `write_stats_header`.
+pub fn write_stats_row<W: Write>(
+    sink: &mut W,
+    iteration: u32,
+    timestamp: &str,
+    substance_names: &[String],
+    stats: &[SubstanceStats],
+) -> io::Result<()> {
+    write!(sink, "{},{}", iteration, csv_field(timestamp))?;
+    for idx in by_name(substance_names) {
+        let stats = &stats[idx];
+        write!(sink, ",{},{},{}", stats.total, stats.mean, stats.max)?;
+    }
+    writeln!(sink)?;
+    sink.flush()
+}
+
+fn by_name(substance_names: &[String]) -> Vec<usize> {
+    let mut by_name: Vec<usize> = (0..substance_names.len()).collect();
+    by_name.sort_by_key(|&idx| &substance_names[idx]);
+    by_name
+}
+
+/// Quotes the field if it contains separators, quotes or line breaks.
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        String::from(field)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn columns_ordered_by_name() {
+        let names = vec![String::from("water"), String::from("dark, rust")];
+        let surfels: Vec<&[f32]> = vec![&[1.0, 0.25][..], &[0.5, 0.75][..]];
+        let stats = substance_stats(2, surfels);
+
+        let mut csv = Vec::new();
+        write_stats_header(&mut csv, &names).unwrap();
+        write_stats_row(&mut csv, 1, "2018-06-01T12:00:00+02:00", &names, &stats).unwrap();
+
+        assert_eq!(
+            "iteration,timestamp,\"dark, rust_total\",\"dark, rust_mean\",\"dark, rust_max\",water_total,water_mean,water_max\n1,2018-06-01T12:00:00+02:00,1,0.5,0.75,1.5,0.75,1\n",
+            String::from_utf8(csv).unwrap()
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use runner::RunStatus;
+use std::fmt;
+use std::path::PathBuf;
+
+/// What happened in a call to `SimulationRunner::run`, as logged after a
+/// run and written by `--summary-json`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub status: RunStatus,
+    /// Iterations simulated, not counting iteration 0 and preheat iterations.
+    pub iterations: u32,
+    /// Wall-clock time of the run, including preheating and effects.
+    pub duration_seconds: f64,
+    /// Gammatons traced, including preheat iterations.
+    pub gammatons_traced: u64,
+    /// Output files written during the run, in the order they were first
+    /// written.
+    pub outputs: Vec<PathBuf>,
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Iterations:         {}\n", self.iterations)?;
+        write!(f, "Duration:           {:.3}s\n", self.duration_seconds)?;
+        write!(f, "Gammatons traced:   {}\n", self.gammatons_traced)?;
+        write!(f, "Output files:       {}", self.outputs.len())?;
+        for output in self.outputs.iter() {
+            write!(f, "\n  {}", output.display())?;
+        }
+        Ok(())
+    }
+}
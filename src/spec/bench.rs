@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct BenchSpec {
     pub iterations: Option<PathBuf>,
     pub tracing: Option<PathBuf>,
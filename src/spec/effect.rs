@@ -1,6 +1,12 @@
+use serde::de::value::Error as DeError;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_yaml::{self, Value};
+use std::cell::Cell;
 use std::path::PathBuf;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub enum EffectSpec {
     #[serde(rename = "density")]
     Density {
@@ -12,6 +18,10 @@ pub enum EffectSpec {
         surfel_lookup: SurfelLookup,
         #[serde(default = "default_bleed")]
         island_bleed: usize,
+        /// Paths of the baked textures, written as PNG with densities
+        /// between white and black with `output_bit_depth` bits per
+        /// channel, or as OpenEXR with unquantized float concentrations if
+        /// ending in `.exr`.
         tex_pattern: String,
         obj_pattern: Option<String>,
         mtl_pattern: Option<String>,
@@ -19,6 +29,10 @@ pub enum EffectSpec {
     /// Writes the scene with the effects before the declaration to the
     /// given paths. This should usually the last step, but exporting
     /// before can be useful for debugging.
+    ///
+    /// If the OBJ pattern ends with `.gltf` or `.glb`, the scene is written
+    /// as glTF 2.0 with all textures embedded instead and the MTL pattern
+    /// is not needed.
     #[serde(rename = "export")]
     Export {
         obj_pattern: Option<String>,
@@ -59,7 +73,83 @@ pub enum EffectSpec {
     DumpSurfels { obj_pattern: String },
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl EffectSpec {
+    /// YAML tags and one-line descriptions of all effect kinds, as listed
+    /// by `--list-effects`.
+    ///
+    /// The tags are the variant names declared by the derived deserializer,
+    /// so every kind the spec deserializer accepts is listed.
+    pub fn kinds() -> Vec<(String, &'static str)> {
+        variant_tags()
+            .iter()
+            .map(|&tag| (tag.to_string(), kind_description(tag)))
+            .collect()
+    }
+
+    /// Tag of this kind of effect in YAML specs, e.g. `density`.
+    pub fn tag(&self) -> String {
+        match serde_yaml::to_value(self) {
+            Ok(Value::Mapping(ref mapping)) => match mapping.iter().next() {
+                Some((&Value::String(ref tag), _)) => tag.clone(),
+                _ => unreachable!("Effects are serialized as a mapping from tag to fields"),
+            },
+            _ => unreachable!("Effects are serialized as a mapping from tag to fields"),
+        }
+    }
+
+    /// One-line description of this kind of effect.
+    pub fn description(&self) -> &'static str {
+        kind_description(&self.tag())
+    }
+}
+
+/// Tags of all effect kinds, obtained by letting the derived deserializer
+/// of effects declare its variants to a deserializer that only records them.
+fn variant_tags() -> &'static [&'static str] {
+    struct Variants<'a>(&'a Cell<&'static [&'static str]>);
+
+    impl<'de, 'a> Deserializer<'de> for Variants<'a> {
+        type Error = DeError;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, DeError> {
+            Err(de::Error::custom("Expected effects to be deserialized as an enum"))
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            variants: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, DeError> {
+            self.0.set(variants);
+            Err(de::Error::custom("Only the variants are recorded"))
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map struct
+            identifier ignored_any
+        }
+    }
+
+    let variants = Cell::new(&[][..]);
+    // Always fails after the variants have been recorded
+    let _ = EffectSpec::deserialize(Variants(&variants));
+    variants.get()
+}
+
+fn kind_description(tag: &str) -> &'static str {
+    match tag {
+        "density" => "Writes a texture with the concentration of each substance per entity.",
+        "export" => "Writes the scene with the results of the effects before it as OBJ or glTF.",
+        "layer" => "Blends sample textures into the maps of materials, guided by a substance.",
+        "dump_surfels" => "Writes the surfels as points into an OBJ file.",
+        _ => "",
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Blend {
     /// If specified, use this output texture width instead
     /// of the width of the original map from the material or
@@ -85,11 +175,12 @@ pub struct Blend {
     /// Note that texture samples may also be partly transparent.
     #[serde(default = "default_influence")]
     pub influence: f32,
-    /// {entity} {iteration} {id} {substance}
+    /// {entity} {iteration} {id} {substance} {datetime} {spec_name}
     pub tex_pattern: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Stop {
     /// Path to the texture sample.
     pub sample: Option<PathBuf>,
@@ -99,7 +190,7 @@ pub struct Stop {
     pub cenith: f32,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, JsonSchema)]
 #[serde(untagged)]
 pub enum SurfelLookup {
     Nearest { count: usize },
@@ -117,3 +208,24 @@ fn default_influence() -> f32 {
 fn default_surfel_lookup() -> SurfelLookup {
     SurfelLookup::Nearest { count: 6 }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kinds_have_deserializable_tags() {
+        let tags: Vec<String> = EffectSpec::kinds().into_iter().map(|(tag, _)| tag).collect();
+        assert_eq!(vec!["density", "export", "layer", "dump_surfels"], tags);
+
+        let export: EffectSpec = serde_yaml::from_str("export: {}").unwrap();
+        assert_eq!("export", export.tag());
+    }
+
+    #[test]
+    fn every_kind_is_described() {
+        for (tag, description) in EffectSpec::kinds() {
+            assert!(!description.is_empty(), "No description for {}", tag);
+        }
+    }
+}
@@ -7,7 +7,7 @@ mod transport;
 
 pub use self::bench::BenchSpec;
 pub use self::effect::{Blend, EffectSpec, Stop, SurfelLookup};
-pub use self::sim::SimulationSpec;
+pub use self::sim::{SimulationSpec, SPEC_VERSION};
 pub use self::source::TonSourceSpec;
 pub use self::surfel::{SurfelRuleSpec, SurfelSpec};
 pub use self::transport::Transport;
@@ -1,10 +1,24 @@
+use schemars::schema::RootSchema;
 use spec::{BenchSpec, EffectSpec, SurfelRuleSpec, Transport};
 use std::collections::HashMap;
 use std::default::Default;
 use std::path::PathBuf;
 
-#[derive(Debug, Deserialize)]
+/// Version of the spec format understood by this build, to be increased
+/// whenever the format changes incompatibly.
+pub const SPEC_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct SimulationSpec {
+    /// Version of the spec format the spec was written for. Specs without
+    /// a version are treated as written for version 1.
+    pub version: Option<u32>,
+    /// Other spec files to merge into this one before deserialization,
+    /// relative to this one. Keys of this spec take precedence. Always
+    /// empty after loading.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<PathBuf>,
     #[serde(default)]
     pub name: String,
     #[serde(default)]
@@ -27,13 +41,33 @@ pub struct SimulationSpec {
     pub benchmark: Option<BenchSpec>,
     pub transport: Option<Transport>,
     pub flat_filtering: Option<bool>,
+    /// Bits per channel of density textures written as PNG, either 8 or
+    /// 16. Defaults to 8.
+    pub output_bit_depth: Option<u8>,
     #[serde(default)]
     pub rules: Vec<SurfelRuleSpec>,
+    /// Optional image in UV space restricting weathering to the
+    /// triangles where the mask is bright. Surfels outside of the
+    /// mask keep their initial concentrations.
+    pub region_mask: Option<PathBuf>,
+}
+
+impl SimulationSpec {
+    /// JSON Schema of simulation specs, as printed by `--dump-config-schema`
+    /// for editors to validate and complete specs.
+    ///
+    /// The schema is derived from the spec types, including their serde
+    /// attributes, so it always matches what the deserializer accepts.
+    pub fn json_schema() -> RootSchema {
+        schema_for!(SimulationSpec)
+    }
 }
 
 impl Default for SimulationSpec {
     fn default() -> Self {
         Self {
+            version: None,
+            include: Vec::new(),
             name: String::new(),
             description: String::new(),
             scenes: Vec::new(),
@@ -47,7 +81,9 @@ impl Default for SimulationSpec {
             benchmark: None,
             transport: None,
             flat_filtering: None,
+            output_bit_depth: None,
             rules: Vec::new(),
+            region_mask: None,
         }
     }
 }
@@ -55,6 +91,7 @@ impl Default for SimulationSpec {
 #[cfg(test)]
 mod test {
     use super::*;
+    use serde_json;
     use serde_yaml;
     use std::fs::File;
 
@@ -101,4 +138,17 @@ mod test {
             _ => (),
         }
     }
+
+    #[test]
+    fn json_schema_lists_top_level_properties() {
+        let schema = serde_json::to_value(SimulationSpec::json_schema()).unwrap();
+        let properties = schema["properties"]
+            .as_object()
+            .expect("Expected spec schema to have properties");
+
+        for property in &["scenes", "sources", "surfels_by_material", "effects", "iterations"] {
+            assert!(properties.contains_key(*property), "Missing {}", property);
+        }
+        assert_eq!(false, schema["additionalProperties"]);
+    }
 }
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct TonSourceSpec {
     name: String,
     description: String,
@@ -24,6 +25,12 @@ pub struct TonSourceSpec {
     pub flow_direction: Option<[f32; 3]>,
 }
 
+impl TonSourceSpec {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 fn is_diffuse_default() -> bool {
     false
 }
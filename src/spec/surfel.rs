@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct SurfelSpec {
     pub name: String,
     description: String,
@@ -13,13 +14,14 @@ pub struct SurfelSpec {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct TonReflectance {
     pub delta_straight: f32,
     pub delta_parabolic: f32,
     pub delta_flow: f32,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 #[serde(untagged)]
 pub enum SurfelRuleSpec {
     Transfer {